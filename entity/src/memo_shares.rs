@@ -0,0 +1,22 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "memo_shares")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub memo_id: Uuid,
+    #[sea_orm(unique)]
+    pub slug: String,
+    pub max_views: Option<i32>,
+    pub view_count: i32,
+    pub last_viewed_at: Option<DateTime>,
+    pub expires_at: Option<DateTime>,
+    pub expired_at: Option<DateTime>,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
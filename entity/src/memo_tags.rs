@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "memo_tags")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub memo_id: Uuid,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub tag_id: Uuid,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::tags::Entity",
+        from = "Column::TagId",
+        to = "super::tags::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Tags,
+    #[sea_orm(
+        belongs_to = "super::voice_memos1::Entity",
+        from = "Column::MemoId",
+        to = "super::voice_memos1::Column::Id",
+        on_delete = "Cascade"
+    )]
+    VoiceMemos1,
+}
+
+impl Related<super::tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Tags.def()
+    }
+}
+
+impl Related<super::voice_memos1::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VoiceMemos1.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,17 @@
+pub mod ai_usage;
+pub mod api_tokens;
+pub mod deleted_keys;
+pub mod folders;
+pub mod helper_app;
+pub mod maintenance_runs;
+pub mod memo_links;
+pub mod memo_shares;
+pub mod memo_tags;
+pub mod password_resets;
+pub mod revoked_tokens;
+pub mod sessions;
+pub mod tags;
+pub mod user_avatars;
+pub mod user_preferences;
+pub mod users;
+pub mod voice_memos1;
@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "tags")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::memo_tags::Entity")]
+    MemoTags,
+}
+
+impl Related<super::memo_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MemoTags.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,30 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub username: String,
+    #[sea_orm(unique)]
+    pub email: String,
+    pub password: String,
+    pub created_at: DateTime,
+    pub role: String,
+    pub sandbox_ai: bool,
+    pub token_version: i32,
+    pub failed_attempts: i32,
+    pub locked_until: Option<DateTime>,
+    pub pending_email: Option<String>,
+    pub email_change_token_hash: Option<String>,
+    pub email_change_expires_at: Option<DateTime>,
+    pub suspended_at: Option<DateTime>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub suspension_reason: Option<String>,
+    pub is_verified: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
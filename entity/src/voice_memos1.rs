@@ -0,0 +1,47 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "voice_memos1")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub title: String,
+    #[sea_orm(column_type = "VarBinary(StringLen::None)", nullable)]
+    pub audio_blob: Option<Vec<u8>>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub transcript: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub translate: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub summary: Option<String>,
+    /// JSON array of the tag names; `memo_tags` is what queries use
+    #[sea_orm(column_type = "Text", nullable)]
+    pub tags: Option<String>,
+    pub duration: String,
+    pub created_at: DateTime,
+    pub transcript_ai_generated: bool,
+    pub translate_ai_generated: bool,
+    pub summary_ai_generated: bool,
+    pub deleted_at: Option<DateTime>,
+    pub updated_at: DateTime,
+    pub version: i32,
+    pub is_favorite: bool,
+    pub folder_id: Option<Uuid>,
+    pub duration_seconds: Option<i32>,
+    pub audio_key: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::memo_tags::Entity")]
+    MemoTags,
+}
+
+impl Related<super::memo_tags::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MemoTags.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
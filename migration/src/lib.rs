@@ -2,6 +2,43 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
 mod m20250725_052107_add_helper_status;
+mod m20250802_091500_create_memo_links;
+mod m20250804_120000_add_user_role;
+mod m20250806_083000_add_ai_generated_flags;
+mod m20250808_101000_create_tags;
+mod m20250810_140000_create_deleted_keys;
+mod m20250812_090000_add_memo_deleted_at;
+mod m20250814_110000_lowercase_user_emails;
+mod m20250816_150000_create_memo_shares;
+mod m20250818_100000_unique_helper_app_user;
+mod m20250820_090000_add_user_sandbox_ai;
+mod m20250822_100000_add_memo_updated_at;
+mod m20250824_080000_share_expiry_sweep;
+mod m20250826_093000_add_memo_version;
+mod m20250828_120000_create_api_tokens;
+mod m20250830_090000_create_sessions;
+mod m20250901_100000_create_user_avatars;
+mod m20250902_090000_add_user_token_version;
+mod m20250904_110000_add_user_lockout;
+mod m20250906_100000_add_user_pending_email;
+mod m20250908_090000_create_ai_usage;
+mod m20250910_090000_add_user_suspension;
+mod m20250912_090000_create_user_preferences;
+mod m20250914_090000_add_preference_timezone;
+mod m20250916_090000_add_memo_favorite;
+mod m20250918_090000_add_memo_search_vector;
+mod m20250920_090000_add_user_verified;
+mod m20250922_090000_create_password_resets;
+mod m20250924_090000_create_revoked_tokens;
+mod m20250926_090000_create_folders;
+mod m20250928_090000_unique_usernames;
+mod m20250930_090000_add_memo_duration_seconds;
+mod m20251002_090000_add_memo_audio_key;
+mod m20251004_090000_session_and_email_change_expiry;
+
+// Re-run by tests over rows they seed in the old, un-normalized form
+pub use m20250814_110000_lowercase_user_emails::Migration as LowercaseUserEmails;
+pub use m20250928_090000_unique_usernames::Migration as UniqueUsernames;
 
 pub struct Migrator;
 
@@ -11,6 +48,39 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20250725_052107_add_helper_status::Migration),
+            Box::new(m20250802_091500_create_memo_links::Migration),
+            Box::new(m20250804_120000_add_user_role::Migration),
+            Box::new(m20250806_083000_add_ai_generated_flags::Migration),
+            Box::new(m20250808_101000_create_tags::Migration),
+            Box::new(m20250810_140000_create_deleted_keys::Migration),
+            Box::new(m20250812_090000_add_memo_deleted_at::Migration),
+            Box::new(m20250814_110000_lowercase_user_emails::Migration),
+            Box::new(m20250816_150000_create_memo_shares::Migration),
+            Box::new(m20250818_100000_unique_helper_app_user::Migration),
+            Box::new(m20250820_090000_add_user_sandbox_ai::Migration),
+            Box::new(m20250822_100000_add_memo_updated_at::Migration),
+            Box::new(m20250824_080000_share_expiry_sweep::Migration),
+            Box::new(m20250826_093000_add_memo_version::Migration),
+            Box::new(m20250828_120000_create_api_tokens::Migration),
+            Box::new(m20250830_090000_create_sessions::Migration),
+            Box::new(m20250901_100000_create_user_avatars::Migration),
+            Box::new(m20250902_090000_add_user_token_version::Migration),
+            Box::new(m20250904_110000_add_user_lockout::Migration),
+            Box::new(m20250906_100000_add_user_pending_email::Migration),
+            Box::new(m20250908_090000_create_ai_usage::Migration),
+            Box::new(m20250910_090000_add_user_suspension::Migration),
+            Box::new(m20250912_090000_create_user_preferences::Migration),
+            Box::new(m20250914_090000_add_preference_timezone::Migration),
+            Box::new(m20250916_090000_add_memo_favorite::Migration),
+            Box::new(m20250918_090000_add_memo_search_vector::Migration),
+            Box::new(m20250920_090000_add_user_verified::Migration),
+            Box::new(m20250922_090000_create_password_resets::Migration),
+            Box::new(m20250924_090000_create_revoked_tokens::Migration),
+            Box::new(m20250926_090000_create_folders::Migration),
+            Box::new(m20250928_090000_unique_usernames::Migration),
+            Box::new(m20250930_090000_add_memo_duration_seconds::Migration),
+            Box::new(m20251002_090000_add_memo_audio_key::Migration),
+            Box::new(m20251004_090000_session_and_email_change_expiry::Migration),
         ]
     }
 }
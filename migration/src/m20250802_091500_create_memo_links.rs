@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("memo_links"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("from_memo_id")).uuid().not_null())
+                    .col(ColumnDef::new(Alias::new("to_memo_id")).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Alias::new("created_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    // Deleting either memo removes the link in both directions
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("memo_links"), Alias::new("from_memo_id"))
+                            .to(Alias::new("voice_memos1"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("memo_links"), Alias::new("to_memo_id"))
+                            .to(Alias::new("voice_memos1"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_memo_links_pair")
+                    .table(Alias::new("memo_links"))
+                    .col(Alias::new("from_memo_id"))
+                    .col(Alias::new("to_memo_id"))
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_memo_links_to_memo_id")
+                    .table(Alias::new("memo_links"))
+                    .col(Alias::new("to_memo_id"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alias::new("memo_links")).to_owned())
+            .await
+    }
+}
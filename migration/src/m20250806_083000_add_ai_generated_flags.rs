@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .add_column(
+                        ColumnDef::new(Alias::new("transcript_ai_generated"))
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(Alias::new("translate_ai_generated"))
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column(
+                        ColumnDef::new(Alias::new("summary_ai_generated"))
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .drop_column(Alias::new("transcript_ai_generated"))
+                    .drop_column(Alias::new("translate_ai_generated"))
+                    .drop_column(Alias::new("summary_ai_generated"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,124 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // tags table (one row per distinct tag name per user)
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("tags"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("user_id")).uuid().not_null())
+                    .col(ColumnDef::new(Alias::new("name")).string().not_null())
+                    .col(
+                        ColumnDef::new(Alias::new("created_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("tags"), Alias::new("user_id"))
+                            .to(Alias::new("users"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_tags_user_id_name")
+                    .table(Alias::new("tags"))
+                    .col(Alias::new("user_id"))
+                    .col(Alias::new("name"))
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // memo_tags join table
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("memo_tags"))
+                    .if_not_exists()
+                    .col(ColumnDef::new(Alias::new("memo_id")).uuid().not_null())
+                    .col(ColumnDef::new(Alias::new("tag_id")).uuid().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(Alias::new("memo_id"))
+                            .col(Alias::new("tag_id")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("memo_tags"), Alias::new("memo_id"))
+                            .to(Alias::new("voice_memos1"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("memo_tags"), Alias::new("tag_id"))
+                            .to(Alias::new("tags"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_memo_tags_tag_id")
+                    .table(Alias::new("memo_tags"))
+                    .col(Alias::new("tag_id"))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Backfill from the JSON-encoded voice_memos1.tags column
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            r#"INSERT INTO tags (id, user_id, name, created_at)
+               SELECT gen_random_uuid(), t.user_id, t.name, NOW()
+               FROM (
+                   SELECT DISTINCT m.user_id, tag.value AS name
+                   FROM voice_memos1 m, json_array_elements_text(m.tags::json) AS tag(value)
+                   WHERE m.tags IS NOT NULL AND m.tags <> ''
+               ) t
+               ON CONFLICT (user_id, name) DO NOTHING"#,
+        )
+        .await?;
+        db.execute_unprepared(
+            r#"INSERT INTO memo_tags (memo_id, tag_id)
+               SELECT DISTINCT m.id, t.id
+               FROM voice_memos1 m, json_array_elements_text(m.tags::json) AS tag(value)
+               JOIN tags t ON t.name = tag.value
+               WHERE m.tags IS NOT NULL AND m.tags <> '' AND t.user_id = m.user_id
+               ON CONFLICT DO NOTHING"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alias::new("memo_tags")).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Alias::new("tags")).to_owned())
+            .await?;
+        Ok(())
+    }
+}
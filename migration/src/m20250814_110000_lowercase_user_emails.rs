@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Refuse to merge accounts silently: two rows differing only by case must be resolved by hand
+        let duplicates = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT LOWER(TRIM(email)) AS email FROM users GROUP BY LOWER(TRIM(email)) HAVING COUNT(*) > 1",
+            ))
+            .await?;
+        if !duplicates.is_empty() {
+            let emails: Vec<String> = duplicates
+                .iter()
+                .filter_map(|row| row.try_get::<String>("", "email").ok())
+                .collect();
+            return Err(DbErr::Migration(format!(
+                "Cannot lowercase emails, these would collide: {}",
+                emails.join(", ")
+            )));
+        }
+
+        db.execute_unprepared("UPDATE users SET email = LOWER(TRIM(email)) WHERE email <> LOWER(TRIM(email))")
+            .await?;
+        db.execute_unprepared("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_lower ON users (LOWER(email))")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The original casing is not recoverable; only the index is removed
+        manager
+            .get_connection()
+            .execute_unprepared("DROP INDEX IF EXISTS idx_users_email_lower")
+            .await?;
+        Ok(())
+    }
+}
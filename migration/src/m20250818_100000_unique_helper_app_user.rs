@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Keep only the most recent row per user before enforcing uniqueness
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"DELETE FROM "helperApp" a
+                   USING "helperApp" b
+                   WHERE a.user_id = b.user_id
+                     AND (a.timestamp, a.id) < (b.timestamp, b.id)"#,
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_helper_app_user_id")
+                    .table(Alias::new("helperApp"))
+                    .col(Alias::new("user_id"))
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_helper_app_user_id")
+                    .table(Alias::new("helperApp"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
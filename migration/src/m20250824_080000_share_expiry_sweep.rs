@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("memo_shares"))
+                    .add_column(ColumnDef::new(Alias::new("expired_at")).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Partial indexes so the sweep only ever scans shares that are still live
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            r#"CREATE INDEX idx_memo_shares_pending_time_expiry ON memo_shares (expires_at)
+               WHERE expires_at IS NOT NULL AND expired_at IS NULL"#,
+        )
+        .await?;
+        db.execute_unprepared(
+            r#"CREATE INDEX idx_memo_shares_pending_view_expiry ON memo_shares (id)
+               WHERE max_views IS NOT NULL AND expired_at IS NULL"#,
+        )
+        .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("maintenance_runs"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("job")).string().not_null())
+                    .col(
+                        ColumnDef::new(Alias::new("affected"))
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(Alias::new("summary")).text().null())
+                    .col(ColumnDef::new(Alias::new("error")).text().null())
+                    .col(
+                        ColumnDef::new(Alias::new("ran_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_maintenance_runs_job_ran_at")
+                    .table(Alias::new("maintenance_runs"))
+                    .col(Alias::new("job"))
+                    .col(Alias::new("ran_at"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alias::new("maintenance_runs")).to_owned())
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_memo_shares_pending_time_expiry").await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_memo_shares_pending_view_expiry").await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("memo_shares"))
+                    .drop_column(Alias::new("expired_at"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
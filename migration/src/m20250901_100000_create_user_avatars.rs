@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // One avatar per user; uploading again overwrites the row
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("user_avatars"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("user_id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("content_type")).string().not_null())
+                    .col(ColumnDef::new(Alias::new("data")).binary().not_null())
+                    .col(ColumnDef::new(Alias::new("etag")).string().not_null())
+                    .col(
+                        ColumnDef::new(Alias::new("updated_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("user_avatars"), Alias::new("user_id"))
+                            .to(Alias::new("users"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alias::new("user_avatars")).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // An email change is staged here until it is confirmed from the new address
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("users"))
+                    .add_column(ColumnDef::new(Alias::new("pending_email")).string().null())
+                    .add_column(ColumnDef::new(Alias::new("email_change_token_hash")).string().null())
+                    .add_column(ColumnDef::new(Alias::new("email_change_expires_at")).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_users_email_change_token_hash")
+                    .table(Alias::new("users"))
+                    .col(Alias::new("email_change_token_hash"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_users_email_change_token_hash")
+                    .table(Alias::new("users"))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("users"))
+                    .drop_column(Alias::new("pending_email"))
+                    .drop_column(Alias::new("email_change_token_hash"))
+                    .drop_column(Alias::new("email_change_expires_at"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // At most one row per user; users without one get the defaults
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("user_preferences"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("user_id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("default_translate_lang")).string().null())
+                    .col(ColumnDef::new(Alias::new("summary_style")).string().null())
+                    .col(ColumnDef::new(Alias::new("preferred_model")).string().null())
+                    .col(
+                        ColumnDef::new(Alias::new("auto_process"))
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(Alias::new("updated_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("user_preferences"), Alias::new("user_id"))
+                            .to(Alias::new("users"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alias::new("user_preferences")).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // IANA name such as `Europe/Berlin`; NULL means UTC
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("user_preferences"))
+                    .add_column(ColumnDef::new(Alias::new("timezone")).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("user_preferences"))
+                    .drop_column(Alias::new("timezone"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
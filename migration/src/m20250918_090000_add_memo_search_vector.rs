@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // tsvector only exists on Postgres; elsewhere `/search_memos/fts` falls back to LIKE
+        if manager.get_database_backend() != DatabaseBackend::Postgres {
+            return Ok(());
+        }
+
+        // Kept up to date by Postgres on every write, so no handler has to remember it
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            r#"ALTER TABLE voice_memos1 ADD COLUMN search_vector tsvector
+               GENERATED ALWAYS AS (
+                   to_tsvector('english', coalesce(title, '') || ' ' || coalesce(transcript, '') || ' ' || coalesce(summary, ''))
+               ) STORED"#,
+        )
+        .await?;
+        db.execute_unprepared("CREATE INDEX idx_voice_memos1_search_vector ON voice_memos1 USING GIN (search_vector)")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DatabaseBackend::Postgres {
+            return Ok(());
+        }
+
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_voice_memos1_search_vector").await?;
+        db.execute_unprepared("ALTER TABLE voice_memos1 DROP COLUMN IF EXISTS search_vector").await?;
+
+        Ok(())
+    }
+}
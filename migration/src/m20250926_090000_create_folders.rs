@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alias::new("folders"))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Alias::new("id"))
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Alias::new("user_id")).uuid().not_null())
+                    .col(ColumnDef::new(Alias::new("name")).string().not_null())
+                    .col(
+                        ColumnDef::new(Alias::new("created_at"))
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Alias::new("folders"), Alias::new("user_id"))
+                            .to(Alias::new("users"), Alias::new("id"))
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One folder of a name per user
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_folders_user_id_name")
+                    .table(Alias::new("folders"))
+                    .col(Alias::new("user_id"))
+                    .col(Alias::new("name"))
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Deleting a folder leaves its memos outside any folder
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .add_column(ColumnDef::new(Alias::new("folder_id")).uuid().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_voice_memos1_folder_id")
+                            .from_tbl(Alias::new("voice_memos1"))
+                            .from_col(Alias::new("folder_id"))
+                            .to_tbl(Alias::new("folders"))
+                            .to_col(Alias::new("id"))
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_voice_memos1_folder_id")
+                    .table(Alias::new("voice_memos1"))
+                    .col(Alias::new("folder_id"))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .drop_foreign_key(Alias::new("fk_voice_memos1_folder_id"))
+                    .drop_column(Alias::new("folder_id"))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Alias::new("folders")).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Usernames are matched ignoring case at login, so they must be unique that way too. Existing
+        // duplicates are not renamed behind their owners' backs; they must be resolved by hand first.
+        let duplicates = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT LOWER(TRIM(username)) AS username FROM users GROUP BY LOWER(TRIM(username)) HAVING COUNT(*) > 1",
+            ))
+            .await?;
+        if !duplicates.is_empty() {
+            let usernames: Vec<String> = duplicates
+                .iter()
+                .filter_map(|row| row.try_get::<String>("", "username").ok())
+                .collect();
+            return Err(DbErr::Migration(format!(
+                "Cannot make usernames unique, these are taken more than once: {}",
+                usernames.join(", ")
+            )));
+        }
+
+        // Casing is kept for display; only surrounding whitespace goes
+        db.execute_unprepared("UPDATE users SET username = TRIM(username) WHERE username <> TRIM(username)")
+            .await?;
+        db.execute_unprepared("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username_lower ON users (LOWER(username))")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Trimmed whitespace is not recoverable; only the index is removed
+        manager
+            .get_connection()
+            .execute_unprepared("DROP INDEX IF EXISTS idx_users_username_lower")
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Null when the client sent a duration that can't be read as a length
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .add_column(ColumnDef::new(Alias::new("duration_seconds")).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Backfills the same `SS`, `M:SS` and `H:MM:SS` forms the API parses. Elsewhere existing memos
+        // get their seconds the next time they are saved.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .get_connection()
+                .execute_unprepared(
+                    r#"UPDATE voice_memos1 SET duration_seconds = CASE
+                           WHEN src.d ~ '^\d{1,9}(\.\d+)?$'
+                               THEN ROUND(src.d::numeric)
+                           WHEN src.d ~ '^\d{1,6}:\d{1,2}(\.\d+)?$'
+                               THEN split_part(src.d, ':', 1)::int * 60 + ROUND(split_part(src.d, ':', 2)::numeric)
+                           WHEN src.d ~ '^\d{1,4}:\d{1,2}:\d{1,2}(\.\d+)?$'
+                               THEN split_part(src.d, ':', 1)::int * 3600 + split_part(src.d, ':', 2)::int * 60
+                                   + ROUND(split_part(src.d, ':', 3)::numeric)
+                       END
+                       FROM (SELECT id, TRIM(duration) AS d FROM voice_memos1) AS src
+                       WHERE voice_memos1.id = src.id"#,
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("voice_memos1"))
+                    .drop_column(Alias::new("duration_seconds"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
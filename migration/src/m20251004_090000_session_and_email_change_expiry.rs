@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("sessions"))
+                    .add_column(ColumnDef::new(Alias::new("expires_at")).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing sessions get the longest default token lifetime (30 days, "remember me")
+        let db = manager.get_connection();
+        db.execute_unprepared("UPDATE sessions SET expires_at = created_at + interval '30 days'")
+            .await?;
+        db.execute_unprepared("ALTER TABLE sessions ALTER COLUMN expires_at SET NOT NULL")
+            .await?;
+
+        // The sweeps only scan rows that can expire: every session, but only users with a pending change
+        db.execute_unprepared("CREATE INDEX idx_sessions_expires_at ON sessions (expires_at)")
+            .await?;
+        db.execute_unprepared(
+            r#"CREATE INDEX idx_users_pending_email_expiry ON users (email_change_expires_at)
+               WHERE email_change_expires_at IS NOT NULL"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_users_pending_email_expiry").await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_sessions_expires_at").await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Alias::new("sessions"))
+                    .drop_column(Alias::new("expires_at"))
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,299 @@
+use std::env;
+
+use chrono::{NaiveDateTime, Utc};
+use poem::{web::Data, Result, error::{BadRequest, Forbidden, NotFound}};
+use poem_openapi::{payload::Json, param::{Path, Query}, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::Expr, DatabaseConnection, DbErr};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use std::error::Error as StdError;
+use std::fmt;
+
+use entity::{users, voice_memos1};
+use crate::api::auth::SessionAuth;
+use crate::api::crypto::Crypto;
+use crate::api::memo_api_store_ops::reencrypt_user_keys;
+use crate::api::storage::AudioStore;
+use crate::api::user::{email_matches, normalize_email};
+use crate::scheduler::{purge_trash, TrashRetention};
+
+// --- Custom Error for Poem ---
+#[derive(Debug)]
+struct ApiError(String);
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl StdError for ApiError {}
+
+
+// --- Constants ---
+pub const ROLE_ADMIN: &str = "admin";
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+// --- API Structs ---
+
+#[derive(Object, Serialize)]
+pub struct AdminUser {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: String,
+    #[oai(skip_serializing_if_is_none)]
+    pub suspended_at: Option<String>,
+    #[oai(skip_serializing_if_is_none)]
+    pub suspension_reason: Option<String>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct SuspendUserPayload {
+    /// Why, for other admins; never shown to the user
+    pub reason: Option<String>,
+}
+
+#[derive(Object, Serialize)]
+pub struct AdminUserList {
+    pub users: Vec<AdminUser>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+#[derive(Object, Serialize)]
+pub struct AdminUserStats {
+    pub user_id: String,
+    pub memo_count: i64,
+    /// Audio kept in the database; recordings in object storage aren't counted
+    pub audio_bytes: i64,
+    /// Memos whose duration couldn't be read count as zero
+    pub total_duration_seconds: i64,
+}
+
+#[derive(Object, Serialize)]
+pub struct ReencryptKeysResponse {
+    pub user_id: String,
+    /// Stored values that were moved to the current encryption key
+    pub reencrypted: u64,
+}
+
+#[derive(Object, Serialize)]
+pub struct PurgeTrashResponse {
+    /// Memos permanently deleted by this run
+    pub purged: u64,
+    pub retention_days: u64,
+}
+
+// --- API Definition ---
+pub struct AdminApi;
+
+#[OpenApi]
+impl AdminApi {
+    /// List users, optionally filtered by an email substring (ignoring case)
+    #[oai(path = "/admin/users", method = "get")]
+    async fn list_users(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        page: Query<Option<u64>>,
+        per_page: Query<Option<u64>>,
+        email: Query<Option<String>>,
+    ) -> Result<Json<AdminUserList>> {
+        require_admin(auth)?;
+
+        let page = page.0.unwrap_or(1).max(1);
+        let per_page = per_page.0.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let mut query = users::Entity::find().order_by_asc(users::Column::CreatedAt);
+        // Stored emails are normalized, so the search term is too
+        if let Some(email) = email.0.as_deref().map(normalize_email).filter(|e| !e.is_empty()) {
+            query = query.filter(users::Column::Email.contains(email));
+        }
+
+        let paginator = query.paginate(db.0, per_page);
+        let total = paginator.num_items().await.map_err(poem::error::InternalServerError)?;
+        let users = paginator
+            .fetch_page(page - 1)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .into_iter()
+            .map(AdminUser::from)
+            .collect();
+
+        Ok(Json(AdminUserList { users, page, per_page, total }))
+    }
+
+    /// Memo count, total stored audio size and total recorded length for a user
+    #[oai(path = "/admin/users/:id/stats", method = "get")]
+    async fn user_stats(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(id): Path<String>,
+    ) -> Result<Json<AdminUserStats>> {
+        require_admin(auth)?;
+
+        let user_id = Uuid::parse_str(&id).map_err(BadRequest)?;
+        users::Entity::find_by_id(user_id)
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .ok_or_else(|| NotFound(ApiError("User not found".to_string())))?;
+
+        let (memo_count, audio_bytes, total_duration_seconds) = voice_memos1::Entity::find()
+            .select_only()
+            .column_as(voice_memos1::Column::Id.count(), "memo_count")
+            .column_as(Expr::cust("COALESCE(SUM(OCTET_LENGTH(audio_blob)), 0)::BIGINT"), "audio_bytes")
+            .column_as(Expr::cust("COALESCE(SUM(duration_seconds), 0)::BIGINT"), "total_duration_seconds")
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .into_tuple::<(i64, i64, i64)>()
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .unwrap_or((0, 0, 0));
+
+        Ok(Json(AdminUserStats { user_id: user_id.to_string(), memo_count, audio_bytes, total_duration_seconds }))
+    }
+
+    /// Re-encrypt a user's stored API keys with the current encryption key, e.g. after a key rotation
+    #[oai(path = "/admin/users/:id/reencrypt_keys", method = "post")]
+    async fn reencrypt_keys(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
+        Path(id): Path<String>,
+    ) -> Result<Json<ReencryptKeysResponse>> {
+        require_admin(auth)?;
+
+        let user_id = Uuid::parse_str(&id).map_err(BadRequest)?;
+        let reencrypted = reencrypt_user_keys(db.0, crypto.0, user_id)
+            .await
+            .map_err(|e| poem::error::InternalServerError(ApiError(e)))?;
+
+        Ok(Json(ReencryptKeysResponse { user_id: user_id.to_string(), reencrypted }))
+    }
+
+    /// Suspend a user: login is refused and every issued token stops working. Memos and keys are kept.
+    #[oai(path = "/admin/users/:id/suspend", method = "post")]
+    async fn suspend_user(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(id): Path<String>,
+        Json(payload): Json<SuspendUserPayload>,
+    ) -> Result<Json<AdminUser>> {
+        let admin = require_admin(auth)?;
+
+        let user_id = Uuid::parse_str(&id).map_err(BadRequest)?;
+        if user_id == admin.id {
+            return Err(BadRequest(ApiError("Admins can't suspend themselves".to_string())));
+        }
+
+        let reason = payload.reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+        let user = set_suspension(db.0, user_id, Some(Utc::now().naive_utc()), reason).await?;
+        tracing::info!(admin_id = %admin.id, user_id = %user_id, "Suspended user");
+
+        Ok(Json(user))
+    }
+
+    /// Lift a suspension. Tokens issued before it work again if they haven't expired.
+    #[oai(path = "/admin/users/:id/unsuspend", method = "post")]
+    async fn unsuspend_user(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(id): Path<String>,
+    ) -> Result<Json<AdminUser>> {
+        let admin = require_admin(auth)?;
+
+        let user_id = Uuid::parse_str(&id).map_err(BadRequest)?;
+        let user = set_suspension(db.0, user_id, None, None).await?;
+        tracing::info!(admin_id = %admin.id, user_id = %user_id, "Unsuspended user");
+
+        Ok(Json(user))
+    }
+
+    /// Run the trash purge now instead of waiting for the next interval, e.g. to test retention settings
+    #[oai(path = "/admin/maintenance/purge_trash", method = "post")]
+    async fn purge_trash_now(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+    ) -> Result<Json<PurgeTrashResponse>> {
+        let admin = require_admin(auth)?;
+
+        // Already validated in main
+        let retention = TrashRetention::from_env().map_err(|e| poem::error::InternalServerError(ApiError(e)))?;
+        let purged = purge_trash(db.0, store.0, retention).await.map_err(poem::error::InternalServerError)?;
+        tracing::info!(admin_id = %admin.id, purged, "Ran trash purge manually");
+
+        Ok(Json(PurgeTrashResponse { purged, retention_days: retention.days }))
+    }
+}
+
+impl From<users::Model> for AdminUser {
+    fn from(user: users::Model) -> Self {
+        AdminUser {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at.and_utc().to_rfc3339(),
+            suspended_at: user.suspended_at.map(|at| at.and_utc().to_rfc3339()),
+            suspension_reason: user.suspension_reason,
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+async fn set_suspension(db: &DatabaseConnection, user_id: Uuid, suspended_at: Option<NaiveDateTime>, reason: Option<String>) -> Result<AdminUser> {
+    let user = users::Entity::update_many()
+        .col_expr(users::Column::SuspendedAt, Expr::value(suspended_at))
+        .col_expr(users::Column::SuspensionReason, Expr::value(reason))
+        .filter(users::Column::Id.eq(user_id))
+        .exec_with_returning(db)
+        .await
+        .map_err(poem::error::InternalServerError)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| NotFound(ApiError("User not found".to_string())))?;
+
+    Ok(AdminUser::from(user))
+}
+
+// The role is checked against the freshly loaded user row, never just the token claim.
+fn require_admin(auth: SessionAuth) -> Result<users::Model> {
+    let user = auth.0.0;
+    if user.role != ROLE_ADMIN {
+        return Err(Forbidden(ApiError("Admin access required".to_string())));
+    }
+
+    Ok(user)
+}
+
+/// Promotes the account named by `ADMIN_EMAIL` to admin so a fresh install can get its first administrator.
+pub async fn bootstrap_admin(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let Ok(email) = env::var("ADMIN_EMAIL") else {
+        return Ok(());
+    };
+
+    let result = users::Entity::update_many()
+        .col_expr(users::Column::Role, Expr::value(ROLE_ADMIN))
+        .filter(email_matches(&email))
+        .exec(db)
+        .await?;
+
+    if result.rows_affected > 0 {
+        tracing::info!("Promoted {} to admin", email.trim());
+    } else {
+        tracing::warn!("ADMIN_EMAIL is set but no user with email {} exists", email.trim());
+    }
+    Ok(())
+}
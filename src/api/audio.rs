@@ -0,0 +1,71 @@
+// --- Audio Formats ---
+// The audio types Gemini accepts inline. Clients may use common aliases; Gemini always gets the
+// canonical type.
+
+/// What uploads were assumed to be before clients could say, and still the fallback.
+pub const DEFAULT_AUDIO_MIME: &str = "audio/wav";
+
+const SUPPORTED: &[(&str, &[&str])] = &[
+    ("audio/wav", &["audio/wav", "audio/wave", "audio/x-wav", "audio/vnd.wave"]),
+    ("audio/mp3", &["audio/mp3", "audio/mpeg", "audio/mpeg3", "audio/x-mp3"]),
+    ("audio/aiff", &["audio/aiff", "audio/x-aiff"]),
+    ("audio/aac", &["audio/aac", "audio/x-aac"]),
+    ("audio/ogg", &["audio/ogg", "audio/opus", "application/ogg"]),
+    ("audio/flac", &["audio/flac", "audio/x-flac"]),
+    ("audio/mp4", &["audio/mp4", "audio/m4a", "audio/x-m4a"]),
+];
+
+/// The canonical type for a declared one, ignoring case and parameters like `; codecs=opus`.
+pub fn supported_mime(declared: &str) -> Option<&'static str> {
+    let declared = declared.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    SUPPORTED
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&declared.as_str()))
+        .map(|(canonical, _)| *canonical)
+}
+
+/// File extension for a canonical type, for download names.
+pub fn file_extension(mime: &str) -> &'static str {
+    match mime {
+        "audio/mp3" => "mp3",
+        "audio/aiff" => "aiff",
+        "audio/aac" => "aac",
+        "audio/ogg" => "ogg",
+        "audio/flac" => "flac",
+        "audio/mp4" => "m4a",
+        _ => "wav",
+    }
+}
+
+pub fn sniff_audio(data: &[u8]) -> Option<&'static str> {
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        Some("audio/wav")
+    } else if data.len() >= 12 && &data[..4] == b"FORM" && (&data[8..12] == b"AIFF" || &data[8..12] == b"AIFC") {
+        Some("audio/aiff")
+    } else if data.starts_with(b"OggS") {
+        Some("audio/ogg")
+    } else if data.starts_with(b"fLaC") {
+        Some("audio/flac")
+    } else if data.len() >= 8 && &data[4..8] == b"ftyp" {
+        Some("audio/mp4")
+    } else if data.len() >= 2 && data[0] == 0xFF && data[1] & 0xF6 == 0xF0 {
+        // ADTS frame sync with layer 0, which MP3 frames never have
+        Some("audio/aac")
+    } else if data.starts_with(b"ID3") || (data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+        Some("audio/mp3")
+    } else {
+        None
+    }
+}
+
+/// The declared type if given, otherwise whatever the bytes look like, otherwise WAV.
+/// A declared type outside the supported set is an error, not a fallback.
+pub fn resolve_mime(declared: Option<&str>, data: &[u8]) -> Result<&'static str, String> {
+    match declared.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(declared) => supported_mime(declared).ok_or_else(|| {
+            let supported: Vec<&str> = SUPPORTED.iter().map(|(canonical, _)| *canonical).collect();
+            format!("Unsupported audio type {}; supported: {}", declared, supported.join(", "))
+        }),
+        None => Ok(sniff_audio(data).unwrap_or(DEFAULT_AUDIO_MIME)),
+    }
+}
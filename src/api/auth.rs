@@ -0,0 +1,278 @@
+use std::ops::Deref;
+
+use chrono::DateTime;
+use jsonwebtoken::errors::ErrorKind;
+use poem::{http::StatusCode, web::Data, Request, Result};
+use poem_openapi::{auth::Bearer, payload::Json, ApiResponse, Enum, Object, OpenApi, SecurityScheme};
+use sea_orm::{EntityTrait, DatabaseConnection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use entity::users;
+use crate::api::error::ErrorBody;
+use crate::api::jwt::decode_claims;
+use crate::api::sessions::{check_not_revoked, check_session};
+use crate::api::tokens::{authorize_user_for, Scope};
+
+// --- Authentication ---
+// Every way a bearer token becomes a user lives here. Handlers take one of the schemes below and
+// receive an already verified user; a bad, expired or revoked token never reaches the handler and
+// is answered with 401 and an `ErrorBody`.
+//
+// The schemes share the name `ApiKeyAuth`, so Swagger shows a single bearer login for all of them.
+// The spec keeps the description of whichever registers first for all of them, so theirs are plain
+// comments rather than doc comments.
+
+/// What login puts in a session JWT, as far as verification needs it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub email: String,
+    pub exp: usize,
+    // Absent on tokens issued before sessions existed
+    #[serde(default)]
+    pub sid: Option<String>,
+    // Absent on tokens issued before logout existed
+    #[serde(default)]
+    pub jti: Option<String>,
+    // Tokens from before token versions existed count as version 0
+    #[serde(default)]
+    pub ver: i32,
+}
+
+/// Why a token was refused. Returned as `reason` by `/auth/verify`.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TokenRejection {
+    InvalidToken,
+    Expired,
+    SessionRevoked,
+    UserNotFound,
+    PasswordChanged,
+    Suspended,
+    ServerError,
+}
+
+/// A refused token: the machine-readable reason and a message for people.
+#[derive(Debug)]
+pub struct TokenError {
+    pub reason: TokenRejection,
+    pub message: String,
+}
+
+impl TokenError {
+    pub fn new(reason: TokenRejection, message: impl Into<String>) -> Self {
+        TokenError { reason, message: message.into() }
+    }
+}
+
+#[derive(Object, Serialize)]
+pub struct TokenVerification {
+    pub valid: bool,
+    #[oai(skip_serializing_if_is_none)]
+    pub user_id: Option<String>,
+    /// RFC 3339
+    #[oai(skip_serializing_if_is_none)]
+    pub expires_at: Option<String>,
+    /// Only set when `valid` is false
+    #[oai(skip_serializing_if_is_none)]
+    pub reason: Option<TokenRejection>,
+}
+
+/// The verified caller.
+pub struct AuthedUser(pub users::Model);
+
+impl Deref for AuthedUser {
+    type Target = users::Model;
+
+    fn deref(&self) -> &users::Model {
+        &self.0
+    }
+}
+
+// A login session JWT. Personal access tokens are refused, for account settings they must not touch.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "session_checker")]
+pub struct SessionAuth(pub AuthedUser);
+
+// A login session JWT with its claims, for the endpoints that act on the session itself.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "current_session_checker")]
+pub struct CurrentSessionAuth(pub CurrentSession);
+
+/// The verified caller and the claims of the session token they called with.
+pub struct CurrentSession {
+    pub user: users::Model,
+    pub claims: Claims,
+}
+
+// A session JWT, or a personal access token with `memos:read`.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "memos_read_checker")]
+pub struct MemosReadAuth(pub AuthedUser);
+
+// A session JWT, or a personal access token with `memos:write`.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "memos_write_checker")]
+pub struct MemosWriteAuth(pub AuthedUser);
+
+// A session JWT, or a personal access token with `ai:invoke`.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "ai_invoke_checker")]
+pub struct AiInvokeAuth(pub AuthedUser);
+
+// A session JWT, or a personal access token with both `memos:write` and `ai:invoke`.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT", checker = "memos_write_ai_checker")]
+pub struct MemosWriteAiAuth(pub AuthedUser);
+
+// Any bearer token, checked by the handler itself.
+#[derive(SecurityScheme)]
+#[oai(rename = "ApiKeyAuth", ty = "bearer", bearer_format = "JWT")]
+struct AnyBearer(Bearer);
+
+#[derive(ApiResponse)]
+enum VerifyApiResponse {
+    #[oai(status = 200)]
+    Valid(Json<TokenVerification>),
+    #[oai(status = 401)]
+    Invalid(Json<TokenVerification>),
+}
+
+// --- API Definition ---
+pub struct AuthApi;
+
+#[OpenApi]
+impl AuthApi {
+    /// Check whether a session token is still accepted, with the same checks every endpoint runs,
+    /// including a database lookup of the session and the user.
+    ///
+    /// A rejected token gets 401 with `valid: false` and one of these `reason` values:
+    ///
+    /// - `invalid_token`: malformed, badly signed, for another issuer or audience, or not a session token
+    /// - `expired`: past its `exp`; log in again
+    /// - `session_revoked`: logged out, or revoked from another device
+    /// - `user_not_found`: the account no longer exists
+    /// - `password_changed`: issued before the last password change
+    /// - `suspended`: the account is suspended
+    /// - `server_error`: the check couldn't complete; the token may still be good, so retry later
+    #[oai(path = "/auth/verify", method = "get")]
+    async fn verify_token(
+        &self,
+        auth: AnyBearer,
+        db: Data<&DatabaseConnection>,
+    ) -> VerifyApiResponse {
+        match verify_session_token(&auth.0.token, db.0).await {
+            Ok((user, claims)) => VerifyApiResponse::Valid(Json(TokenVerification {
+                valid: true,
+                user_id: Some(user.id.to_string()),
+                expires_at: DateTime::from_timestamp(claims.exp as i64, 0).map(|at| at.to_rfc3339()),
+                reason: None,
+            })),
+            Err(err) => VerifyApiResponse::Invalid(Json(TokenVerification {
+                valid: false,
+                user_id: None,
+                expires_at: None,
+                reason: Some(err.reason),
+            })),
+        }
+    }
+}
+
+async fn session_checker(req: &Request, bearer: Bearer) -> Result<AuthedUser> {
+    user_from_session_token(&bearer.token, database(req)?)
+        .await
+        .map(AuthedUser)
+        .map_err(unauthorized)
+}
+
+async fn current_session_checker(req: &Request, bearer: Bearer) -> Result<CurrentSession> {
+    verify_session_token(&bearer.token, database(req)?)
+        .await
+        .map(|(user, claims)| CurrentSession { user, claims })
+        .map_err(|err| unauthorized(err.message))
+}
+
+async fn memos_read_checker(req: &Request, bearer: Bearer) -> Result<AuthedUser> {
+    with_scopes(req, &bearer, &[Scope::MemosRead]).await
+}
+
+async fn memos_write_checker(req: &Request, bearer: Bearer) -> Result<AuthedUser> {
+    with_scopes(req, &bearer, &[Scope::MemosWrite]).await
+}
+
+async fn ai_invoke_checker(req: &Request, bearer: Bearer) -> Result<AuthedUser> {
+    with_scopes(req, &bearer, &[Scope::AiInvoke]).await
+}
+
+async fn memos_write_ai_checker(req: &Request, bearer: Bearer) -> Result<AuthedUser> {
+    with_scopes(req, &bearer, &[Scope::MemosWrite, Scope::AiInvoke]).await
+}
+
+async fn with_scopes(req: &Request, bearer: &Bearer, scopes: &[Scope]) -> Result<AuthedUser> {
+    authorize_user_for(&bearer.token, database(req)?, scopes)
+        .await
+        .map(AuthedUser)
+        .map_err(unauthorized)
+}
+
+/// Verifies a login session JWT: signature and expiry, that neither it nor its session has been revoked, and
+/// that the user still exists, isn't suspended and hasn't changed their password since it was issued.
+pub async fn user_from_session_token(token: &str, db: &DatabaseConnection) -> Result<users::Model, String> {
+    verify_session_token(token, db)
+        .await
+        .map(|(user, _)| user)
+        .map_err(|err| err.message)
+}
+
+/// `user_from_session_token` with the claims and the reason for a refusal.
+pub async fn verify_session_token(token: &str, db: &DatabaseConnection) -> Result<(users::Model, Claims), TokenError> {
+    let claims = decode_claims::<Claims>(token).map_err(|e| match e.kind() {
+        ErrorKind::ExpiredSignature => TokenError::new(TokenRejection::Expired, "Invalid or expired token"),
+        _ => TokenError::new(TokenRejection::InvalidToken, "Invalid or expired token"),
+    })?;
+
+    if let Some(jti) = &claims.jti {
+        check_not_revoked(db, jti).await?;
+    }
+    if let Some(sid) = &claims.sid {
+        check_session(db, sid).await?;
+    }
+
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| TokenError::new(TokenRejection::InvalidToken, "Invalid user ID format in token"))?;
+
+    let user = users::Entity::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while fetching user: {:?}", e);
+            TokenError::new(TokenRejection::ServerError, "Failed to verify user due to a database error")
+        })?
+        .ok_or_else(|| TokenError::new(TokenRejection::UserNotFound, format!("User {} not found in database", user_id)))?;
+
+    if claims.ver != user.token_version {
+        return Err(TokenError::new(TokenRejection::PasswordChanged, "Token is no longer valid, please log in again"));
+    }
+    ensure_not_suspended(&user).map_err(|message| TokenError::new(TokenRejection::Suspended, message))?;
+
+    Ok((user, claims))
+}
+
+/// Suspended accounts keep their data, but no token works for them until the suspension is lifted.
+pub(crate) fn ensure_not_suspended(user: &users::Model) -> Result<(), String> {
+    match user.suspended_at {
+        Some(_) => Err("This account has been suspended".to_string()),
+        None => Ok(()),
+    }
+}
+
+fn database(req: &Request) -> Result<&DatabaseConnection> {
+    req.data::<DatabaseConnection>()
+        .ok_or_else(|| poem::Error::from_string("Database connection is not configured", StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn unauthorized(message: String) -> poem::Error {
+    ErrorBody::error(StatusCode::UNAUTHORIZED, ErrorBody::new("unauthorized", message))
+}
@@ -0,0 +1,211 @@
+use chrono::Utc;
+use poem::web::Data;
+use poem_openapi::{payload::{Binary, Json}, param::Header, ApiRequest, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, sea_query::OnConflict, DatabaseConnection};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use entity::user_avatars;
+use crate::api::auth::SessionAuth;
+use crate::api::error::ErrorBody;
+use crate::api::limits::avatar_limit;
+
+// --- API Structs ---
+
+/// The image itself as the request body, with a matching `Content-Type`.
+#[derive(ApiRequest)]
+enum AvatarUpload {
+    #[oai(content_type = "image/png")]
+    Png(Binary<Vec<u8>>),
+    #[oai(content_type = "image/jpeg")]
+    Jpeg(Binary<Vec<u8>>),
+    #[oai(content_type = "image/webp")]
+    Webp(Binary<Vec<u8>>),
+}
+
+#[derive(Object, Serialize)]
+pub struct AvatarOutput {
+    pub content_type: String,
+    pub size: usize,
+    pub etag: String,
+    pub updated_at: String,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum PutAvatarApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<AvatarOutput>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    /// The body is not the image type its `Content-Type` claims
+    #[oai(status = 415)]
+    UnsupportedMediaType(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum GetAvatarApiResponse {
+    #[oai(status = 200)]
+    Ok(
+        Binary<Vec<u8>>,
+        #[oai(header = "Content-Type")] String,
+        #[oai(header = "ETag")] String,
+    ),
+    #[oai(status = 304)]
+    NotModified(#[oai(header = "ETag")] String),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteAvatarApiResponse {
+    #[oai(status = 204)]
+    Deleted,
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct AvatarApi;
+
+#[OpenApi]
+impl AvatarApi {
+    /// Upload or replace the caller's avatar. Bodies over `MAX_AVATAR_KB` are rejected with 413.
+    #[oai(path = "/me/avatar", method = "put", transform = "avatar_limit")]
+    async fn put_avatar(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        upload: AvatarUpload,
+    ) -> PutAvatarApiResponse {
+        let user = auth.0;
+
+        let (content_type, Binary(data)) = match upload {
+            AvatarUpload::Png(body) => ("image/png", body),
+            AvatarUpload::Jpeg(body) => ("image/jpeg", body),
+            AvatarUpload::Webp(body) => ("image/webp", body),
+        };
+        if sniff_image(&data) != Some(content_type) {
+            return PutAvatarApiResponse::UnsupportedMediaType(ErrorBody::new(
+                "unsupported_media_type",
+                format!("Body is not a valid {} image", content_type),
+            ));
+        }
+
+        let avatar = user_avatars::ActiveModel {
+            user_id: Set(user.id),
+            content_type: Set(content_type.to_string()),
+            etag: Set(etag_for(&data)),
+            data: Set(data),
+            updated_at: Set(Utc::now().naive_utc()),
+        };
+
+        let saved = user_avatars::Entity::insert(avatar)
+            .on_conflict(
+                OnConflict::column(user_avatars::Column::UserId)
+                    .update_columns([
+                        user_avatars::Column::ContentType,
+                        user_avatars::Column::Data,
+                        user_avatars::Column::Etag,
+                        user_avatars::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(db.0)
+            .await;
+
+        match saved {
+            Ok(avatar) => PutAvatarApiResponse::Ok(Json(AvatarOutput {
+                content_type: avatar.content_type,
+                size: avatar.data.len(),
+                etag: avatar.etag,
+                updated_at: avatar.updated_at.and_utc().to_rfc3339(),
+            })),
+            Err(e) => PutAvatarApiResponse::InternalServerError(ErrorBody::from_db("Failed to save avatar", &e)),
+        }
+    }
+
+    /// The caller's avatar image. Send the last `ETag` in `If-None-Match` to get a 304 when unchanged.
+    #[oai(path = "/me/avatar", method = "get")]
+    async fn get_avatar(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        #[oai(name = "If-None-Match")] if_none_match: Header<Option<String>>,
+    ) -> GetAvatarApiResponse {
+        let user = auth.0;
+
+        match user_avatars::Entity::find_by_id(user.id).one(db.0).await {
+            Ok(Some(avatar)) if etag_matches(if_none_match.0.as_deref(), &avatar.etag) => {
+                GetAvatarApiResponse::NotModified(avatar.etag)
+            }
+            Ok(Some(avatar)) => GetAvatarApiResponse::Ok(Binary(avatar.data), avatar.content_type, avatar.etag),
+            Ok(None) => GetAvatarApiResponse::NotFound(ErrorBody::new("not_found", "No avatar uploaded")),
+            Err(e) => GetAvatarApiResponse::InternalServerError(ErrorBody::from_db("Failed to load avatar", &e)),
+        }
+    }
+
+    /// Remove the caller's avatar
+    #[oai(path = "/me/avatar", method = "delete")]
+    async fn delete_avatar(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> DeleteAvatarApiResponse {
+        let user = auth.0;
+
+        match user_avatars::Entity::delete_by_id(user.id).exec(db.0).await {
+            Ok(result) if result.rows_affected == 0 => {
+                DeleteAvatarApiResponse::NotFound(ErrorBody::new("not_found", "No avatar uploaded"))
+            }
+            Ok(_) => DeleteAvatarApiResponse::Deleted,
+            Err(e) => DeleteAvatarApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete avatar", &e)),
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+// Trust the bytes, not the header: each format has a fixed signature at the start of the file.
+fn sniff_image(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+pub(crate) fn etag_for(data: &[u8]) -> String {
+    let digest: String = Sha256::digest(data).iter().take(16).map(|b| format!("{:02x}", b)).collect();
+    format!("\"{}\"", digest)
+}
+
+pub(crate) fn etag_matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    if_none_match.is_some_and(|value| {
+        value
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag)
+    })
+}
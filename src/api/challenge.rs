@@ -0,0 +1,155 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+// --- Signup Challenge ---
+// Optional captcha check on signup, enabled with SIGNUP_CHALLENGE_PROVIDER.
+
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+pub enum ChallengeError {
+    /// The provider looked at the token and said no
+    Rejected,
+    /// We couldn't get an answer from the provider
+    Unavailable(String),
+}
+
+/// Checks a challenge token from the client. Injected as app data so tests can swap it out.
+#[async_trait]
+pub trait ChallengeVerifier: Send + Sync {
+    async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<(), ChallengeError>;
+}
+
+/// App data for `signup`: `None` when no provider is configured.
+#[derive(Clone)]
+pub struct SignupChallenge(pub Option<Arc<dyn ChallengeVerifier>>);
+
+impl SignupChallenge {
+    pub fn from_env() -> Result<Self, String> {
+        let provider = match env::var("SIGNUP_CHALLENGE_PROVIDER") {
+            Ok(p) if !p.trim().is_empty() => p.trim().to_ascii_lowercase(),
+            _ => return Ok(SignupChallenge(None)),
+        };
+
+        let verify_url = match provider.as_str() {
+            "turnstile" => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+            "hcaptcha" => "https://api.hcaptcha.com/siteverify",
+            other => return Err(format!("Unknown SIGNUP_CHALLENGE_PROVIDER: {}", other)),
+        };
+        let secret = env::var("SIGNUP_CHALLENGE_SECRET")
+            .map_err(|_| "SIGNUP_CHALLENGE_SECRET is required when SIGNUP_CHALLENGE_PROVIDER is set".to_string())?;
+
+        let client = Client::builder()
+            .timeout(VERIFY_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to build challenge client: {}", e))?;
+
+        Ok(SignupChallenge(Some(Arc::new(SiteVerify { client, verify_url, secret }))))
+    }
+}
+
+/// Turnstile and hCaptcha share the same siteverify protocol, only the URL differs.
+struct SiteVerify {
+    client: Client,
+    verify_url: &'static str,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+#[async_trait]
+impl ChallengeVerifier for SiteVerify {
+    async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<(), ChallengeError> {
+        let mut form = vec![("secret", self.secret.as_str()), ("response", token)];
+        if let Some(ip) = remote_ip {
+            form.push(("remoteip", ip));
+        }
+
+        let res = self
+            .client
+            .post(self.verify_url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| ChallengeError::Unavailable(e.to_string()))?;
+
+        let body: SiteVerifyResponse = res
+            .json()
+            .await
+            .map_err(|e| ChallengeError::Unavailable(e.to_string()))?;
+
+        if body.success { Ok(()) } else { Err(ChallengeError::Rejected) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use poem::http::StatusCode;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+    use uuid::Uuid;
+
+    use entity::users;
+    use super::*;
+    use crate::test_support::{test_app_with, test_db};
+
+    /// Accepts `pass`, rejects `fail`, is unreachable for anything else, and keeps every call.
+    #[derive(Default)]
+    struct MockVerifier {
+        calls: Mutex<Vec<(String, Option<String>)>>,
+    }
+
+    #[async_trait]
+    impl ChallengeVerifier for MockVerifier {
+        async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<(), ChallengeError> {
+            self.calls.lock().unwrap().push((token.to_string(), remote_ip.map(str::to_string)));
+            match token {
+                "pass" => Ok(()),
+                "fail" => Err(ChallengeError::Rejected),
+                _ => Err(ChallengeError::Unavailable("connection refused".to_string())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn signup_requires_a_token_the_verifier_accepts() {
+        let Some(db) = test_db().await else { return };
+        let verifier = Arc::new(MockVerifier::default());
+        let client = test_app_with(&db, SignupChallenge(Some(verifier.clone())));
+
+        let username = format!("user_{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let email = format!("{}@example.test", username);
+        let signup = |token: Option<&str>| {
+            let mut payload = serde_json::json!({ "username": username, "email": email, "password": "Unrelated-Passw0rd!" });
+            if let Some(token) = token {
+                payload["challenge_token"] = token.into();
+            }
+            client.post("/api/signup").body_json(&payload).send()
+        };
+
+        for (token, code) in [(None, "challenge_required"), (Some("fail"), "challenge_failed"), (Some("timeout"), "challenge_unavailable")] {
+            let resp = signup(token).await;
+            resp.assert_status(StatusCode::BAD_REQUEST);
+            resp.json().await.value().object().get("code").assert_string(code);
+        }
+        let created = users::Entity::find().filter(users::Column::Username.eq(&username)).one(&db).await.unwrap();
+        assert!(created.is_none(), "a refused signup created the account");
+
+        signup(Some("pass")).await.assert_status_is_ok();
+        let created = users::Entity::find().filter(users::Column::Username.eq(&username)).one(&db).await.unwrap();
+        assert!(created.is_some());
+
+        // Without a token the provider isn't asked at all
+        let tokens: Vec<String> = verifier.calls.lock().unwrap().iter().map(|(token, _)| token.clone()).collect();
+        assert_eq!(tokens, ["fail", "timeout", "pass"]);
+    }
+}
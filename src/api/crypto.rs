@@ -1,53 +1,158 @@
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng}, 
+    aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Nonce,
 };
-use base64::{Engine as _, engine::general_purpose}; 
-const ENCRYPTION_KEY: &[u8; 32] = b"01234567890123456789012345678901"; 
-
-pub fn encrypt(plain_text: &str) -> Result<String, String> {
-   
-    let cipher = Aes256Gcm::new(ENCRYPTION_KEY.into());
-
-  
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng); 
-    
-   
-    let ciphertext = cipher
-        .encrypt(&nonce, plain_text.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-
-   
-    let mut combined = nonce.to_vec();
-    combined.extend_from_slice(&ciphertext);
-    
-   
-    Ok(general_purpose::STANDARD.encode(combined))
+use base64::{Engine as _, engine::general_purpose};
+use std::env;
+
+/// AES-256-GCM for the API keys users store with us. Lives in app data.
+///
+/// Keys come from `ENCRYPTION_KEY` (32 bytes, base64 or hex encoded) tagged with
+/// `ENCRYPTION_KEY_VERSION`, plus optionally the key being rotated out in
+/// `ENCRYPTION_KEY_PREVIOUS` / `ENCRYPTION_KEY_PREVIOUS_VERSION`. Ciphertext is written as
+/// `v<version>:<base64>`, so reads pick the right key and rotation needs no bulk migration.
+///
+/// Migration note: values written before this was configurable were encrypted with the old
+/// built-in development key and can't be decrypted with a new one; they must be re-encrypted
+/// (or users asked to re-enter their keys) when switching.
+#[derive(Clone)]
+pub struct Crypto {
+    current: VersionedKey,
+    previous: Option<VersionedKey>,
+}
+
+#[derive(Clone)]
+struct VersionedKey {
+    version: u32,
+    key: [u8; 32],
+}
+
+impl Crypto {
+    pub fn from_env() -> Result<Self, String> {
+        let current = VersionedKey::from_env("ENCRYPTION_KEY", "ENCRYPTION_KEY_VERSION")?
+            .ok_or_else(|| "ENCRYPTION_KEY is not set".to_string())?;
+        let previous = VersionedKey::from_env("ENCRYPTION_KEY_PREVIOUS", "ENCRYPTION_KEY_PREVIOUS_VERSION")?;
+
+        if previous.as_ref().is_some_and(|p| p.version == current.version) {
+            return Err("ENCRYPTION_KEY_PREVIOUS_VERSION must differ from ENCRYPTION_KEY_VERSION".to_string());
+        }
+
+        Ok(Crypto { current, previous })
+    }
+
+    /// A single version-1 key, so tests don't depend on the environment.
+    #[cfg(test)]
+    pub fn with_key(key: [u8; 32]) -> Self {
+        Crypto { current: VersionedKey { version: 1, key }, previous: None }
+    }
+
+    /// Always encrypts with the current key.
+    pub fn encrypt(&self, plain_text: &str) -> Result<String, String> {
+        let cipher = Aes256Gcm::new(&self.current.key.into());
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(&nonce, plain_text.as_bytes())
+            .map_err(|e| format!("Encryption failed: {}", e))?;
+
+        let mut combined = nonce.to_vec();
+        combined.extend_from_slice(&ciphertext);
+
+        Ok(format!("v{}:{}", self.current.version, general_purpose::STANDARD.encode(combined)))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<String, String> {
+        match split_version(encoded) {
+            Some((version, payload)) => {
+                let key = self
+                    .keys()
+                    .find(|k| k.version == version)
+                    .ok_or_else(|| format!("No encryption key configured for version {}", version))?;
+                key.decrypt(payload)
+            }
+            // Unversioned values predate rotation; they were written with whichever key was configured then
+            None => self
+                .keys()
+                .find_map(|k| k.decrypt(encoded).ok())
+                .ok_or_else(|| "Decryption failed".to_string()),
+        }
+    }
+
+    /// True when `encoded` was not written with the current key.
+    pub fn is_stale(&self, encoded: &str) -> bool {
+        split_version(encoded).is_none_or(|(version, _)| version != self.current.version)
+    }
+
+    /// Decrypts with whatever key wrote the value and encrypts it again with the current one.
+    pub fn reencrypt(&self, encoded: &str) -> Result<String, String> {
+        self.encrypt(&self.decrypt(encoded)?)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &VersionedKey> {
+        std::iter::once(&self.current).chain(self.previous.as_ref())
+    }
 }
 
-pub fn decrypt(encoded: &str) -> Result<String, String> {
-    
-    let combined = general_purpose::STANDARD.decode(encoded)
-        .map_err(|e| format!("Base64 decode error: {}", e))?;
-    
-  
-    if combined.len() < 12 {
-        return Err("Invalid encrypted payload: too short".to_string());
-    }
-
-    
-    let (nonce_bytes, ciphertext) = combined.split_at(12);
-    let nonce = Nonce::from_slice(nonce_bytes);
-    
-    
-    let cipher = Aes256Gcm::new(ENCRYPTION_KEY.into());
-
-   
-    let decrypted_bytes = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|e| format!("Decryption failed: {}", e))?;
-
-    
-    String::from_utf8(decrypted_bytes)
-        .map_err(|e| format!("UTF-8 decode error: {}", e))
-}
\ No newline at end of file
+impl VersionedKey {
+    fn from_env(key_var: &str, version_var: &str) -> Result<Option<Self>, String> {
+        let encoded = match env::var(key_var) {
+            Ok(v) if !v.trim().is_empty() => v,
+            _ => return Ok(None),
+        };
+        let encoded = encoded.trim();
+
+        let bytes = decode_hex(encoded)
+            .or_else(|| general_purpose::STANDARD.decode(encoded).ok())
+            .ok_or_else(|| format!("{} must be base64 or hex encoded", key_var))?;
+
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|b: Vec<u8>| format!("{} must be exactly 32 bytes, got {}", key_var, b.len()))?;
+
+        let version = match env::var(version_var) {
+            Ok(v) => v.trim().parse().map_err(|_| format!("{} must be a non-negative integer", version_var))?,
+            Err(_) => 1,
+        };
+
+        Ok(Some(VersionedKey { version, key }))
+    }
+
+    fn decrypt(&self, encoded: &str) -> Result<String, String> {
+        let combined = general_purpose::STANDARD.decode(encoded)
+            .map_err(|e| format!("Base64 decode error: {}", e))?;
+
+        if combined.len() < 12 {
+            return Err("Invalid encrypted payload: too short".to_string());
+        }
+
+        let (nonce_bytes, ciphertext) = combined.split_at(12);
+        let nonce = Nonce::from_slice(nonce_bytes);
+
+        let cipher = Aes256Gcm::new(&self.key.into());
+
+        let decrypted_bytes = cipher
+            .decrypt(nonce, ciphertext)
+            .map_err(|e| format!("Decryption failed: {}", e))?;
+
+        String::from_utf8(decrypted_bytes)
+            .map_err(|e| format!("UTF-8 decode error: {}", e))
+    }
+}
+
+// "v3:AbC..." -> (3, "AbC..."). Base64 never contains ':', so unversioned values can't be mistaken for versioned ones.
+fn split_version(encoded: &str) -> Option<(u32, &str)> {
+    let (version, payload) = encoded.strip_prefix('v')?.split_once(':')?;
+    Some((version.parse().ok()?, payload))
+}
+
+// 64 hex characters; anything else falls through to base64.
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
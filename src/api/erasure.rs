@@ -0,0 +1,335 @@
+use chrono::Utc;
+use poem::web::Data;
+use poem_openapi::{payload::Json, ApiResponse, Object, OpenApi};
+use sea_orm::{
+    entity::*, query::*, sea_query::{IntoCondition, Query as SubQuery, SelectStatement},
+    DatabaseConnection, DatabaseTransaction, DbErr, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use entity::{
+    ai_usage, api_tokens, deleted_keys, folders, helper_app, memo_links, memo_shares, memo_tags, password_resets, revoked_tokens,
+    sessions, tags, user_avatars, user_preferences, users, voice_memos1,
+};
+use crate::api::auth::SessionAuth;
+use crate::api::error::ErrorBody;
+use crate::api::memo::stored_audio_keys;
+use crate::api::storage::AudioStore;
+use crate::api::user::password_matches;
+
+// --- API Structs ---
+
+#[derive(Object, Deserialize)]
+pub struct ErasePayload {
+    /// The account password, to confirm
+    pub password: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct ErasedTable {
+    pub table: String,
+    pub rows: u64,
+}
+
+#[derive(Object, Serialize)]
+pub struct ErasureReport {
+    pub user_id: String,
+    pub erased_at: String,
+    /// Every table that held data for the account, including those with nothing to remove
+    pub tables: Vec<ErasedTable>,
+    pub total_rows: u64,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum EraseApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<ErasureReport>),
+    /// Wrong password, or a missing, invalid or expired token
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorBody>),
+    /// Nothing was deleted
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct ErasureApi;
+
+#[OpenApi]
+impl ErasureApi {
+    /// Permanently delete the account and everything stored for it, in one transaction, and report
+    /// how many rows each table lost. The token used for the call stops working with it.
+    #[oai(path = "/me/erase", method = "post")]
+    async fn erase_me(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Json(payload): Json<ErasePayload>,
+    ) -> EraseApiResponse {
+        let user = auth.0;
+
+        match password_matches(&payload.password, &user) {
+            Ok(true) => {}
+            Ok(false) => return EraseApiResponse::Unauthorized(ErrorBody::new("invalid_password", "Password is incorrect")),
+            Err(_) => return EraseApiResponse::InternalServerError(ErrorBody::new("internal_error", "Failed to verify password")),
+        }
+
+        let txn = match db.0.begin().await {
+            Ok(txn) => txn,
+            Err(e) => return EraseApiResponse::InternalServerError(ErrorBody::from_db("Failed to start erasure", &e)),
+        };
+        // Returning early drops the transaction, which rolls everything back
+        let audio_keys = match stored_audio_keys(&txn, Condition::all().add(voice_memos1::Column::UserId.eq(user.id))).await {
+            Ok(keys) => keys,
+            Err(e) => return EraseApiResponse::InternalServerError(ErrorBody::from_db("Failed to erase account", &e)),
+        };
+        let tables = match erase_account(&txn, user.id).await {
+            Ok(tables) => tables,
+            Err(e) => return EraseApiResponse::InternalServerError(ErrorBody::from_db("Failed to erase account", &e)),
+        };
+        if let Err(e) = txn.commit().await {
+            return EraseApiResponse::InternalServerError(ErrorBody::from_db("Failed to erase account", &e));
+        }
+        // Only once the rows are gone for good; objects can't be rolled back
+        store.0.discard(audio_keys).await;
+
+        let total_rows = tables.iter().map(|table| table.rows).sum();
+        tracing::info!(user_id = %user.id, total_rows, "Erased account");
+
+        EraseApiResponse::Ok(Json(ErasureReport {
+            user_id: user.id.to_string(),
+            erased_at: Utc::now().to_rfc3339(),
+            tables,
+            total_rows,
+        }))
+    }
+}
+
+// --- Helper Functions ---
+
+// Children before parents, so the result doesn't depend on which foreign keys cascade.
+async fn erase_account(txn: &DatabaseTransaction, user_id: Uuid) -> Result<Vec<ErasedTable>, DbErr> {
+    Ok(vec![
+        erase(
+            txn,
+            memo_links::Entity,
+            Condition::any()
+                .add(memo_links::Column::FromMemoId.in_subquery(owned_memo_ids(user_id)))
+                .add(memo_links::Column::ToMemoId.in_subquery(owned_memo_ids(user_id))),
+        )
+        .await?,
+        erase(txn, memo_shares::Entity, memo_shares::Column::MemoId.in_subquery(owned_memo_ids(user_id))).await?,
+        erase(txn, memo_tags::Entity, memo_tags::Column::MemoId.in_subquery(owned_memo_ids(user_id))).await?,
+        erase(txn, tags::Entity, tags::Column::UserId.eq(user_id)).await?,
+        erase(txn, voice_memos1::Entity, voice_memos1::Column::UserId.eq(user_id)).await?,
+        erase(txn, folders::Entity, folders::Column::UserId.eq(user_id)).await?,
+        erase(txn, helper_app::Entity, helper_app::Column::UserId.eq(user_id)).await?,
+        erase(txn, deleted_keys::Entity, deleted_keys::Column::UserId.eq(user_id)).await?,
+        erase(txn, api_tokens::Entity, api_tokens::Column::UserId.eq(user_id)).await?,
+        erase(txn, sessions::Entity, sessions::Column::UserId.eq(user_id)).await?,
+        erase(txn, user_avatars::Entity, user_avatars::Column::UserId.eq(user_id)).await?,
+        erase(txn, user_preferences::Entity, user_preferences::Column::UserId.eq(user_id)).await?,
+        erase(txn, ai_usage::Entity, ai_usage::Column::UserId.eq(user_id)).await?,
+        erase(txn, password_resets::Entity, password_resets::Column::UserId.eq(user_id)).await?,
+        erase(txn, revoked_tokens::Entity, revoked_tokens::Column::UserId.eq(user_id)).await?,
+        erase(txn, users::Entity, users::Column::Id.eq(user_id)).await?,
+    ])
+}
+
+async fn erase<E: EntityTrait>(txn: &DatabaseTransaction, entity: E, condition: impl IntoCondition) -> Result<ErasedTable, DbErr> {
+    let result = E::delete_many().filter(condition).exec(txn).await?;
+    Ok(ErasedTable { table: entity.table_name().to_string(), rows: result.rows_affected })
+}
+
+fn owned_memo_ids(user_id: Uuid) -> SelectStatement {
+    SubQuery::select()
+        .column(voice_memos1::Column::Id)
+        .from(voice_memos1::Entity)
+        .and_where(voice_memos1::Column::UserId.eq(user_id))
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Duration;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test_support::{bearer, login, new_memo, seed_user, test_app, test_db, TEST_PASSWORD};
+
+    #[tokio::test]
+    async fn erasure_reports_and_removes_a_row_from_every_user_table() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let bystander = seed_user(&db).await;
+        // Starts the user's one session
+        let token = login(&client, &user).await;
+        let now = Utc::now().naive_utc();
+
+        let folder = folders::ActiveModel { id: Set(Uuid::new_v4()), user_id: Set(user.id), name: Set("Work".into()), created_at: Set(now) }
+            .insert(&db)
+            .await
+            .unwrap();
+        let mut filed = new_memo(&user, "Filed");
+        filed.folder_id = Set(Some(folder.id));
+        let filed = filed.insert(&db).await.unwrap();
+        let other = new_memo(&user, "Linked").insert(&db).await.unwrap();
+        let kept = new_memo(&bystander, "Not theirs").insert(&db).await.unwrap();
+        let tag = tags::ActiveModel { id: Set(Uuid::new_v4()), user_id: Set(user.id), name: Set("work".into()), created_at: Set(now) }
+            .insert(&db)
+            .await
+            .unwrap();
+        memo_tags::ActiveModel { memo_id: Set(filed.id), tag_id: Set(tag.id) }.insert(&db).await.unwrap();
+        let link = memo_links::ActiveModel { id: Set(Uuid::new_v4()), from_memo_id: Set(filed.id), to_memo_id: Set(other.id), created_at: Set(now) }
+            .insert(&db)
+            .await
+            .unwrap();
+        let share = memo_shares::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            memo_id: Set(filed.id),
+            slug: Set(Uuid::new_v4().simple().to_string()),
+            max_views: Set(None),
+            view_count: Set(0),
+            last_viewed_at: Set(None),
+            expires_at: Set(None),
+            expired_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        helper_app::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            gemini_key: Set(Some("ciphertext".into())),
+            elevenlabs_key: Set(None),
+            user_id: Set(user.id),
+            action: Set("api_keys_save".into()),
+            timestamp: Set(now),
+            helper_status: Set(false),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        deleted_keys::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            provider: Set("elevenlabs".into()),
+            encrypted_key: Set("ciphertext".into()),
+            deleted_at: Set(now),
+            expires_at: Set(now + Duration::days(7)),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        api_tokens::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            name: Set("script".into()),
+            prefix: Set("smk_0000".into()),
+            token_hash: Set(Uuid::new_v4().to_string()),
+            scopes: Set("memos:read".into()),
+            created_at: Set(now),
+            last_used_at: Set(None),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        user_avatars::ActiveModel {
+            user_id: Set(user.id),
+            content_type: Set("image/png".into()),
+            data: Set(vec![0x89, b'P', b'N', b'G']),
+            etag: Set("avatar".into()),
+            updated_at: Set(now),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        user_preferences::ActiveModel {
+            user_id: Set(user.id),
+            default_translate_lang: Set(None),
+            summary_style: Set(None),
+            preferred_model: Set(None),
+            auto_process: Set(false),
+            timezone: Set(None),
+            updated_at: Set(now),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        ai_usage::ActiveModel { id: Set(Uuid::new_v4()), user_id: Set(user.id), operation: Set("summary".into()), created_at: Set(now) }
+            .insert(&db)
+            .await
+            .unwrap();
+        password_resets::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            token_hash: Set(Uuid::new_v4().to_string()),
+            created_at: Set(now),
+            expires_at: Set(now + Duration::hours(1)),
+            used_at: Set(None),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        revoked_tokens::ActiveModel { jti: Set(Uuid::new_v4()), user_id: Set(user.id), revoked_at: Set(now), expires_at: Set(now + Duration::days(1)) }
+            .insert(&db)
+            .await
+            .unwrap();
+
+        let resp = client
+            .post("/api/me/erase")
+            .header("Authorization", bearer(&token))
+            .body_json(&json!({ "password": TEST_PASSWORD }))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let report: Value = serde_json::from_str(&resp.0.into_body().into_string().await.unwrap()).unwrap();
+
+        let erased: BTreeMap<&str, u64> = report["tables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|table| (table["table"].as_str().unwrap(), table["rows"].as_u64().unwrap()))
+            .collect();
+        let mut expected: BTreeMap<&str, u64> = [
+            "memo_links", "memo_shares", "memo_tags", "tags", "folders", "helperApp", "deleted_keys", "api_tokens", "sessions",
+            "user_avatars", "user_preferences", "ai_usage", "password_resets", "revoked_tokens", "users",
+        ]
+        .into_iter()
+        .map(|table| (table, 1))
+        .collect();
+        expected.insert(voice_memos1::Entity.table_name(), 2);
+        assert_eq!(erased, expected);
+        assert_eq!(report["total_rows"], 17);
+
+        let remaining = [
+            ("memo_links", memo_links::Entity::find_by_id(link.id).count(&db).await.unwrap()),
+            ("memo_shares", memo_shares::Entity::find_by_id(share.id).count(&db).await.unwrap()),
+            ("memo_tags", memo_tags::Entity::find().filter(memo_tags::Column::TagId.eq(tag.id)).count(&db).await.unwrap()),
+            ("tags", tags::Entity::find().filter(tags::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("memos", voice_memos1::Entity::find().filter(voice_memos1::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("folders", folders::Entity::find().filter(folders::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("helperApp", helper_app::Entity::find().filter(helper_app::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("deleted_keys", deleted_keys::Entity::find().filter(deleted_keys::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("api_tokens", api_tokens::Entity::find().filter(api_tokens::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("sessions", sessions::Entity::find().filter(sessions::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("user_avatars", user_avatars::Entity::find().filter(user_avatars::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("user_preferences", user_preferences::Entity::find().filter(user_preferences::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("ai_usage", ai_usage::Entity::find().filter(ai_usage::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("password_resets", password_resets::Entity::find().filter(password_resets::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("revoked_tokens", revoked_tokens::Entity::find().filter(revoked_tokens::Column::UserId.eq(user.id)).count(&db).await.unwrap()),
+            ("users", users::Entity::find_by_id(user.id).count(&db).await.unwrap()),
+        ];
+        for (table, rows) in remaining {
+            assert_eq!(rows, 0, "{} still has rows for the erased user", table);
+        }
+        assert!(voice_memos1::Entity::find_by_id(kept.id).one(&db).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,118 @@
+use poem::{http::{header, StatusCode}, IntoResponse, Response};
+use poem_openapi::{payload::Json, Object};
+use sea_orm::{DbErr, SqlErr};
+use serde::Serialize;
+use validator::ValidationErrors;
+
+/// Error envelope returned instead of raw error strings. `code` is stable; `message` is safe to show.
+#[derive(Object, Serialize, Debug)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    /// Machine-readable context for the error, e.g. how long to wait before retrying
+    #[oai(skip_serializing_if_is_none)]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ErrorBody {
+    pub fn new(code: &str, message: impl Into<String>) -> Json<ErrorBody> {
+        Json(ErrorBody { code: code.to_string(), message: message.into(), details: None })
+    }
+
+    pub fn with_details(code: &str, message: impl Into<String>, details: serde_json::Value) -> Json<ErrorBody> {
+        Json(ErrorBody { code: code.to_string(), message: message.into(), details: Some(details) })
+    }
+
+    /// The envelope as a `poem::Error`, for handlers that return `poem::Result`.
+    pub fn error(status: StatusCode, body: Json<ErrorBody>) -> poem::Error {
+        let mut response = body.into_response();
+        response.set_status(status);
+        poem::Error::from_response(response)
+    }
+
+    /// Maps a database error to a sanitized envelope, logging the full error server-side.
+    pub fn from_db(context: &str, err: &DbErr) -> Json<ErrorBody> {
+        tracing::error!("{}: {:?}", context, err);
+
+        if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+            return ErrorBody::new("conflict", "The record already exists");
+        }
+
+        match err {
+            DbErr::RecordNotFound(_) => ErrorBody::new("not_found", "The record was not found"),
+            DbErr::Conn(_) | DbErr::ConnectionAcquire(_) => {
+                ErrorBody::new("database_unavailable", "The database is temporarily unavailable")
+            }
+            _ => ErrorBody::new("internal_error", "An internal error occurred"),
+        }
+    }
+}
+
+/// One failed rule on one request field, e.g. `{ field: "email", code: "email", ... }`.
+#[derive(Object, Serialize, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+/// Every validation failure of a request at once, so forms can mark each field.
+#[derive(Object, Serialize, Debug)]
+pub struct ValidationErrorResponse {
+    pub message: String,
+    pub errors: Vec<FieldError>,
+}
+
+impl From<ValidationErrors> for ValidationErrorResponse {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<_> = errors.field_errors().into_iter().collect();
+        fields.sort_by_key(|(field, _)| *field);
+
+        let errors = fields
+            .into_iter()
+            .flat_map(|(field, errs)| {
+                errs.iter().map(move |err| FieldError {
+                    field: field.to_string(),
+                    code: err.code.to_string(),
+                    message: err.message.as_deref().unwrap_or("Invalid value").to_string(),
+                })
+            })
+            .collect();
+
+        ValidationErrorResponse { message: "Validation failed".to_string(), errors }
+    }
+}
+
+/// Wraps errors that reach poem as plain text, such as `ApiError`s, database errors and rejected
+/// payloads, in an `ErrorBody`, so every endpoint fails in the same shape. Responses that are
+/// already JSON pass through untouched. Server errors are logged and answered with a generic message.
+pub async fn error_envelope(err: poem::Error) -> Response {
+    let message = err.to_string();
+    let response = err.into_response();
+    if response.content_type().is_some_and(|content_type| content_type.starts_with("application/json")) {
+        return response;
+    }
+
+    let status = response.status();
+    let message = if status.is_server_error() {
+        tracing::error!("{}: {}", status, message);
+        "An internal error occurred".to_string()
+    } else {
+        message
+    };
+    // "Payload Too Large" becomes "payload_too_large"
+    let code = status
+        .canonical_reason()
+        .map(|reason| reason.to_lowercase().replace([' ', '-'], "_"))
+        .unwrap_or_else(|| "error".to_string());
+
+    let mut envelope = ErrorBody::new(&code, message).into_response();
+    envelope.set_status(status);
+    // Keeps headers such as Retry-After and WWW-Authenticate
+    for (name, value) in response.headers() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            envelope.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    envelope
+}
@@ -0,0 +1,479 @@
+use std::io;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::{NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use futures::{channel::mpsc, SinkExt};
+use poem::{web::Data, Body, Result};
+use poem_openapi::{param::Query, payload::{Binary, Json}, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::Expr, DatabaseConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+use entity::{helper_app, users, voice_memos1};
+use crate::api::auth::{MemosReadAuth, SessionAuth};
+use crate::api::error::ErrorBody;
+use crate::api::memo::memo_ids_with_tag;
+use crate::api::preferences::{format_timestamp, preferences_or_default};
+use crate::api::storage::AudioStore;
+
+// --- Constants ---
+/// Version of the JSON archive format, written as `export_version`. Bump it when a change would
+/// make older importers misread a new archive.
+pub const EXPORT_VERSION: u32 = 1;
+// Memos per query. Small with audio, so only a few blobs are held in memory at a time.
+const PAGE_SIZE_WITH_AUDIO: u64 = 10;
+const PAGE_SIZE_METADATA_ONLY: u64 = 200;
+// How many pages the writer may get ahead of a slow client
+const BUFFERED_PAGES: usize = 2;
+const CSV_HEADER: [&str; 7] = ["id", "title", "duration", "created_at", "tags", "has_audio", "has_transcript"];
+
+// --- API Structs ---
+
+#[derive(Serialize)]
+struct ExportedUser {
+    id: String,
+    username: String,
+    email: String,
+    role: String,
+    created_at: String,
+}
+
+/// The JSON archive written by `/export_memos` and `/me/export` and read by `/import_memos`. The
+/// account export also has `user` and `helper_status`, which import ignores.
+#[derive(Object)]
+pub struct MemoArchive {
+    /// Archives from before versioning have none and are read as version 1
+    pub export_version: Option<u32>,
+    /// RFC 3339
+    pub exported_at: Option<String>,
+    pub memos: Vec<ArchivedMemo>,
+}
+
+/// One memo in a `MemoArchive`. Exports fill in everything they have; imports only need
+/// `title` and `duration`.
+#[derive(Object, Serialize)]
+pub struct ArchivedMemo {
+    /// The ID in the archive, only used to name skipped and failed memos; imports always get a new ID
+    pub id: Option<String>,
+    pub title: String,
+    pub transcript: Option<String>,
+    pub translate: Option<String>,
+    pub summary: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub duration: String,
+    /// RFC 3339; the time of import when omitted
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub is_favorite: Option<bool>,
+    /// Set for memos in the trash, which are imported into the trash
+    #[oai(skip_serializing_if_is_none)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// Base64; left out entirely for `include_audio=false`
+    #[oai(skip_serializing_if_is_none)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_base64: Option<String>,
+}
+
+impl From<&users::Model> for ExportedUser {
+    fn from(user: &users::Model) -> Self {
+        ExportedUser {
+            id: user.id.to_string(),
+            username: user.username.clone(),
+            email: user.email.clone(),
+            role: user.role.clone(),
+            created_at: user.created_at.and_utc().to_rfc3339(),
+        }
+    }
+}
+
+impl ArchivedMemo {
+    fn new(memo: voice_memos1::Model, include_audio: bool) -> Self {
+        ArchivedMemo {
+            id: Some(memo.id.to_string()),
+            title: memo.title,
+            transcript: memo.transcript,
+            translate: memo.translate,
+            summary: memo.summary,
+            tags: Some(memo.tags.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()),
+            duration: memo.duration,
+            created_at: Some(memo.created_at.and_utc().to_rfc3339()),
+            updated_at: Some(memo.updated_at.and_utc().to_rfc3339()),
+            is_favorite: Some(memo.is_favorite),
+            deleted_at: memo.deleted_at.map(|at| at.and_utc().to_rfc3339()),
+            audio_base64: memo.audio_blob.filter(|_| include_audio).map(|blob| STANDARD.encode(blob)),
+        }
+    }
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum ExportApiResponse {
+    /// A `MemoArchive`, streamed as it is read
+    #[oai(status = 200, content_type = "application/json")]
+    Ok(Binary<Body>, #[oai(header = "Content-Disposition")] String),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum CsvExportApiResponse {
+    /// One row per memo, streamed as it is read
+    #[oai(status = 200, content_type = "text/csv; charset=utf-8")]
+    Ok(Binary<Body>, #[oai(header = "Content-Disposition")] String),
+}
+
+// --- API Definition ---
+pub struct ExportApi;
+
+#[OpenApi]
+impl ExportApi {
+    /// Download everything stored for the caller's account: profile, helper status and every memo,
+    /// including those in the trash
+    #[oai(path = "/me/export", method = "get")]
+    async fn export_account(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        /// Set to false for a metadata-only export without audio
+        include_audio: Query<Option<bool>>,
+    ) -> Result<ExportApiResponse> {
+        let user = auth.0;
+
+        let helper_status = match helper_app::Entity::find()
+            .filter(helper_app::Column::UserId.eq(user.id))
+            .one(db.0)
+            .await
+        {
+            Ok(record) => record.is_some_and(|record| record.helper_status),
+            Err(e) => return Ok(ExportApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch helper status", &e))),
+        };
+
+        let head = format!(
+            r#"{{"export_version":{},"exported_at":{},"user":{},"helper_status":{},"memos":["#,
+            EXPORT_VERSION,
+            serde_json::to_string(&Utc::now().to_rfc3339()).map_err(poem::error::InternalServerError)?,
+            serde_json::to_string(&ExportedUser::from(&*user)).map_err(poem::error::InternalServerError)?,
+            helper_status,
+        );
+
+        let (tx, rx) = mpsc::channel(BUFFERED_PAGES);
+        tokio::spawn(write_memos(db.0.clone(), store.0.clone(), user.id, include_audio.0.unwrap_or(true), true, None, head, tx));
+
+        Ok(ExportApiResponse::Ok(
+            Binary(Body::from_bytes_stream(rx)),
+            format!("attachment; filename=\"smartmemo-export-{}.json\"", Utc::now().format("%Y-%m-%d")),
+        ))
+    }
+
+    /// Back up the caller's memos as a `MemoArchive` (`{ export_version, exported_at, memos: [...] }`),
+    /// without the trash. Memos are read a page at a time. For the whole account, use `/me/export`.
+    #[oai(path = "/export_memos", method = "get")]
+    async fn export_memos(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        /// Include each memo's audio as base64 (default true)
+        include_audio: Query<Option<bool>>,
+        /// Only memos carrying exactly this tag
+        tag: Query<Option<String>>,
+    ) -> Result<ExportApiResponse> {
+        let tag = tag.0.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+        export_archive(db.0, store.0, auth.0.id, include_audio.0.unwrap_or(true), tag)
+    }
+
+    /// Old path of `/export_memos`, which leaves audio out unless asked
+    #[oai(path = "/export", method = "get", deprecated)]
+    async fn export_memos_legacy(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        /// Include each memo's audio as base64 (default false)
+        include_audio: Query<Option<bool>>,
+    ) -> Result<ExportApiResponse> {
+        export_archive(db.0, store.0, auth.0.id, include_audio.0.unwrap_or(false), None)
+    }
+
+    /// Download the caller's memo metadata as CSV, one row per memo outside the trash. Tags are
+    /// joined with `;`, timestamps follow the `timezone` preference, and audio is never included.
+    #[oai(path = "/export.csv", method = "get")]
+    async fn export_csv(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> CsvExportApiResponse {
+        let user_id = auth.0.id;
+        let tz = preferences_or_default(db.0, user_id).await.tz();
+
+        let (tx, rx) = mpsc::channel(BUFFERED_PAGES);
+        tokio::spawn(write_csv(db.0.clone(), user_id, tz, tx));
+
+        CsvExportApiResponse::Ok(Binary(Body::from_bytes_stream(rx)), "attachment; filename=\"memos.csv\"".to_string())
+    }
+}
+
+// --- Helper Functions ---
+
+fn export_archive(db: &DatabaseConnection, store: &AudioStore, user_id: Uuid, include_audio: bool, tag: Option<String>) -> Result<ExportApiResponse> {
+    let head = format!(
+        r#"{{"export_version":{},"exported_at":{},"memos":["#,
+        EXPORT_VERSION,
+        serde_json::to_string(&Utc::now().to_rfc3339()).map_err(poem::error::InternalServerError)?,
+    );
+
+    let (tx, rx) = mpsc::channel(BUFFERED_PAGES);
+    tokio::spawn(write_memos(db.clone(), store.clone(), user_id, include_audio, false, tag, head, tx));
+
+    Ok(ExportApiResponse::Ok(
+        Binary(Body::from_bytes_stream(rx)),
+        format!("attachment; filename=\"smartmemo-memos-{}.json\"", Utc::now().format("%Y-%m-%d")),
+    ))
+}
+
+// Feeds the response body one page of memos at a time and stops once the client hangs up.
+// A failure mid-export aborts the body, so the client gets a broken download rather than a
+// well-formed file that is silently missing memos.
+#[allow(clippy::too_many_arguments)]
+async fn write_memos(
+    db: DatabaseConnection,
+    store: AudioStore,
+    user_id: Uuid,
+    include_audio: bool,
+    include_trash: bool,
+    tag: Option<String>,
+    head: String,
+    mut tx: mpsc::Sender<Result<Vec<u8>, io::Error>>,
+) {
+    if tx.send(Ok(head.into_bytes())).await.is_err() {
+        return;
+    }
+
+    let page_size = if include_audio { PAGE_SIZE_WITH_AUDIO } else { PAGE_SIZE_METADATA_ONLY };
+    let mut query = voice_memos1::Entity::find().filter(voice_memos1::Column::UserId.eq(user_id));
+    if !include_trash {
+        query = query.filter(voice_memos1::Column::DeletedAt.is_null());
+    }
+    if let Some(tag) = &tag {
+        query = query.filter(voice_memos1::Column::Id.in_subquery(memo_ids_with_tag(user_id, tag)));
+    }
+    let mut pages = query
+        .order_by_asc(voice_memos1::Column::CreatedAt)
+        .order_by_asc(voice_memos1::Column::Id)
+        .paginate(&db, page_size);
+
+    let mut first = true;
+    loop {
+        let chunk = match pages.fetch_and_next().await {
+            Ok(Some(mut memos)) => match load_stored_audio(&store, &mut memos, include_audio).await {
+                Ok(()) => encode_page(memos, include_audio, &mut first),
+                Err(e) => Err(e),
+            },
+            Ok(None) => break,
+            Err(e) => Err(io::Error::other(e)),
+        };
+        let failed = chunk.is_err();
+        if let Err(e) = &chunk {
+            tracing::error!(user_id = %user_id, "Account export aborted: {}", e);
+        }
+        if tx.send(chunk).await.is_err() || failed {
+            return;
+        }
+    }
+
+    let _ = tx.send(Ok(b"]}".to_vec())).await;
+}
+
+// Same as `write_memos`, but only the metadata columns are ever read, so pages can be large.
+async fn write_csv(db: DatabaseConnection, user_id: Uuid, tz: Option<Tz>, mut tx: mpsc::Sender<Result<Vec<u8>, io::Error>>) {
+    let mut pages = voice_memos1::Entity::find()
+        .select_only()
+        .column(voice_memos1::Column::Id)
+        .column(voice_memos1::Column::Title)
+        .column(voice_memos1::Column::Duration)
+        .column(voice_memos1::Column::CreatedAt)
+        .column(voice_memos1::Column::Tags)
+        .column_as(voice_memos1::Column::AudioBlob.is_not_null().or(voice_memos1::Column::AudioKey.is_not_null()), "has_audio")
+        .column_as(Expr::cust("COALESCE(transcript, '') <> ''"), "has_transcript")
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .order_by_asc(voice_memos1::Column::CreatedAt)
+        .order_by_asc(voice_memos1::Column::Id)
+        .into_tuple::<CsvRow>()
+        .paginate(&db, PAGE_SIZE_METADATA_ONLY);
+
+    if tx.send(encode_csv(&[], tz, true)).await.is_err() {
+        return;
+    }
+
+    loop {
+        let chunk = match pages.fetch_and_next().await {
+            Ok(Some(rows)) => encode_csv(&rows, tz, false),
+            Ok(None) => return,
+            Err(e) => Err(io::Error::other(e)),
+        };
+        let failed = chunk.is_err();
+        if let Err(e) = &chunk {
+            tracing::error!(user_id = %user_id, "CSV export aborted: {}", e);
+        }
+        if tx.send(chunk).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+type CsvRow = (Uuid, String, String, NaiveDateTime, Option<String>, bool, bool);
+
+fn encode_csv(rows: &[CsvRow], tz: Option<Tz>, header: bool) -> Result<Vec<u8>, io::Error> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    if header {
+        writer.write_record(CSV_HEADER)?;
+    }
+    for (id, title, duration, created_at, tags, has_audio, has_transcript) in rows {
+        let tags: Vec<String> = tags.as_deref().and_then(|json| serde_json::from_str(json).ok()).unwrap_or_default();
+        writer.write_record([
+            id.to_string(),
+            title.clone(),
+            duration.clone(),
+            format_timestamp(*created_at, tz),
+            tags.join(";"),
+            has_audio.to_string(),
+            has_transcript.to_string(),
+        ])?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())
+}
+
+// Fetches recordings kept in object storage into `audio_blob`, so they are exported like the rest.
+async fn load_stored_audio(store: &AudioStore, memos: &mut [voice_memos1::Model], include_audio: bool) -> Result<(), io::Error> {
+    if !include_audio {
+        return Ok(());
+    }
+    for memo in memos.iter_mut().filter(|memo| memo.audio_blob.is_none()) {
+        if let Some(key) = memo.audio_key.as_deref() {
+            memo.audio_blob = Some(store.fetch(key).await.map_err(io::Error::other)?);
+        }
+    }
+    Ok(())
+}
+
+fn encode_page(memos: Vec<voice_memos1::Model>, include_audio: bool, first: &mut bool) -> Result<Vec<u8>, io::Error> {
+    let mut chunk = Vec::new();
+    for memo in memos {
+        if !*first {
+            chunk.push(b',');
+        }
+        *first = false;
+        serde_json::to_writer(&mut chunk, &ArchivedMemo::new(memo, include_audio))?;
+    }
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use poem::test::TestClient;
+    use poem::endpoint::BoxEndpoint;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::test_support::{bearer, login, new_memo, seed_user, test_app, test_db};
+
+    async fn export(client: &TestClient<BoxEndpoint<'static>>, token: &str, include_audio: bool) -> Value {
+        let resp = client
+            .get("/api/me/export")
+            .query("include_audio", &include_audio)
+            .header("Authorization", bearer(token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        serde_json::from_slice(&resp.0.into_body().into_bytes().await.unwrap()).unwrap()
+    }
+
+    // A memo without what the importer assigns anew
+    fn portable(memo: &Value) -> Value {
+        let mut memo = memo.clone();
+        memo.as_object_mut().unwrap().remove("id");
+        memo
+    }
+
+    #[tokio::test]
+    async fn account_export_round_trips_through_import() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let recorded = (Utc::now() - Duration::days(3)).naive_utc();
+        let audio = vec![1u8, 2, 3, 250, 251, 252];
+        let mut voice = new_memo(&user, "Voice note");
+        voice.audio_blob = Set(Some(audio.clone()));
+        voice.transcript = Set(Some("Pick up the keys".to_string()));
+        voice.summary = Set(Some("Keys".to_string()));
+        voice.tags = Set(Some(r#"["errands","home"]"#.to_string()));
+        voice.is_favorite = Set(true);
+        voice.created_at = Set(recorded);
+        voice.updated_at = Set(recorded);
+        voice.insert(&db).await.unwrap();
+        let mut trashed = new_memo(&user, "Thrown away");
+        trashed.created_at = Set(recorded + Duration::hours(1));
+        trashed.deleted_at = Set(Some(recorded + Duration::hours(2)));
+        trashed.insert(&db).await.unwrap();
+        client
+            .post("/api/helper/status")
+            .header("Authorization", bearer(&token))
+            .body_json(&json!({ "status": true }))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let archive = export(&client, &token, true).await;
+        assert_eq!(archive["export_version"], EXPORT_VERSION);
+        assert_eq!(archive["user"]["id"], user.id.to_string());
+        assert_eq!(archive["user"]["email"], user.email);
+        assert_eq!(archive["helper_status"], true);
+        let memos = archive["memos"].as_array().unwrap();
+        assert_eq!(memos.len(), 2);
+        assert_eq!(memos[0]["title"], "Voice note");
+        assert_eq!(memos[0]["tags"], json!(["errands", "home"]));
+        assert_eq!(memos[0]["is_favorite"], true);
+        assert_eq!(STANDARD.decode(memos[0]["audio_base64"].as_str().unwrap()).unwrap(), audio);
+        assert_eq!(memos[1]["title"], "Thrown away");
+        assert!(memos[1]["deleted_at"].is_string());
+
+        // The metadata-only export is the same minus the audio
+        let light = export(&client, &token, false).await;
+        let light_memos = light["memos"].as_array().unwrap();
+        assert!(light_memos.iter().all(|memo| memo.get("audio_base64").is_none()));
+        let mut without_audio = memos[0].clone();
+        without_audio.as_object_mut().unwrap().remove("audio_base64");
+        assert_eq!(light_memos[0], without_audio);
+
+        // Importing the export into another account and exporting that gives the same memos back
+        let copy = seed_user(&db).await;
+        let copy_token = login(&client, &copy).await;
+        // The upload limit wants a Content-Length, which the test client doesn't add on its own
+        let body = serde_json::to_vec(&archive).unwrap();
+        client
+            .post("/api/import_memos")
+            .header("Authorization", bearer(&copy_token))
+            .content_type("application/json")
+            .header("Content-Length", body.len())
+            .body(body)
+            .send()
+            .await
+            .assert_status_is_ok();
+        let copied = export(&client, &copy_token, true).await;
+        let copied_memos: Vec<Value> = copied["memos"].as_array().unwrap().iter().map(portable).collect();
+        let original_memos: Vec<Value> = memos.iter().map(portable).collect();
+        assert_eq!(copied_memos, original_memos);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use poem::web::Data;
+use poem_openapi::{payload::Json, param::{Path, Query}, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::Expr, ConnectionTrait, DatabaseConnection, DbErr, FromQueryResult, SqlErr};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use entity::{folders, voice_memos1};
+use crate::api::auth::{MemosReadAuth, MemosWriteAuth};
+use crate::api::error::ErrorBody;
+
+// --- Folders ---
+// Named folders for memos, one level deep. A memo is in at most one folder; tags still cut across them.
+
+// --- Constants ---
+const MAX_FOLDER_NAME_LEN: usize = 100;
+
+// --- API Structs ---
+
+#[derive(Object, Serialize)]
+pub struct FolderOutput {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    /// Memos in the folder, not counting the trash
+    pub memo_count: u64,
+}
+
+#[derive(Object, Deserialize)]
+pub struct FolderPayload {
+    /// Unique among the caller's folders
+    pub name: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct DeleteFolderResponse {
+    pub message: String,
+    /// Memos taken out of the folder, trashed ones included
+    pub unfiled: u64,
+}
+
+#[derive(FromQueryResult)]
+struct FolderCount {
+    folder_id: Uuid,
+    memo_count: i64,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum FolderApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<FolderOutput>),
+    /// An empty or overlong name, or a malformed folder ID
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The caller already has a folder with this name
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum ListFoldersApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<FolderOutput>>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteFolderApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<DeleteFolderResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The folder still has memos and `force` wasn't set
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct FolderApi;
+
+#[OpenApi]
+impl FolderApi {
+    /// Create a folder
+    #[oai(path = "/folders", method = "post")]
+    async fn create_folder(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<FolderPayload>,
+    ) -> FolderApiResponse {
+        let name = match folder_name(&payload.name) {
+            Ok(name) => name,
+            Err(error) => return FolderApiResponse::BadRequest(error),
+        };
+
+        let saved = folders::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(auth.0.id),
+            name: Set(name),
+            created_at: Set(Utc::now().naive_utc()),
+        }
+        .insert(db.0)
+        .await;
+
+        match saved {
+            Ok(folder) => FolderApiResponse::Ok(Json(FolderOutput::new(folder, 0))),
+            Err(e) => save_error(e),
+        }
+    }
+
+    /// List the caller's folders by name, with how many memos each holds
+    #[oai(path = "/folders", method = "get")]
+    async fn list_folders(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> ListFoldersApiResponse {
+        let user_id = auth.0.id;
+
+        let folders = match folders::Entity::find()
+            .filter(folders::Column::UserId.eq(user_id))
+            .order_by_asc(folders::Column::Name)
+            .all(db.0)
+            .await
+        {
+            Ok(folders) => folders,
+            Err(e) => return ListFoldersApiResponse::InternalServerError(ErrorBody::from_db("Failed to list folders", &e)),
+        };
+        let counts = match memo_counts(db.0, user_id).await {
+            Ok(counts) => counts,
+            Err(e) => return ListFoldersApiResponse::InternalServerError(ErrorBody::from_db("Failed to list folders", &e)),
+        };
+
+        ListFoldersApiResponse::Ok(Json(
+            folders
+                .into_iter()
+                .map(|folder| {
+                    let memo_count = counts.get(&folder.id).copied().unwrap_or(0);
+                    FolderOutput::new(folder, memo_count)
+                })
+                .collect(),
+        ))
+    }
+
+    /// Rename a folder
+    #[oai(path = "/folders/:folder_id", method = "patch")]
+    async fn rename_folder(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(folder_id): Path<String>,
+        Json(payload): Json<FolderPayload>,
+    ) -> FolderApiResponse {
+        let user_id = auth.0.id;
+
+        let Ok(folder_uuid) = Uuid::parse_str(&folder_id) else {
+            return FolderApiResponse::BadRequest(ErrorBody::new("invalid_folder_id", "Invalid folder ID"));
+        };
+        let name = match folder_name(&payload.name) {
+            Ok(name) => name,
+            Err(error) => return FolderApiResponse::BadRequest(error),
+        };
+
+        let folder = match find_owned_folder(db.0, user_id, folder_uuid).await {
+            Ok(Some(folder)) => folder,
+            Ok(None) => return FolderApiResponse::NotFound(ErrorBody::new("not_found", "Folder not found")),
+            Err(e) => return FolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to rename folder", &e)),
+        };
+
+        let mut active: folders::ActiveModel = folder.into();
+        active.name = Set(name);
+        let renamed = match active.update(db.0).await {
+            Ok(renamed) => renamed,
+            Err(e) => return save_error(e),
+        };
+
+        match memo_counts(db.0, user_id).await {
+            Ok(counts) => {
+                let memo_count = counts.get(&renamed.id).copied().unwrap_or(0);
+                FolderApiResponse::Ok(Json(FolderOutput::new(renamed, memo_count)))
+            }
+            Err(e) => FolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to rename folder", &e)),
+        }
+    }
+
+    /// Delete a folder. One with memos is refused unless `force` is set, in which case its memos
+    /// are kept and just taken out of the folder.
+    #[oai(path = "/folders/:folder_id", method = "delete")]
+    async fn delete_folder(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(folder_id): Path<String>,
+        /// Delete the folder even if it has memos
+        force: Query<Option<bool>>,
+    ) -> DeleteFolderApiResponse {
+        let user_id = auth.0.id;
+
+        let Ok(folder_uuid) = Uuid::parse_str(&folder_id) else {
+            return DeleteFolderApiResponse::BadRequest(ErrorBody::new("invalid_folder_id", "Invalid folder ID"));
+        };
+
+        let txn = match db.0.begin().await {
+            Ok(txn) => txn,
+            Err(e) => return DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e)),
+        };
+
+        match find_owned_folder(&txn, user_id, folder_uuid).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return DeleteFolderApiResponse::NotFound(ErrorBody::new("not_found", "Folder not found")),
+            Err(e) => return DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e)),
+        }
+
+        if !force.0.unwrap_or(false) {
+            // Trashed memos don't block the delete; they are unfiled along with the folder
+            let in_folder = voice_memos1::Entity::find()
+                .filter(voice_memos1::Column::UserId.eq(user_id))
+                .filter(voice_memos1::Column::FolderId.eq(folder_uuid))
+                .filter(voice_memos1::Column::DeletedAt.is_null())
+                .count(&txn)
+                .await;
+            match in_folder {
+                Ok(0) => {}
+                Ok(count) => {
+                    return DeleteFolderApiResponse::Conflict(ErrorBody::new(
+                        "folder_not_empty",
+                        format!("The folder has {} memo(s); pass force=true to delete it and keep them outside any folder", count),
+                    ));
+                }
+                Err(e) => return DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e)),
+            }
+        }
+
+        // The foreign key would null these too; doing it here reports how many moved
+        let unfiled = match voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::FolderId, Expr::value(Option::<Uuid>::None))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::FolderId.eq(folder_uuid))
+            .exec(&txn)
+            .await
+        {
+            Ok(res) => res.rows_affected,
+            Err(e) => return DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e)),
+        };
+
+        let deleted = folders::Entity::delete_many()
+            .filter(folders::Column::Id.eq(folder_uuid))
+            .filter(folders::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await;
+        if let Err(e) = deleted {
+            return DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e));
+        }
+
+        match txn.commit().await {
+            Ok(()) => DeleteFolderApiResponse::Ok(Json(DeleteFolderResponse { message: "Folder deleted".to_string(), unfiled })),
+            Err(e) => DeleteFolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete folder", &e)),
+        }
+    }
+}
+
+impl FolderOutput {
+    fn new(folder: folders::Model, memo_count: u64) -> Self {
+        FolderOutput {
+            id: folder.id.to_string(),
+            name: folder.name,
+            created_at: folder.created_at.and_utc().to_rfc3339(),
+            memo_count,
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+/// The caller's folder with this ID, if there is one.
+pub(crate) async fn find_owned_folder<C: ConnectionTrait>(db: &C, user_id: Uuid, folder_id: Uuid) -> Result<Option<folders::Model>, DbErr> {
+    folders::Entity::find_by_id(folder_id)
+        .filter(folders::Column::UserId.eq(user_id))
+        .one(db)
+        .await
+}
+
+fn folder_name(raw: &str) -> Result<String, Json<ErrorBody>> {
+    let name = raw.trim();
+    if name.is_empty() {
+        return Err(ErrorBody::new("invalid_name", "Folder name is required"));
+    }
+    if name.chars().count() > MAX_FOLDER_NAME_LEN {
+        return Err(ErrorBody::new("invalid_name", format!("Folder names are limited to {} characters", MAX_FOLDER_NAME_LEN)));
+    }
+    Ok(name.to_string())
+}
+
+// The unique index on (user_id, name) is what catches a duplicate, including a concurrent one
+fn save_error(err: DbErr) -> FolderApiResponse {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            FolderApiResponse::Conflict(ErrorBody::new("folder_exists", "A folder with this name already exists"))
+        }
+        _ => FolderApiResponse::InternalServerError(ErrorBody::from_db("Failed to save folder", &err)),
+    }
+}
+
+// Memos per folder, leaving out the trash.
+async fn memo_counts(db: &DatabaseConnection, user_id: Uuid) -> Result<HashMap<Uuid, u64>, DbErr> {
+    let counts = voice_memos1::Entity::find()
+        .select_only()
+        .column(voice_memos1::Column::FolderId)
+        .column_as(voice_memos1::Column::Id.count(), "memo_count")
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::FolderId.is_not_null())
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .group_by(voice_memos1::Column::FolderId)
+        .into_model::<FolderCount>()
+        .all(db)
+        .await?;
+
+    Ok(counts.into_iter().map(|row| (row.folder_id, row.memo_count as u64)).collect())
+}
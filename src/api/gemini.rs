@@ -1,30 +1,62 @@
-use base64::{Engine as _, engine::general_purpose::STANDARD};
+use std::sync::OnceLock;
+use std::time::Instant;
 
-use poem::web::Data; // Use poem::web::Data for the database connection
-use poem_openapi::auth::Bearer;
-use poem_openapi::{Object, OpenApi, SecurityScheme, payload::Json, payload::PlainText};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use poem::{http::StatusCode, web::Data}; // Use poem::web::Data for the database connection
+use poem_openapi::{ApiResponse, Enum, Object, OpenApi, param::Path, payload::Json, payload::PlainText};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use entity::{helper_app, users};
-use crate::api::crypto::decrypt;
+use entity::{helper_app, memo_links, users, voice_memos1};
+use crate::api::audio;
+use crate::api::auth::{AiInvokeAuth, SessionAuth};
+use crate::api::crypto::Crypto;
+use crate::api::error::ErrorBody;
+use crate::api::limits::upload_limit;
+use crate::api::preferences::{preferences_or_default, UserPreferences};
+use crate::api::sandbox;
+use crate::api::usage::{consume_quota, AiOperation, QuotaError};
+use crate::metrics;
 use sea_orm::{DatabaseConnection, entity::*, query::*};
-use crate::api::memo_api_store_ops::get_user_from_token; 
+use uuid::Uuid;
 
 pub struct GeminiApi;
 
+const DEFAULT_GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+// Where Gemini calls go; only tests point it anywhere else
+static GEMINI_API_BASE: OnceLock<String> = OnceLock::new();
+
 
 
 // --- Structs for API Payloads ---
 
 #[derive(Debug, Deserialize, Object)]
 pub struct AudioBufferRequest {
+    /// The audio as a byte array; send either this or `audio_base64`
+    #[oai(default)]
+    #[serde(default)]
     pub audio_bytes: Vec<u8>,
+    /// The audio as standard base64, which is much smaller in JSON than a byte array
+    pub audio_base64: Option<String>,
+    /// e.g. `audio/mpeg` or `audio/mp4`; detected from the bytes when omitted, WAV if that fails
+    pub mime_type: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Object)]
 pub struct TranslateRequest {
-    pub lang: String,
+    /// Target language; the `default_translate_lang` preference when omitted
+    pub lang: Option<String>,
     pub text: String,
+    /// Language of `text`; detected by Gemini when omitted
+    pub source_lang: Option<String>,
+}
+
+#[derive(Debug, Serialize, Object)]
+pub struct TranslationOutput {
+    /// `source_lang` as given, or the language Gemini detected. Absent if detection failed.
+    #[oai(skip_serializing_if_is_none)]
+    pub detected_source: Option<String>,
+    pub target: String,
+    pub translated_text: String,
 }
 
 #[derive(Debug, Deserialize, Object)]
@@ -35,120 +67,438 @@ pub struct GenerateTitle {
 #[derive(Debug, Deserialize, Object)]
 pub struct SummaryRequest {
     pub text: String,
+    /// How long the summary should be; the `summary_style` preference when omitted
+    pub length: Option<SummaryLength>,
+}
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryLength {
+    /// One or two sentences
+    Short,
+    #[default]
+    Medium,
+    /// A markdown list of the key points, one `- ` line each
+    Bullets,
+}
+
+impl SummaryLength {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            SummaryLength::Short => "short",
+            SummaryLength::Medium => "medium",
+            SummaryLength::Bullets => "bullets",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Option<SummaryLength> {
+        [SummaryLength::Short, SummaryLength::Medium, SummaryLength::Bullets]
+            .into_iter()
+            .find(|length| length.as_str() == value)
+    }
+
+    fn instruction(self) -> &'static str {
+        match self {
+            SummaryLength::Short => "Summarize the following text in one or two sentences. Return only the summary:",
+            SummaryLength::Medium => "Provide a concise summary of the following text. Keep it brief and capture the main points:",
+            SummaryLength::Bullets => "Summarize the key points of the following text as a markdown bullet list, one \"- \" line per point and nothing else:",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Object)]
+pub struct ExtractRequest {
+    pub transcript: String,
+    pub mode: ExtractMode,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Deserialize)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractMode {
+    ActionItems,
+    Keywords,
+    Topics,
+}
+
+impl ExtractMode {
+    fn instruction(self) -> &'static str {
+        match self {
+            ExtractMode::ActionItems => "List the action items in the following transcript: tasks someone committed to or was asked to do, each as a short imperative sentence, with the owner if one is named.",
+            ExtractMode::Keywords => "List the most important keywords and key phrases in the following transcript, at most 15.",
+            ExtractMode::Topics => "List the distinct topics discussed in the following transcript, each as a short phrase.",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Object)]
+pub struct LinkSuggestion {
+    pub memo_id: String,
+    pub title: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Object)]
+pub struct SandboxSettingPayload {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Object)]
+pub struct AiCapabilities {
+    /// AI endpoints return deterministic fake output instead of calling Gemini
+    pub sandbox: bool,
+    /// Sandbox mode is forced for the whole deployment and can't be turned off per user
+    pub sandbox_forced: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RawTranslation {
+    detected_source: Option<String>,
+    translated_text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLinkSuggestion {
+    memo_id: String,
+    #[serde(default)]
+    reason: String,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum AiTextResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    /// No Gemini key configured
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// The daily AI quota is used up
+    #[oai(status = 429)]
+    QuotaExceeded(Json<ErrorBody>),
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub email: String,
-    pub exp: usize,
+impl AiTextResponse {
+    fn generated(text: String, provider: &AiProvider) -> Self {
+        AiTextResponse::Ok(PlainText(text), provider.is_sandbox().then_some(true))
+    }
 }
 
-// --- Security Scheme Definition for Swagger ---
+#[derive(ApiResponse)]
+enum TranscribeApiResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    /// No audio, invalid base64, an unsupported audio type or no Gemini key
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// The daily AI quota is used up
+    #[oai(status = 429)]
+    QuotaExceeded(Json<ErrorBody>),
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
+}
 
-#[derive(SecurityScheme)]
-#[oai(ty = "bearer", bearer_format = "JWT")]
-struct ApiKeyAuth(Bearer);
+#[derive(ApiResponse)]
+enum TranslateApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<TranslationOutput>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    /// No Gemini key configured, or no target language given or preferred
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 429)]
+    QuotaExceeded(Json<ErrorBody>),
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum ExtractApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<String>>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    /// No Gemini key configured
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 429)]
+    QuotaExceeded(Json<ErrorBody>),
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
+}
 
 #[OpenApi]
 impl GeminiApi {
-    #[oai(path = "/transcribe", method = "post")]
+    #[oai(path = "/transcribe", method = "post", transform = "upload_limit")]
     async fn transcribe_audio(
         &self,
-        auth: ApiKeyAuth,
+        auth: AiInvokeAuth,
         db: Data<&DatabaseConnection>, // Use poem::web::Data
+        crypto: Data<&Crypto>,
         Json(payload): Json<AudioBufferRequest>,
-    ) -> PlainText<String> {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user) => user,
-            Err(err) => return PlainText(format!("User fetch error: {}", err.0.message)),
+    ) -> TranscribeApiResponse {
+        let user = auth.0;
+
+        let audio_bytes = match (payload.audio_bytes.is_empty(), payload.audio_base64.as_deref()) {
+            (false, None) => payload.audio_bytes,
+            (true, Some(encoded)) => match STANDARD.decode(encoded.trim()) {
+                Ok(decoded) if !decoded.is_empty() => decoded,
+                Ok(_) => return TranscribeApiResponse::BadRequest(ErrorBody::new("audio_required", "Audio is required")),
+                Err(e) => return TranscribeApiResponse::BadRequest(ErrorBody::new("invalid_base64", format!("audio_base64 is not valid base64: {}", e))),
+            },
+            (false, Some(_)) => return TranscribeApiResponse::BadRequest(ErrorBody::new("ambiguous_audio", "Send either audio_bytes or audio_base64, not both")),
+            (true, None) => return TranscribeApiResponse::BadRequest(ErrorBody::new("audio_required", "Audio is required")),
         };
 
-        let gemini_api_key = match get_decrypted_gemini_key(&user, db.0).await {
-            Ok(key) => key,
-            Err(msg) => return PlainText(msg),
+        let mime_type = match audio::resolve_mime(payload.mime_type.as_deref(), &audio_bytes) {
+            Ok(mime_type) => mime_type,
+            Err(msg) => return TranscribeApiResponse::BadRequest(ErrorBody::new("unsupported_audio_type", msg)),
         };
 
-        match transcribe_with_gemini(&payload.audio_bytes, &gemini_api_key).await {
-            Ok(transcription) => PlainText(transcription),
-            Err(err) => PlainText(format!("Transcription Error: {}", err)),
+        let provider = match AiProvider::for_user(&user, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return TranscribeApiResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
+        };
+
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Transcribe).await {
+            return TranscribeApiResponse::QuotaExceeded(ErrorBody::new("quota_exceeded", msg));
+        }
+
+        match provider.transcribe(&audio_bytes, mime_type).await {
+            Ok(transcription) => TranscribeApiResponse::Ok(PlainText(transcription), provider.is_sandbox().then_some(true)),
+            Err(err) => TranscribeApiResponse::BadGateway(ErrorBody::new("upstream_error", format!("Transcription Error: {}", err))),
         }
     }
 
+    /// Translate text. Without `source_lang`, the response says which language was detected.
     #[oai(path = "/translate", method = "post")]
     async fn gemini_translate(
         &self,
-        auth: ApiKeyAuth,
+        auth: AiInvokeAuth,
         db: Data<&DatabaseConnection>, // <-- FIX: Add DB connection
+        crypto: Data<&Crypto>,
         Json(payload): Json<TranslateRequest>,
-    ) -> PlainText<String> {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user) => user,
-            Err(err) => return PlainText(format!("User fetch error: {}", err.0.message)),
+    ) -> TranslateApiResponse {
+        let user = auth.0;
+        let preferences = preferences_or_default(db.0, user.id).await;
+
+        let requested = payload.lang.map(|lang| lang.trim().to_string()).filter(|lang| !lang.is_empty());
+        let Some(target) = requested.or_else(|| preferences.default_translate_lang.clone()) else {
+            return TranslateApiResponse::BadRequest(ErrorBody::new("missing_language", "No target language given and no default_translate_lang preference set"));
         };
 
-        let gemini_api_key = match get_decrypted_gemini_key(&user, db.0).await {
-            Ok(key) => key,
-            Err(msg) => return PlainText(msg),
+        let provider = match AiProvider::with_preferences(&user, &preferences, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return TranslateApiResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
         };
 
-        match translate_with_gemini(&payload.text, &payload.lang, &gemini_api_key).await { // <-- FIX: Pass key
-            Ok(result) => PlainText(result),
-            Err(err) => PlainText(format!("Error: {}", err)),
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Translate).await {
+            return TranslateApiResponse::QuotaExceeded(ErrorBody::new("quota_exceeded", msg));
+        }
+
+        let source_lang = payload.source_lang.as_deref().map(str::trim).filter(|lang| !lang.is_empty());
+        match provider.translate(&payload.text, &target, source_lang).await {
+            Ok(translation) => TranslateApiResponse::Ok(
+                Json(TranslationOutput {
+                    detected_source: translation.detected_source,
+                    target,
+                    translated_text: translation.translated_text,
+                }),
+                provider.is_sandbox().then_some(true),
+            ),
+            Err(err) => TranslateApiResponse::BadGateway(ErrorBody::new("upstream_error", format!("Error: {}", err))),
         }
     }
 
     #[oai(path = "/summary", method = "post")]
     async fn gemini_client(
         &self,
-        auth: ApiKeyAuth,
+        auth: AiInvokeAuth,
         db: Data<&DatabaseConnection>, // <-- FIX: Add DB connection
+        crypto: Data<&Crypto>,
         Json(payload): Json<SummaryRequest>,
-    ) -> PlainText<String> {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user) => user,
-            Err(err) => return PlainText(format!("User fetch error: {}", err.0.message)),
-        };
+    ) -> AiTextResponse {
+        let user = auth.0;
+        let preferences = preferences_or_default(db.0, user.id).await;
         
-        let gemini_api_key = match get_decrypted_gemini_key(&user, db.0).await {
-            Ok(key) => key,
-            Err(msg) => return PlainText(msg),
+        let provider = match AiProvider::with_preferences(&user, &preferences, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return AiTextResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
         };
 
-        match summarize_text(&payload.text, &gemini_api_key).await { // <-- FIX: Pass key
-            Ok(result) => PlainText(result),
-            Err(err) => PlainText(format!("Error: {}", err)),
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Summary).await {
+            return AiTextResponse::QuotaExceeded(ErrorBody::new("quota_exceeded", msg));
+        }
+
+        match provider.summarize(&payload.text, payload.length.unwrap_or(preferences.summary_style)).await {
+            Ok(result) => AiTextResponse::generated(result, &provider),
+            Err(err) => AiTextResponse::BadGateway(ErrorBody::new("upstream_error", format!("Error: {}", err))),
         }
     }
 
     #[oai(path = "/generate_memo_name", method = "post")]
     async fn gemini_generate_memo_name(
         &self,
-        auth: ApiKeyAuth,
+        auth: AiInvokeAuth,
         db: Data<&DatabaseConnection>, // <-- FIX: Add DB connection
+        crypto: Data<&Crypto>,
         Json(payload): Json<GenerateTitle>,
-    ) -> PlainText<String> {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user) => user,
-            Err(err) => return PlainText(format!("User fetch error: {}", err.0.message)),
-        };
+    ) -> AiTextResponse {
+        let user = auth.0;
         
-        let gemini_api_key = match get_decrypted_gemini_key(&user, db.0).await {
-            Ok(key) => key,
-            Err(msg) => return PlainText(msg),
+        let provider = match AiProvider::for_user(&user, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return AiTextResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
         };
         
-        match generate_title(&payload.transcript, &gemini_api_key).await { // <-- FIX: Pass key
-            Ok(result) => PlainText(result),
-            Err(err) => PlainText(format!("Error: {}", err)),
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Title).await {
+            return AiTextResponse::QuotaExceeded(ErrorBody::new("quota_exceeded", msg));
+        }
+
+        match provider.title(&payload.transcript).await {
+            Ok(result) => AiTextResponse::generated(result, &provider),
+            Err(err) => AiTextResponse::BadGateway(ErrorBody::new("upstream_error", format!("Error: {}", err))),
         }
     }
+
+    /// Pull action items, keywords or topics out of a transcript, as a list of strings
+    #[oai(path = "/extract", method = "post")]
+    async fn gemini_extract(
+        &self,
+        auth: AiInvokeAuth,
+        db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
+        Json(payload): Json<ExtractRequest>,
+    ) -> ExtractApiResponse {
+        let user = auth.0;
+
+        let provider = match AiProvider::for_user(&user, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return ExtractApiResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
+        };
+
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Extract).await {
+            return ExtractApiResponse::QuotaExceeded(ErrorBody::new("quota_exceeded", msg));
+        }
+
+        match provider.extract(&payload.transcript, payload.mode).await {
+            Ok(items) => ExtractApiResponse::Ok(Json(items), provider.is_sandbox().then_some(true)),
+            Err(err) => ExtractApiResponse::BadGateway(ErrorBody::new("upstream_error", format!("Error: {}", err))),
+        }
+    }
+
+    /// Ask Gemini which of the user's other memos this memo refers to. Links are never created automatically.
+    #[oai(path = "/memo/:memo_id/links/suggestions", method = "get")]
+    async fn gemini_suggest_links(
+        &self,
+        auth: AiInvokeAuth,
+        db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
+        Path(memo_id): Path<String>,
+    ) -> poem::Result<Json<Vec<LinkSuggestion>>> {
+        let user = auth.0;
+
+        let memo_uuid = Uuid::parse_str(&memo_id)
+            .map_err(|_| ErrorBody::error(StatusCode::BAD_REQUEST, ErrorBody::new("invalid_memo_id", "Invalid memo ID")))?;
+
+        let memo = voice_memos1::Entity::find_by_id(memo_uuid)
+            .filter(voice_memos1::Column::UserId.eq(user.id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .ok_or_else(|| ErrorBody::error(StatusCode::NOT_FOUND, ErrorBody::new("not_found", "Memo not found or access denied")))?;
+
+        let already_linked: Vec<Uuid> = memo_links::Entity::find()
+            .filter(memo_links::Column::FromMemoId.eq(memo.id))
+            .all(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .into_iter()
+            .map(|link| link.to_memo_id)
+            .collect();
+
+        let candidates: Vec<voice_memos1::Model> = voice_memos1::Entity::find()
+            .filter(voice_memos1::Column::UserId.eq(user.id))
+            .filter(voice_memos1::Column::Id.ne(memo.id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .all(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .into_iter()
+            .filter(|candidate| !already_linked.contains(&candidate.id))
+            .collect();
+
+        let text = memo.transcript.as_deref().or(memo.summary.as_deref()).unwrap_or_default();
+        if candidates.is_empty() || text.trim().is_empty() {
+            return Ok(Json(vec![]));
+        }
+
+        let provider = AiProvider::for_user(&user, db.0, crypto.0)
+            .await
+            .map_err(|msg| ErrorBody::error(StatusCode::BAD_REQUEST, ErrorBody::new("no_api_key", msg)))?;
+
+        let library: Vec<serde_json::Value> = candidates
+            .iter()
+            .map(|c| serde_json::json!({ "memo_id": c.id.to_string(), "title": c.title, "created_at": c.created_at.to_string() }))
+            .collect();
+
+        provider
+            .meter(db.0, user.id, AiOperation::SuggestLinks)
+            .await
+            .map_err(|msg| ErrorBody::error(StatusCode::TOO_MANY_REQUESTS, ErrorBody::new("quota_exceeded", msg)))?;
+
+        let raw = provider
+            .suggest_links(text, &memo.created_at.to_string(), &serde_json::Value::Array(library).to_string())
+            .await
+            .map_err(|err| ErrorBody::error(StatusCode::BAD_GATEWAY, ErrorBody::new("upstream_error", err)))?;
+
+        let suggestions = parse_link_suggestions(&raw)
+            .into_iter()
+            .filter_map(|s| {
+                let candidate = candidates.iter().find(|c| c.id.to_string() == s.memo_id)?;
+                Some(LinkSuggestion { memo_id: s.memo_id, title: candidate.title.clone(), reason: s.reason })
+            })
+            .collect();
+
+        Ok(Json(suggestions))
+    }
+
+    /// Which AI mode applies to the current user, so the UI can badge sandbox output
+    #[oai(path = "/ai/capabilities", method = "get")]
+    async fn ai_capabilities(
+        &self,
+        auth: SessionAuth,
+    ) -> poem::Result<Json<AiCapabilities>> {
+        Ok(Json(AiCapabilities::for_user(&auth.0)))
+    }
+
+    /// Turn the sandbox AI provider on or off for the current user
+    #[oai(path = "/ai/sandbox", method = "put")]
+    async fn set_ai_sandbox(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<SandboxSettingPayload>,
+    ) -> poem::Result<Json<AiCapabilities>> {
+        let mut active_user: users::ActiveModel = auth.0.0.into();
+        active_user.sandbox_ai = Set(payload.enabled);
+        let user = active_user.update(db.0).await.map_err(poem::error::InternalServerError)?;
+
+        Ok(Json(AiCapabilities::for_user(&user)))
+    }
+}
+
+impl AiCapabilities {
+    fn for_user(user: &users::Model) -> Self {
+        AiCapabilities { sandbox: sandbox::enabled_for(user), sandbox_forced: sandbox::forced() }
+    }
 }
 
 
 // --- Refactored Helper Function for fetching the key ---
 // This function avoids code duplication in your API handlers.
-async fn get_decrypted_gemini_key(user: &users::Model, db: &DatabaseConnection) -> Result<String, String> {
+pub(crate) async fn get_decrypted_gemini_key(user: &users::Model, db: &DatabaseConnection, crypto: &Crypto) -> Result<String, String> {
     let key_record = helper_app::Entity::find()
         .filter(helper_app::Column::UserId.eq(user.id))
         .one(db)
@@ -159,7 +509,7 @@ async fn get_decrypted_gemini_key(user: &users::Model, db: &DatabaseConnection)
     let decrypted_key = key_record
         .gemini_key
         .as_deref()
-        .and_then(|k| decrypt(k).ok())
+        .and_then(|k| crypto.decrypt(k).ok())
         .ok_or_else(|| "Failed to decrypt Gemini API key".to_string())?;
 
     Ok(decrypted_key)
@@ -169,14 +519,131 @@ async fn get_decrypted_gemini_key(user: &users::Model, db: &DatabaseConnection)
 
 
 
+// --- AI Provider ---
+
+/// The models users can pick with the `preferred_model` preference; the first is the default.
+pub(crate) const GEMINI_MODELS: &[&str] = &["gemini-2.0-flash", "gemini-2.0-flash-lite", "gemini-2.5-flash", "gemini-2.5-pro"];
+
+/// The model name as listed in `GEMINI_MODELS`, or the default for anything not listed.
+pub(crate) fn gemini_model(name: Option<&str>) -> &'static str {
+    name.and_then(|name| GEMINI_MODELS.iter().find(|model| **model == name))
+        .copied()
+        .unwrap_or(GEMINI_MODELS[0])
+}
+
+/// Where a user's AI requests go: Gemini with their own key and preferred model, or the deterministic sandbox.
+pub(crate) enum AiProvider {
+    Gemini { key: String, model: &'static str },
+    Sandbox,
+}
+
+impl AiProvider {
+    pub(crate) async fn for_user(user: &users::Model, db: &DatabaseConnection, crypto: &Crypto) -> Result<Self, String> {
+        let preferences = preferences_or_default(db, user.id).await;
+        Self::with_preferences(user, &preferences, db, crypto).await
+    }
+
+    /// Same as `for_user`, for handlers that already loaded the preferences.
+    pub(crate) async fn with_preferences(user: &users::Model, preferences: &UserPreferences, db: &DatabaseConnection, crypto: &Crypto) -> Result<Self, String> {
+        if sandbox::enabled_for(user) {
+            return Ok(AiProvider::Sandbox);
+        }
+        let key = get_decrypted_gemini_key(user, db, crypto).await?;
+        Ok(AiProvider::Gemini { key, model: gemini_model(Some(&preferences.preferred_model)) })
+    }
+
+    pub(crate) fn is_sandbox(&self) -> bool {
+        matches!(self, AiProvider::Sandbox)
+    }
+
+    /// Counts a call against the user's daily quota; `Err` with a message once it is used up.
+    /// Sandbox calls never reach Gemini and are free.
+    pub(crate) async fn meter(&self, db: &DatabaseConnection, user_id: Uuid, operation: AiOperation) -> Result<(), String> {
+        if self.is_sandbox() {
+            return Ok(());
+        }
+        match consume_quota(db, user_id, operation).await {
+            Ok(()) => Ok(()),
+            Err(QuotaError::Exceeded { limit }) => Err(format!("Daily AI quota of {} calls reached, try again tomorrow", limit)),
+            // Metering is there to protect the upstream API, not worth failing the user's request over
+            Err(QuotaError::Db(e)) => {
+                tracing::warn!("Failed to record AI usage: {:?}", e);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) async fn transcribe(&self, audio_bytes: &[u8], mime_type: &str) -> Result<String, String> {
+        match self {
+            AiProvider::Gemini { key, model } => transcribe_with_gemini(audio_bytes, mime_type, key, model).await,
+            AiProvider::Sandbox => Ok(sandbox::transcribe(audio_bytes)),
+        }
+    }
+
+    pub(crate) async fn translate(&self, text: &str, target_lang: &str, source_lang: Option<&str>) -> Result<RawTranslation, String> {
+        match self {
+            AiProvider::Gemini { key, model } => translate_with_gemini(text, target_lang, source_lang, key, model).await,
+            AiProvider::Sandbox => Ok(RawTranslation {
+                detected_source: Some(source_lang.unwrap_or(sandbox::DETECTED_LANGUAGE).to_string()),
+                translated_text: sandbox::translate(text, target_lang),
+            }),
+        }
+    }
+
+    pub(crate) async fn summarize(&self, text: &str, length: SummaryLength) -> Result<String, String> {
+        match self {
+            AiProvider::Gemini { key, model } => summarize_text(text, length, key, model).await,
+            AiProvider::Sandbox => Ok(sandbox::summarize(text, length)),
+        }
+    }
+
+    pub(crate) async fn title(&self, transcript: &str) -> Result<String, String> {
+        match self {
+            AiProvider::Gemini { key, model } => generate_title(transcript, key, model).await,
+            AiProvider::Sandbox => Ok(sandbox::title(transcript)),
+        }
+    }
+
+    async fn extract(&self, transcript: &str, mode: ExtractMode) -> Result<Vec<String>, String> {
+        match self {
+            AiProvider::Gemini { key, model } => extract_with_gemini(transcript, mode, key, model).await.map(|raw| parse_extracted(&raw)),
+            AiProvider::Sandbox => Ok(sandbox::extract(transcript)),
+        }
+    }
+
+    async fn suggest_links(&self, text: &str, recorded_at: &str, library_json: &str) -> Result<String, String> {
+        match self {
+            AiProvider::Gemini { key, model } => suggest_links(text, recorded_at, library_json, key, model).await,
+            AiProvider::Sandbox => Ok(sandbox::suggest_links()),
+        }
+    }
+}
+
 // --- Gemini Client and Helper Functions ---
 // Ensure these functions correctly receive the api_key parameter.
 
-pub async fn gemini_client(contents: serde_json::Value, key: &str) -> Result<String, String> {
+pub async fn gemini_client(contents: serde_json::Value, key: &str, model: &str) -> Result<String, String> {
+    let started = Instant::now();
+    let result = call_gemini(contents, key, model).await;
+    metrics::record_gemini_call(model, result.is_ok(), started.elapsed());
+    result
+}
+
+/// Sends Gemini calls to a stand-in at `base` instead. The first caller sets it.
+#[cfg(test)]
+pub fn init_test_gemini_base(base: String) {
+    let _ = GEMINI_API_BASE.set(base);
+}
+
+fn gemini_api_base() -> &'static str {
+    GEMINI_API_BASE.get_or_init(|| DEFAULT_GEMINI_API_BASE.to_string())
+}
+
+async fn call_gemini(contents: serde_json::Value, key: &str, model: &str) -> Result<String, String> {
     let client = Client::new();
 
     let res = client
-        .post("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent")
+        .post(format!("{}/models/{}:generateContent", gemini_api_base(), model))
         .query(&[("key", key)]) // Key is used here
         .json(&serde_json::json!({ "contents": [contents] }))
         .send()
@@ -211,45 +678,107 @@ pub async fn gemini_client(contents: serde_json::Value, key: &str) -> Result<Str
     }
 }
 
-pub async fn transcribe_with_gemini(audio_bytes: &[u8], api_key: &str) -> Result<String, String> {
+pub async fn transcribe_with_gemini(audio_bytes: &[u8], mime_type: &str, api_key: &str, model: &str) -> Result<String, String> {
     let base64_audio = STANDARD.encode(audio_bytes);
     let content = serde_json::json!({
         "parts": [
             { "text": "Please transcribe this audio." },
             {
                 "inline_data": {
-                    "mime_type": "audio/wav",
+                    "mime_type": mime_type,
                     "data": base64_audio
                 }
             }
         ]
     });
-    gemini_client(content, api_key).await
+    gemini_client(content, api_key, model).await
 }
 
-pub async fn translate_with_gemini(text: &str, target_lang: &str, api_key: &str) -> Result<String, String> {
+// With no source language, Gemini is asked for JSON so the detected language comes back alongside the text.
+async fn translate_with_gemini(text: &str, target_lang: &str, source_lang: Option<&str>, api_key: &str, model: &str) -> Result<RawTranslation, String> {
+    if let Some(source_lang) = source_lang {
+        let content = serde_json::json!({
+            "parts": [
+                { "text": format!( "Translate the following text from {} to {}. Return only the translated text without any extra formatting or explanation:\n\n{}", source_lang, target_lang, text) }
+            ]
+        });
+        let translated_text = gemini_client(content, api_key, model).await?;
+        return Ok(RawTranslation { detected_source: Some(source_lang.to_string()), translated_text });
+    }
+
     let content = serde_json::json!({
         "parts": [
-            { "text": format!( "Translate the following text to {}. Return only the translated text without any extra formatting or explanation:\n\n{}", target_lang, text) }
+            { "text": format!( "Detect the language of the following text and translate it to {}. Return only a JSON object with \"detected_source\" (the English name of the source language) and \"translated_text\":\n\n{}", target_lang, text) }
         ]
     });
-    gemini_client(content, api_key).await
+    let raw = gemini_client(content, api_key, model).await?;
+    // If Gemini ignored the format, keep the translation and just go without the label
+    Ok(serde_json::from_str(strip_code_fence(&raw)).unwrap_or(RawTranslation {
+        detected_source: None,
+        translated_text: raw.trim().to_string(),
+    }))
 }
 
-pub async fn summarize_text(text: &str, api_key: &str) -> Result<String, String> {
+pub async fn summarize_text(text: &str, length: SummaryLength, api_key: &str, model: &str) -> Result<String, String> {
     let content = serde_json::json!({
         "parts": [
-            { "text": format!("Provide a concise summary of the following text. Keep it brief and capture the main points:\n\n{}", text) }
+            { "text": format!("{}\n\n{}", length.instruction(), text) }
         ]
     });
-    gemini_client(content, api_key).await
+    gemini_client(content, api_key, model).await
 }
 
-pub async fn generate_title(transcript: &str, api_key: &str) -> Result<String, String> {
+pub async fn generate_title(transcript: &str, api_key: &str, model: &str) -> Result<String, String> {
     let content = serde_json::json!({
         "parts":[
             {"text":format!("Generate a short, descriptive title (2-4 words) for this voice memo based on its content. Return only the title:\n\n{}",transcript)}
         ]
     });
-    gemini_client(content, api_key).await
-}
\ No newline at end of file
+    gemini_client(content, api_key, model).await
+}
+
+pub async fn extract_with_gemini(transcript: &str, mode: ExtractMode, api_key: &str, model: &str) -> Result<String, String> {
+    let content = serde_json::json!({
+        "parts":[
+            {"text":format!("{} Return only a JSON array of strings, or [] if there are none.\n\n{}", mode.instruction(), transcript)}
+        ]
+    });
+    gemini_client(content, api_key, model).await
+}
+
+pub async fn suggest_links(text: &str, recorded_at: &str, library_json: &str, api_key: &str, model: &str) -> Result<String, String> {
+    let content = serde_json::json!({
+        "parts":[
+            {"text":format!("This voice memo was recorded at {}. Find the memos from the library below that it explicitly refers to, by title or by a mentioned date (resolve relative dates like \"Tuesday\" against the recording time). Return only a JSON array of objects with \"memo_id\" and a short \"reason\", or [] if none match.\n\nLibrary:\n{}\n\nMemo:\n{}", recorded_at, library_json, text)}
+        ]
+    });
+    gemini_client(content, api_key, model).await
+}
+
+// Anything unparseable yields no suggestions.
+fn parse_link_suggestions(raw: &str) -> Vec<RawLinkSuggestion> {
+    serde_json::from_str(strip_code_fence(raw)).unwrap_or_default()
+}
+
+// If Gemini ignored the format, fall back to one item per line, without list markers.
+fn parse_extracted(raw: &str) -> Vec<String> {
+    let raw = strip_code_fence(raw);
+    match serde_json::from_str::<Vec<String>>(raw) {
+        Ok(items) => items.into_iter().map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect(),
+        Err(_) => raw
+            .lines()
+            .map(|line| line.trim().trim_start_matches(['-', '*', '•']).trim())
+            .filter(|line| !line.is_empty() && *line != "[]")
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+// Gemini often wraps JSON in a markdown code fence.
+fn strip_code_fence(raw: &str) -> &str {
+    raw.trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim()
+}
@@ -0,0 +1,308 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, ApiResponse, Enum, Object, OpenApi};
+use sea_orm::{entity::*, query::*, DatabaseConnection, DatabaseTransaction, DbErr, TransactionTrait};
+use serde::Serialize;
+use uuid::Uuid;
+use std::collections::HashSet;
+
+use entity::voice_memos1;
+use crate::api::auth::MemosWriteAuth;
+use crate::api::error::ErrorBody;
+use crate::api::export::{ArchivedMemo, MemoArchive, EXPORT_VERSION};
+use crate::api::limits::{max_audio_bytes, upload_limit};
+use crate::api::memo::{normalize_tags, parse_duration_seconds, sync_memo_tags};
+use crate::api::storage::AudioStore;
+
+// --- Constants ---
+// Each chunk is saved in its own transaction, so a failure only costs the chunk it happens in
+const IMPORT_CHUNK_SIZE: usize = 100;
+
+// --- API Structs ---
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Skip memos with the same title and `created_at` as one the caller already has
+    #[default]
+    Skip,
+    /// Import every memo, even ones that look already imported
+    Duplicate,
+}
+
+/// A memo of the archive that wasn't imported, and why.
+#[derive(Object, Serialize)]
+pub struct ImportIssue {
+    /// Position in the archive's `memos`
+    pub index: usize,
+    #[oai(skip_serializing_if_is_none)]
+    pub id: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Memos that are already there
+    pub skipped: Vec<ImportIssue>,
+    /// Invalid memos, and valid ones the database refused
+    pub failed: Vec<ImportIssue>,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum ImportApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<ImportSummary>),
+    /// The archive is from a newer version of the export format. A body that isn't an archive at
+    /// all is refused with 400 before the handler runs.
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct ImportApi;
+
+#[OpenApi]
+impl ImportApi {
+    /// Recreate the memos of an archive from `/export_memos` for the caller, with new IDs and the
+    /// archive's timestamps. Memos are saved in chunks, each in its own transaction; a memo the
+    /// database refuses is reported under `failed` without holding back the others.
+    #[oai(path = "/import_memos", method = "post", transform = "upload_limit")]
+    async fn import_memos(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        /// What to do with memos that look already imported (default `skip`)
+        mode: Query<Option<ImportMode>>,
+        Json(archive): Json<MemoArchive>,
+    ) -> ImportApiResponse {
+        import_archive(db.0, store.0, auth.0.id, archive, mode.0.unwrap_or_default()).await
+    }
+
+    /// Deprecated: use `/import_memos`. Imports every memo, as it always has.
+    #[oai(path = "/import", method = "post", transform = "upload_limit", deprecated)]
+    async fn import_memos_legacy(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Json(archive): Json<MemoArchive>,
+    ) -> ImportApiResponse {
+        import_archive(db.0, store.0, auth.0.id, archive, ImportMode::Duplicate).await
+    }
+}
+
+// --- Helper Functions ---
+
+struct ValidMemo {
+    index: usize,
+    /// The new memo's ID, picked up front so uploaded audio can be filed under it
+    id: Uuid,
+    memo: ArchivedMemo,
+    tags: Vec<String>,
+    audio: Option<Vec<u8>>,
+    /// Set once the audio is uploaded to object storage, which takes it out of `audio`
+    audio_key: Option<String>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    deleted_at: Option<NaiveDateTime>,
+}
+
+impl ValidMemo {
+    fn issue(&self, reason: impl Into<String>) -> ImportIssue {
+        ImportIssue { index: self.index, id: self.memo.id.clone(), reason: reason.into() }
+    }
+}
+
+async fn import_archive(db: &DatabaseConnection, store: &AudioStore, user_id: Uuid, archive: MemoArchive, mode: ImportMode) -> ImportApiResponse {
+    let version = archive.export_version.unwrap_or(1);
+    if version > EXPORT_VERSION {
+        return ImportApiResponse::BadRequest(ErrorBody::new(
+            "unsupported_export_version",
+            format!("Archive is export_version {}; this server reads up to {}", version, EXPORT_VERSION),
+        ));
+    }
+    // Validated in main, so this only falls back if called outside the server
+    let max_audio_bytes = max_audio_bytes().unwrap_or(usize::MAX);
+
+    let mut valid = Vec::new();
+    let mut failed = Vec::new();
+    for (index, memo) in archive.memos.into_iter().enumerate() {
+        let id = memo.id.clone();
+        match validate(index, memo, max_audio_bytes) {
+            Ok(memo) => valid.push(memo),
+            Err(reason) => failed.push(ImportIssue { index, id, reason }),
+        }
+    }
+
+    let mut imported = 0;
+    let mut skipped = Vec::new();
+    // Also catches the same memo twice in one archive
+    let mut seen = HashSet::new();
+    let mut remaining = valid.into_iter().peekable();
+    while remaining.peek().is_some() {
+        let mut chunk: Vec<ValidMemo> = remaining.by_ref().take(IMPORT_CHUNK_SIZE).collect();
+
+        if mode == ImportMode::Skip {
+            let existing = match existing_keys(db, user_id, &chunk).await {
+                Ok(existing) => existing,
+                Err(e) => {
+                    let reason = ErrorBody::from_db("Failed to check for already imported memos", &e).0.message;
+                    failed.extend(chunk.iter().map(|memo| memo.issue(reason.clone())));
+                    continue;
+                }
+            };
+            chunk.retain(|memo| {
+                let key = (memo.memo.title.clone(), memo.created_at);
+                let duplicate = existing.contains(&key) || !seen.insert(key);
+                if duplicate {
+                    skipped.push(memo.issue("A memo with the same title and created_at already exists"));
+                }
+                !duplicate
+            });
+        }
+
+        // Uploaded once up front, so retrying a chunk doesn't upload again
+        if store.uses_object_storage() {
+            let mut uploaded = Vec::with_capacity(chunk.len());
+            for mut memo in chunk {
+                let Some(audio) = memo.audio.take() else {
+                    uploaded.push(memo);
+                    continue;
+                };
+                match store.put(memo.id, audio).await {
+                    Ok(stored) => {
+                        memo.audio_key = stored.key;
+                        uploaded.push(memo);
+                    }
+                    Err(e) => failed.push(memo.issue(e)),
+                }
+            }
+            chunk = uploaded;
+        }
+
+        if insert_chunk(db, user_id, &chunk).await.is_ok() {
+            imported += chunk.len();
+            continue;
+        }
+        // Retried one by one, so only the memos the database actually refuses are lost
+        for memo in &chunk {
+            match insert_chunk(db, user_id, std::slice::from_ref(memo)).await {
+                Ok(()) => imported += 1,
+                Err(e) => {
+                    store.discard(memo.audio_key.clone()).await;
+                    failed.push(memo.issue(ErrorBody::from_db("Failed to import memo", &e).0.message));
+                }
+            }
+        }
+    }
+
+    tracing::info!(user_id = %user_id, imported, skipped = skipped.len(), failed = failed.len(), "Imported memos");
+    ImportApiResponse::Ok(Json(ImportSummary { imported, skipped, failed }))
+}
+
+// The (title, created_at) pairs among the caller's memos that the chunk would repeat.
+async fn existing_keys(db: &DatabaseConnection, user_id: Uuid, chunk: &[ValidMemo]) -> Result<HashSet<(String, NaiveDateTime)>, DbErr> {
+    let created_at: Vec<NaiveDateTime> = chunk.iter().map(|memo| memo.created_at).collect();
+    let rows = voice_memos1::Entity::find()
+        .select_only()
+        .column(voice_memos1::Column::Title)
+        .column(voice_memos1::Column::CreatedAt)
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::CreatedAt.is_in(created_at))
+        .into_tuple::<(String, NaiveDateTime)>()
+        .all(db)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+// Returning early drops the transaction, which rolls the whole chunk back.
+async fn insert_chunk(db: &DatabaseConnection, user_id: Uuid, chunk: &[ValidMemo]) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    for memo in chunk {
+        insert(&txn, user_id, memo).await?;
+    }
+    txn.commit().await
+}
+
+fn validate(index: usize, memo: ArchivedMemo, max_audio_bytes: usize) -> Result<ValidMemo, String> {
+    if memo.title.trim().is_empty() {
+        return Err("Title is empty".to_string());
+    }
+    if memo.duration.trim().is_empty() {
+        return Err("Duration is empty".to_string());
+    }
+
+    let audio = match memo.audio_base64.as_deref().map(str::trim).filter(|encoded| !encoded.is_empty()) {
+        Some(encoded) => {
+            let audio = STANDARD.decode(encoded).map_err(|e| format!("audio_base64 is not valid base64: {}", e))?;
+            if audio.len() > max_audio_bytes {
+                return Err(format!("Audio is {} bytes; the limit is {} bytes", audio.len(), max_audio_bytes));
+            }
+            Some(audio)
+        }
+        None => None,
+    };
+
+    let now = Utc::now().naive_utc();
+    let created_at = parse_timestamp("created_at", memo.created_at.as_deref())?.unwrap_or(now);
+    let updated_at = parse_timestamp("updated_at", memo.updated_at.as_deref())?.unwrap_or(created_at);
+    let deleted_at = parse_timestamp("deleted_at", memo.deleted_at.as_deref())?;
+    let tags = normalize_tags(memo.tags.as_deref().unwrap_or_default());
+
+    Ok(ValidMemo { index, id: Uuid::new_v4(), memo, tags, audio, audio_key: None, created_at, updated_at, deleted_at })
+}
+
+fn parse_timestamp(field: &str, value: Option<&str>) -> Result<Option<NaiveDateTime>, String> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value.trim())
+                .map(|at| at.naive_utc())
+                .map_err(|_| format!("{} is not an RFC 3339 timestamp", field))
+        })
+        .transpose()
+}
+
+async fn insert(txn: &DatabaseTransaction, user_id: Uuid, valid: &ValidMemo) -> Result<(), DbErr> {
+    let ValidMemo { id, memo, tags, audio, audio_key, created_at, updated_at, deleted_at, .. } = valid;
+
+    let saved = voice_memos1::ActiveModel {
+        id: Set(*id),
+        user_id: Set(user_id),
+        title: Set(memo.title.clone()),
+        audio_blob: Set(audio.clone()),
+        audio_key: Set(audio_key.clone()),
+        transcript: Set(memo.transcript.clone()),
+        translate: Set(memo.translate.clone()),
+        summary: Set(memo.summary.clone()),
+        tags: Set(if tags.is_empty() { None } else { serde_json::to_string(tags).ok() }),
+        duration_seconds: Set(parse_duration_seconds(&memo.duration)),
+        duration: Set(memo.duration.clone()),
+        created_at: Set(*created_at),
+        updated_at: Set(*updated_at),
+        version: Set(1),
+        // The archive doesn't say what was AI-generated, so nothing is marked as such
+        transcript_ai_generated: Set(false),
+        translate_ai_generated: Set(false),
+        summary_ai_generated: Set(false),
+        deleted_at: Set(*deleted_at),
+        is_favorite: Set(memo.is_favorite.unwrap_or(false)),
+        // Folders belong to the exporting account, so imported memos start outside any
+        folder_id: Set(None),
+    }
+    .insert(txn)
+    .await?;
+
+    if !tags.is_empty() {
+        sync_memo_tags(txn, user_id, saved.id, tags).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use std::env;
+use std::sync::OnceLock;
+
+use chrono::Duration;
+
+use jsonwebtoken::{decode, encode, errors::{Error, ErrorKind}, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{de::DeserializeOwned, Serialize};
+
+const DEFAULT_ISSUER: &str = "smartmemo";
+const DEFAULT_AUDIENCE: &str = "smartmemo-api";
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 24 * 60;
+const DEFAULT_REMEMBER_ME_TTL_MINUTES: i64 = 30 * 24 * 60;
+// Anything beyond a year is treated as a misconfiguration
+const MAX_TOKEN_TTL_MINUTES: i64 = 365 * 24 * 60;
+
+/// Expected `iss` claim, from `JWT_ISSUER`.
+pub fn issuer() -> String {
+    env::var("JWT_ISSUER").unwrap_or_else(|_| DEFAULT_ISSUER.to_string())
+}
+
+/// Expected `aud` claim, from `JWT_AUDIENCE`.
+pub fn audience() -> String {
+    env::var("JWT_AUDIENCE").unwrap_or_else(|_| DEFAULT_AUDIENCE.to_string())
+}
+
+/// Signing and verification keys. RS256 when `JWT_PRIVATE_KEY_PEM` and `JWT_PUBLIC_KEY_PEM` are
+/// both set, the HS256 shared secret from `JWT_SECRET` otherwise.
+///
+/// Validation pins the one configured algorithm. Accepting both would let anyone holding the
+/// public key forge tokens by using it as an HS256 secret.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+static KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+impl JwtKeys {
+    pub fn from_env() -> Result<Self, String> {
+        let private_pem = pem_from_env("JWT_PRIVATE_KEY_PEM");
+        let public_pem = pem_from_env("JWT_PUBLIC_KEY_PEM");
+
+        match (private_pem, public_pem) {
+            (Some(private_pem), Some(public_pem)) => Self::rs256(&private_pem, &public_pem),
+            (None, None) => {
+                // No built-in fallback: a secret baked into the source signs tokens for every deployment
+                let secret = env::var("JWT_SECRET")
+                    .ok()
+                    .filter(|v| !v.trim().is_empty())
+                    .ok_or("JWT_SECRET must be set when no RSA key pair is configured")?;
+                Ok(Self::hs256(secret.as_bytes()))
+            }
+            _ => Err("JWT_PRIVATE_KEY_PEM and JWT_PUBLIC_KEY_PEM must be set together".to_string()),
+        }
+    }
+
+    pub fn hs256(secret: &[u8]) -> Self {
+        JwtKeys {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    pub fn rs256(private_pem: &str, public_pem: &str) -> Result<Self, String> {
+        Ok(JwtKeys {
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(private_pem.as_bytes())
+                .map_err(|e| format!("JWT_PRIVATE_KEY_PEM is not a valid RSA private key: {}", e))?,
+            decoding: DecodingKey::from_rsa_pem(public_pem.as_bytes())
+                .map_err(|e| format!("JWT_PUBLIC_KEY_PEM is not a valid RSA public key: {}", e))?,
+        })
+    }
+
+    fn encode<T: Serialize>(&self, claims: &T) -> Result<String, Error> {
+        encode(&Header::new(self.algorithm), claims, &self.encoding)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, Error> {
+        // Exactly one algorithm; a token whose header names any other is rejected
+        let mut validation = Validation::new(self.algorithm);
+        validation.set_issuer(&[issuer()]);
+        validation.set_audience(&[audience()]);
+
+        match decode::<T>(token, &self.decoding, &validation) {
+            Err(e) if legacy_grace_enabled() && matches!(e.kind(), ErrorKind::MissingRequiredClaim(claim) if claim == "iss" || claim == "aud") => {
+                decode::<T>(token, &self.decoding, &Validation::new(self.algorithm)).map(|data| data.claims)
+            }
+            result => result.map(|data| data.claims),
+        }
+    }
+}
+
+/// Loads the keys from the environment. Called at startup so a bad key stops the boot.
+pub fn init_keys() -> Result<(), String> {
+    let keys = JwtKeys::from_env()?;
+    // Already set means someone got here first with the same environment
+    let _ = KEYS.set(keys);
+    Ok(())
+}
+
+/// HS256 with a fixed secret, so tests don't depend on the environment. The first caller sets them.
+#[cfg(test)]
+pub fn init_test_keys() {
+    let _ = KEYS.set(JwtKeys::hs256(b"smartmemo-test-secret"));
+}
+
+fn keys() -> &'static JwtKeys {
+    KEYS.get_or_init(|| JwtKeys::from_env().expect("Invalid JWT key configuration"))
+}
+
+// Env files can't hold multi-line values everywhere, so an escaped `\n` counts as a newline
+fn pem_from_env(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.replace("\\n", "\n"))
+}
+
+/// Signs claims with the configured key.
+pub fn encode_claims<T: Serialize>(claims: &T) -> Result<String, Error> {
+    keys().encode(claims)
+}
+
+/// Token lifetimes from `ACCESS_TOKEN_TTL_MINUTES` and `REMEMBER_ME_TTL_MINUTES`.
+pub struct TokenLifetimes {
+    pub access: Duration,
+    pub remember_me: Duration,
+}
+
+impl TokenLifetimes {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(TokenLifetimes {
+            access: ttl_from_env("ACCESS_TOKEN_TTL_MINUTES", DEFAULT_ACCESS_TOKEN_TTL_MINUTES)?,
+            remember_me: ttl_from_env("REMEMBER_ME_TTL_MINUTES", DEFAULT_REMEMBER_ME_TTL_MINUTES)?,
+        })
+    }
+}
+
+fn ttl_from_env(name: &str, default: i64) -> Result<Duration, String> {
+    let minutes = match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| format!("{} must be a whole number of minutes, got {:?}", name, value))?,
+        Err(_) => default,
+    };
+
+    if minutes <= 0 || minutes > MAX_TOKEN_TTL_MINUTES {
+        return Err(format!("{} must be between 1 and {} minutes, got {}", name, MAX_TOKEN_TTL_MINUTES, minutes));
+    }
+    Ok(Duration::minutes(minutes))
+}
+
+// While `JWT_LEGACY_GRACE` is on, tokens minted before iss/aud existed are still accepted.
+fn legacy_grace_enabled() -> bool {
+    env::var("JWT_LEGACY_GRACE")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+/// Decodes and validates a token's signature, expiry, issuer and audience.
+pub fn decode_claims<T: DeserializeOwned>(token: &str) -> Result<T, Error> {
+    keys().decode(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use crate::test_support::{bearer, login, seed_user, test_app, test_db};
+
+    const TEST_PRIVATE_PEM: &str = include_str!("testdata/jwt_test_private.pem");
+    const TEST_PUBLIC_PEM: &str = include_str!("testdata/jwt_test_public.pem");
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        iss: String,
+        aud: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "42".to_string(),
+            iss: issuer(),
+            aud: audience(),
+            exp: (chrono::Utc::now() + Duration::minutes(5)).timestamp() as usize,
+        }
+    }
+
+    #[test]
+    fn rs256_token_is_accepted() {
+        let keys = JwtKeys::rs256(TEST_PRIVATE_PEM, TEST_PUBLIC_PEM).unwrap();
+        let token = keys.encode(&claims()).unwrap();
+
+        let decoded: TestClaims = keys.decode(&token).unwrap();
+        assert_eq!(decoded.sub, "42");
+    }
+
+    #[test]
+    fn hs256_token_forged_with_public_key_is_rejected() {
+        let keys = JwtKeys::rs256(TEST_PRIVATE_PEM, TEST_PUBLIC_PEM).unwrap();
+        let forged = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(TEST_PUBLIC_PEM.as_bytes()),
+        )
+        .unwrap();
+
+        let err = keys.decode::<TestClaims>(&forged).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidAlgorithm);
+    }
+
+    #[test]
+    fn token_for_another_audience_is_rejected() {
+        let keys = JwtKeys::hs256(b"secret");
+        let token = keys.encode(&TestClaims { aud: "another-api".to_string(), ..claims() }).unwrap();
+
+        let err = keys.decode::<TestClaims>(&token).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidAudience);
+    }
+
+    #[tokio::test]
+    async fn memo_endpoints_accept_only_tokens_for_this_audience() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let resp = client.get("/api/get_memos").header("Authorization", bearer(&token)).send().await;
+        resp.assert_status_is_ok();
+
+        // Same key, same user, but minted for another service
+        let mut claims: serde_json::Value = decode_claims(&token).unwrap();
+        claims["aud"] = "another-api".into();
+        let foreign = encode_claims(&claims).unwrap();
+        let resp = client.get("/api/get_memos").header("Authorization", bearer(&foreign)).send().await;
+        resp.assert_status(poem::http::StatusCode::UNAUTHORIZED);
+    }
+}
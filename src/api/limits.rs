@@ -0,0 +1,54 @@
+use std::env;
+
+use poem::{middleware::SizeLimit, Endpoint, EndpointExt};
+
+// --- Upload Limits ---
+// Audio and images arrive in the request body, so without a cap one request can exhaust memory.
+
+const DEFAULT_MAX_UPLOAD_MB: usize = 25;
+const DEFAULT_MAX_AVATAR_KB: usize = 1024;
+const DEFAULT_MAX_AUDIO_MB: usize = 10;
+
+/// Request body cap for endpoints that accept audio, from `MAX_UPLOAD_MB` (default 25).
+///
+/// Audio is sent as a JSON array of bytes, which takes roughly 3-4x the size of the
+/// raw audio on the wire, so size this from the body rather than the recording.
+pub fn max_upload_bytes() -> Result<usize, String> {
+    Ok(positive_env("MAX_UPLOAD_MB", DEFAULT_MAX_UPLOAD_MB)? * 1024 * 1024)
+}
+
+/// Largest decoded audio stored with a memo, from `MAX_AUDIO_MB` (default 10).
+pub fn max_audio_bytes() -> Result<usize, String> {
+    Ok(positive_env("MAX_AUDIO_MB", DEFAULT_MAX_AUDIO_MB)? * 1024 * 1024)
+}
+
+/// Largest avatar image accepted, from `MAX_AVATAR_KB` (default 1024).
+pub fn max_avatar_bytes() -> Result<usize, String> {
+    Ok(positive_env("MAX_AVATAR_KB", DEFAULT_MAX_AVATAR_KB)? * 1024)
+}
+
+/// `transform` for audio endpoints. Larger bodies are rejected with 413 before they are read.
+pub fn upload_limit(ep: impl Endpoint) -> impl Endpoint {
+    // Already validated in main, so this only falls back if called outside the server
+    let limit = max_upload_bytes().unwrap_or(DEFAULT_MAX_UPLOAD_MB * 1024 * 1024);
+    ep.with(SizeLimit::new(limit))
+}
+
+/// `transform` for avatar uploads, same as `upload_limit` with the avatar cap.
+pub fn avatar_limit(ep: impl Endpoint) -> impl Endpoint {
+    let limit = max_avatar_bytes().unwrap_or(DEFAULT_MAX_AVATAR_KB * 1024);
+    ep.with(SizeLimit::new(limit))
+}
+
+/// A positive integer from the environment, or `default` when unset.
+pub(crate) fn positive_env(name: &str, default: usize) -> Result<usize, String> {
+    match env::var(name) {
+        Ok(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("{} must be a positive integer", name)),
+        _ => Ok(default),
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{entity::*, query::*, sea_query::Expr, DatabaseConnection, DbErr};
+use uuid::Uuid;
+
+use entity::users;
+use crate::api::limits::positive_env;
+
+// --- Login Throttling ---
+// Failed logins are counted per account identifier and per client IP in fixed windows. Hitting the
+// threshold locks that key out, and each further lockout doubles the cooldown up to a cap.
+
+// Above this many tracked keys, idle entries are dropped on the next failure
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// App data for `login`. Cloning shares the same counters.
+///
+/// State is in memory, so it is per process; every handler in this server shares it, but a
+/// deployment with several instances gets one limiter per instance.
+#[derive(Clone)]
+pub struct LoginLimiter {
+    config: LimiterConfig,
+    attempts: Arc<Mutex<HashMap<String, Attempts>>>,
+}
+
+#[derive(Clone, Copy)]
+struct LimiterConfig {
+    max_failures: u32,
+    max_failures_per_ip: u32,
+    window: Duration,
+    base_lockout: Duration,
+    max_lockout: Duration,
+}
+
+struct Attempts {
+    failures: u32,
+    window_start: Instant,
+    lockouts: u32,
+    locked_until: Option<Instant>,
+    last_failure: Instant,
+}
+
+impl LoginLimiter {
+    /// Thresholds from `LOGIN_MAX_FAILURES` (5), `LOGIN_MAX_FAILURES_PER_IP` (20),
+    /// `LOGIN_FAILURE_WINDOW_SECS` (900), `LOGIN_LOCKOUT_SECS` (30) and `LOGIN_LOCKOUT_MAX_SECS` (3600).
+    pub fn from_env() -> Result<Self, String> {
+        let config = LimiterConfig {
+            max_failures: positive_env("LOGIN_MAX_FAILURES", 5)? as u32,
+            max_failures_per_ip: positive_env("LOGIN_MAX_FAILURES_PER_IP", 20)? as u32,
+            window: Duration::from_secs(positive_env("LOGIN_FAILURE_WINDOW_SECS", 900)? as u64),
+            base_lockout: Duration::from_secs(positive_env("LOGIN_LOCKOUT_SECS", 30)? as u64),
+            max_lockout: Duration::from_secs(positive_env("LOGIN_LOCKOUT_MAX_SECS", 3600)? as u64),
+        };
+        if config.max_lockout < config.base_lockout {
+            return Err("LOGIN_LOCKOUT_MAX_SECS must not be less than LOGIN_LOCKOUT_SECS".to_string());
+        }
+
+        Ok(LoginLimiter { config, attempts: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    /// `Err(retry_after)` while the identifier or the IP is locked out.
+    pub fn check(&self, identifier: &str, ip: Option<&str>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let attempts = self.attempts.lock().unwrap();
+
+        let retry_after = keys(identifier, ip)
+            .filter_map(|(key, _)| attempts.get(&key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max();
+
+        match retry_after {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_failure(&self, identifier: &str, ip: Option<&str>) {
+        let now = Instant::now();
+        let config = self.config;
+        let mut attempts = self.attempts.lock().unwrap();
+
+        if attempts.len() > PRUNE_THRESHOLD {
+            let idle = config.window + config.max_lockout;
+            attempts.retain(|_, entry| now.duration_since(entry.last_failure) < idle);
+        }
+
+        for (key, per_ip) in keys(identifier, ip) {
+            let max = if per_ip { config.max_failures_per_ip } else { config.max_failures };
+            let entry = attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                window_start: now,
+                lockouts: 0,
+                locked_until: None,
+                last_failure: now,
+            });
+
+            if now.duration_since(entry.window_start) > config.window {
+                entry.failures = 0;
+                entry.window_start = now;
+                // A whole quiet window since the last lockout ends the escalation
+                if entry.locked_until.is_none_or(|until| now.duration_since(until) > config.window) {
+                    entry.lockouts = 0;
+                }
+            }
+
+            entry.failures += 1;
+            entry.last_failure = now;
+
+            if entry.failures >= max {
+                let cooldown = config
+                    .base_lockout
+                    .saturating_mul(2u32.saturating_pow(entry.lockouts))
+                    .min(config.max_lockout);
+                entry.lockouts += 1;
+                entry.locked_until = Some(now + cooldown);
+                entry.failures = 0;
+                entry.window_start = now;
+            }
+        }
+    }
+
+    /// Clears the identifier's counter. The IP counter is left alone, otherwise logging in to an
+    /// account of your own between guesses would reset it.
+    pub fn record_success(&self, identifier: &str) {
+        self.attempts.lock().unwrap().remove(&account_key(identifier));
+    }
+}
+
+// Each key paired with whether it is the per-IP one
+fn keys(identifier: &str, ip: Option<&str>) -> impl Iterator<Item = (String, bool)> {
+    std::iter::once((account_key(identifier), false)).chain(ip.map(|ip| (format!("ip:{}", ip), true)))
+}
+
+fn account_key(identifier: &str) -> String {
+    format!("id:{}", identifier.to_lowercase())
+}
+
+// --- Account Lockout ---
+// Persistent per-account counter on `users`, so it holds across restarts and however many IPs
+// the attacker rotates through.
+
+/// From `ACCOUNT_LOCKOUT_THRESHOLD` (10 consecutive failures) and `ACCOUNT_LOCKOUT_MINUTES` (15).
+pub struct LockoutPolicy {
+    pub threshold: i32,
+    pub cooldown: chrono::Duration,
+}
+
+impl LockoutPolicy {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(LockoutPolicy {
+            threshold: positive_env("ACCOUNT_LOCKOUT_THRESHOLD", 10)? as i32,
+            cooldown: chrono::Duration::minutes(positive_env("ACCOUNT_LOCKOUT_MINUTES", 15)? as i64),
+        })
+    }
+}
+
+/// When the account unlocks, if it is locked right now.
+pub fn locked_until(user: &users::Model) -> Option<NaiveDateTime> {
+    user.locked_until.filter(|until| *until > Utc::now().naive_utc())
+}
+
+/// Counts a failed password against the account and locks it when it reaches the threshold.
+pub async fn record_account_failure(db: &DatabaseConnection, user_id: Uuid, policy: &LockoutPolicy) -> Result<(), DbErr> {
+    // Incremented in SQL so concurrent failures can't overwrite each other's count
+    let counted = users::Entity::update_many()
+        .col_expr(users::Column::FailedAttempts, Expr::col(users::Column::FailedAttempts).add(1))
+        .filter(users::Column::Id.eq(user_id))
+        .exec_with_returning(db)
+        .await?;
+    if !counted.iter().any(|user| user.failed_attempts >= policy.threshold) {
+        return Ok(());
+    }
+
+    // The count starts over once the lock expires
+    users::Entity::update_many()
+        .col_expr(users::Column::FailedAttempts, Expr::value(0))
+        .col_expr(users::Column::LockedUntil, Expr::value(Utc::now().naive_utc() + policy.cooldown))
+        .filter(users::Column::Id.eq(user_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Resets the counter after a successful login. Skips the write when there is nothing to reset.
+pub async fn clear_account_failures(db: &DatabaseConnection, user: &users::Model) -> Result<(), DbErr> {
+    if user.failed_attempts == 0 && user.locked_until.is_none() {
+        return Ok(());
+    }
+
+    users::Entity::update_many()
+        .col_expr(users::Column::FailedAttempts, Expr::value(0))
+        .col_expr(users::Column::LockedUntil, Expr::value(Option::<NaiveDateTime>::None))
+        .filter(users::Column::Id.eq(user.id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
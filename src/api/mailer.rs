@@ -0,0 +1,212 @@
+use std::env;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+// --- Outgoing Mail ---
+// SMTP when `SMTP_HOST` is set; otherwise `LogMailer` only logs, which is enough for development.
+
+const DEFAULT_SMTP_PORT: u16 = 587;
+
+/// Delivers a message to one recipient. Injected as app data so the transport can be swapped out.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body_html: &str, body_text: &str) -> Result<(), String>;
+}
+
+/// App data for endpoints that send mail.
+#[derive(Clone)]
+pub struct Mail {
+    pub mailer: Arc<dyn Mailer>,
+    /// `MAIL_DEV_MODE`: also return confirmation tokens in API responses, so flows can be
+    /// completed without a mailbox. Never enable in production.
+    pub dev_mode: bool,
+}
+
+impl Mail {
+    /// SMTP from `SMTP_HOST`, `SMTP_PORT` (587, STARTTLS; 465 uses implicit TLS), `SMTP_USER`,
+    /// `SMTP_PASSWORD` and `SMTP_FROM`, or the logging mailer when `SMTP_HOST` is unset.
+    pub fn from_env() -> Result<Self, String> {
+        let dev_mode = env::var("MAIL_DEV_MODE")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+
+        let mailer: Arc<dyn Mailer> = match env::var("SMTP_HOST").ok().filter(|h| !h.trim().is_empty()) {
+            Some(host) => Arc::new(SmtpMailer::from_env(host.trim())?),
+            None => Arc::new(LogMailer),
+        };
+
+        Ok(Mail { mailer, dev_mode })
+    }
+
+    /// Sends a rendered template. Failures are logged here; callers decide whether they matter.
+    pub async fn deliver(&self, to: &str, email: &Email) -> Result<(), String> {
+        self.mailer
+            .send(to, &email.subject, &email.html, &email.text)
+            .await
+            .inspect_err(|e| tracing::error!(to, "Failed to send \"{}\": {}", email.subject, e))
+    }
+}
+
+struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    fn from_env(host: &str) -> Result<Self, String> {
+        let port = match env::var("SMTP_PORT") {
+            Ok(raw) => raw.trim().parse::<u16>().map_err(|_| format!("SMTP_PORT must be a port number, got {:?}", raw))?,
+            Err(_) => DEFAULT_SMTP_PORT,
+        };
+        let from = env::var("SMTP_FROM")
+            .map_err(|_| "SMTP_FROM must be set when SMTP_HOST is".to_string())?
+            .parse::<Mailbox>()
+            .map_err(|e| format!("SMTP_FROM is not a valid address: {}", e))?;
+
+        let builder = if port == 465 {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+        }
+        .map_err(|e| format!("Invalid SMTP_HOST: {}", e))?
+        .port(port);
+
+        let builder = match (env::var("SMTP_USER"), env::var("SMTP_PASSWORD")) {
+            (Ok(user), Ok(password)) => builder.credentials(Credentials::new(user, password)),
+            (Err(_), Err(_)) => builder,
+            _ => return Err("Set both SMTP_USER and SMTP_PASSWORD, or neither".to_string()),
+        };
+
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &str, subject: &str, body_html: &str, body_text: &str) -> Result<(), String> {
+        let to = to.parse::<Mailbox>().map_err(|e| format!("Invalid recipient: {}", e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(body_text.to_string(), body_html.to_string()))
+            .map_err(|e| e.to_string())?;
+
+        self.transport.send(message).await.map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+/// Logs who would have been mailed. The body is left out since it usually carries a token.
+struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, _body_html: &str, _body_text: &str) -> Result<(), String> {
+        tracing::info!(to, "Mail not sent (no mail provider configured): {}", subject);
+        Ok(())
+    }
+}
+
+// --- Templates ---
+
+/// A rendered message, in both HTML and plain text.
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub fn welcome(username: &str) -> Email {
+    Email {
+        subject: "Welcome to Smart Memo".to_string(),
+        html: format!(
+            "<p>Hi {},</p><p>Your Smart Memo account is ready. Record a memo and we'll transcribe, title and summarize it for you.</p>",
+            escape_html(username)
+        ),
+        text: format!(
+            "Hi {},\n\nYour Smart Memo account is ready. Record a memo and we'll transcribe, title and summarize it for you.",
+            username
+        ),
+    }
+}
+
+pub fn verify_email(token: &str, ttl_hours: i64) -> Email {
+    Email {
+        subject: "Verify your email address".to_string(),
+        html: format!(
+            "<p>Use this code to verify the email address of your Smart Memo account:</p><p><code>{}</code></p><p>It expires in {} hours. If you didn't sign up, ignore this message.</p>",
+            escape_html(token), ttl_hours
+        ),
+        text: format!(
+            "Use this code to verify the email address of your Smart Memo account: {}\n\nIt expires in {} hours. If you didn't sign up, ignore this message.",
+            token, ttl_hours
+        ),
+    }
+}
+
+pub fn password_reset(token: &str, ttl_minutes: i64) -> Email {
+    Email {
+        subject: "Reset your password".to_string(),
+        html: format!(
+            "<p>Use this code to choose a new Smart Memo password:</p><p><code>{}</code></p><p>It expires in {} minutes and works once. If you didn't ask for this, ignore this message; your password stays the same.</p>",
+            escape_html(token), ttl_minutes
+        ),
+        text: format!(
+            "Use this code to choose a new Smart Memo password: {}\n\nIt expires in {} minutes and works once. If you didn't ask for this, ignore this message; your password stays the same.",
+            token, ttl_minutes
+        ),
+    }
+}
+
+pub fn email_change(token: &str, ttl_hours: i64) -> Email {
+    Email {
+        subject: "Confirm your new email address".to_string(),
+        html: format!(
+            "<p>Use this code to confirm your new Smart Memo email address:</p><p><code>{}</code></p><p>It expires in {} hours. If you didn't ask for this, ignore this message.</p>",
+            escape_html(token), ttl_hours
+        ),
+        text: format!(
+            "Use this code to confirm your new Smart Memo email address: {}\n\nIt expires in {} hours. If you didn't ask for this, ignore this message.",
+            token, ttl_hours
+        ),
+    }
+}
+
+/// One message for everything of a user that expired in a maintenance sweep.
+pub fn expiry_digest(memo_titles: &[String], sessions: u64, pending_email: Option<&str>) -> Email {
+    let mut lines = Vec::new();
+    if !memo_titles.is_empty() {
+        lines.push(format!("Share links expired for: {}", memo_titles.join(", ")));
+    }
+    if sessions > 0 {
+        lines.push(format!("{} signed-in session(s) expired; sign in again on those devices", sessions));
+    }
+    if let Some(email) = pending_email {
+        lines.push(format!("The change of your email address to {} was never confirmed and has been cancelled", email));
+    }
+
+    Email {
+        subject: "Some of your Smart Memo access has expired".to_string(),
+        html: format!(
+            "<p>These expired on your Smart Memo account:</p><ul>{}</ul>",
+            lines.iter().map(|line| format!("<li>{}</li>", escape_html(line))).collect::<String>()
+        ),
+        text: format!(
+            "These expired on your Smart Memo account:\n\n{}",
+            lines.iter().map(|line| format!("- {}", line)).collect::<Vec<_>>().join("\n")
+        ),
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
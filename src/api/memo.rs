@@ -1,33 +1,29 @@
 
-use chrono::Utc;
-use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
-use poem::{web::Data, Result, error::{BadRequest, NotFound, Unauthorized}};
-use poem_openapi::{payload::Json, param::Path, Object, OpenApi, SecurityScheme};
-use poem_openapi::auth::Bearer;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use poem::{web::Data, Endpoint, EndpointExt, Result, middleware::SizeLimit};
+use poem_openapi::{payload::{Binary, Json}, param::{Header, Path, Query}, ApiResponse, Enum, Object, OpenApi};
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseBackend, DatabaseConnection, DbErr, EntityTrait, FromQueryResult, JoinType, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Select, SelectModel, Selector, Set, TransactionTrait};
+use sea_orm::sea_query::{extension::postgres::PgExpr, Expr, LikeExpr, NullOrdering, OnConflict, Query as SubQuery};
 use serde::{Deserialize, Serialize};
 use serde_json; // Added for robust JSON handling of tags
 use uuid::Uuid;
-use std::error::Error as StdError;
-use std::fmt;
+use std::collections::HashMap;
 
-use entity::{users, voice_memos1};
-
-// --- Custom Error for Poem ---
-#[derive(Debug)]
-struct ApiError(String);
-
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl StdError for ApiError {}
-
-
-// --- Constants ---
-const JWT_SECRET: &str = "point";
+use entity::{memo_links, memo_tags, tags, voice_memos1};
+use crate::api::audio;
+use crate::api::avatar::{etag_for, etag_matches};
+use crate::api::auth::{MemosReadAuth, MemosWriteAiAuth, MemosWriteAuth};
+use crate::api::crypto::Crypto;
+use crate::api::error::ErrorBody;
+use crate::api::folders::find_owned_folder;
+use crate::api::gemini::{AiProvider, SummaryLength};
+use crate::api::limits::{max_audio_bytes, upload_limit};
+use crate::api::preferences::{format_timestamp, preferences_or_default};
+use crate::api::storage::{AudioStore, StoredAudio};
+use crate::api::usage::AiOperation;
+use crate::metrics;
 
 // --- API Structs ---
 
@@ -42,28 +38,64 @@ pub struct MemoInput {
     pub tags: Option<Vec<String>>, // Correctly defined as a vector of strings
     pub duration: String,
     pub audio_blob: Option<Vec<u8>>,
+    /// Which fields were produced by the AI pipeline rather than typed by the user
+    pub ai_generated: Option<AiGeneratedFlags>,
+    /// Required when `id` refers to an existing memo: the version the client last read
+    pub version: Option<i32>,
+    /// One of the caller's folders. When updating, leave it out to keep the memo where it is,
+    /// or send `""` to take it out of its folder.
+    pub folder_id: Option<String>,
+}
+
+#[derive(Object, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct AiGeneratedFlags {
+    #[oai(default)]
+    #[serde(default)]
+    pub transcript: bool,
+    #[oai(default)]
+    #[serde(default)]
+    pub translate: bool,
+    #[oai(default)]
+    #[serde(default)]
+    pub summary: bool,
 }
 
 #[derive(Object, Debug, Deserialize)]
 pub struct SaveAudioMemoPayload {
     pub title: String,
     pub duration: String,
+    /// The recording as standard base64
     pub audio_blob: String,
 }
 
 #[derive(Object, Debug, Deserialize)]
 pub struct MemoUpdate {
+    /// The version the client last read; the update is rejected if the memo has changed since
+    pub version: i32,
     pub title: Option<String>,
     pub transcript: Option<String>,
     pub translate: Option<String>,
     pub summary: Option<String>,
     pub tags: Option<Vec<String>>, // Correctly defined as a vector of strings
+    /// Move the memo to one of the caller's folders, or out of its folder with `""`
+    pub folder_id: Option<String>,
 }
 
 #[derive(Object, Serialize)]
 pub struct MemoResponse {
     pub message: String,
     pub memo_id: String,
+    /// The memo's version after a save; send it back with the next update
+    #[oai(skip_serializing_if_is_none)]
+    pub version: Option<i32>,
+}
+
+#[derive(Object, Serialize)]
+pub struct MemoConflictResponse {
+    pub message: String,
+    /// What the server has now, so the client can merge and retry with its version. The audio is
+    /// left out, as in `get_memos`.
+    pub current: MemoListItem,
 }
 
 #[derive(Object, Serialize)]
@@ -74,328 +106,3351 @@ pub struct MemoOutput {
     pub translate: Option<String>,
     pub summary: Option<String>,
     pub tags: Option<Vec<String>>, 
+    /// Deprecated: as sent by the client; use `duration_seconds`
     pub duration: String,
+    /// `duration` in whole seconds, or null when it couldn't be read as a length
+    pub duration_seconds: Option<i32>,
+    /// RFC 3339, in the `timezone` preference or UTC; likewise `updated_at` and `deleted_at`
     pub created_at: String,
+    pub updated_at: String,
+    pub version: i32,
+    pub is_favorite: bool,
+    pub folder_id: Option<String>,
+    /// Null for recordings kept in object storage; see `audio_url`
     pub audio_blob: Option<Vec<u8>>,
+    /// Only from get_memo, for recordings kept in object storage: a short-lived download URL
+    #[oai(skip_serializing_if_is_none)]
+    pub audio_url: Option<String>,
+    pub ai_generated: AiGeneratedFlags,
+    // Only set for memos in the trash
+    #[oai(skip_serializing_if_is_none)]
+    pub deleted_at: Option<String>,
+    // Only populated by get_memo_by_id
+    #[oai(skip_serializing_if_is_none)]
+    pub links: Option<Vec<LinkedMemo>>,
+    #[oai(skip_serializing_if_is_none)]
+    pub backlinks: Option<Vec<LinkedMemo>>,
+}
+
+/// A memo as listed by `get_memos` and `trash`: everything but the audio, which only `get_memo` returns.
+#[derive(Object, Serialize)]
+pub struct MemoListItem {
+    pub id: String,
+    pub title: String,
+    pub transcript: Option<String>,
+    pub translate: Option<String>,
+    pub summary: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Deprecated: as sent by the client; use `duration_seconds`
+    pub duration: String,
+    pub duration_seconds: Option<i32>,
+    /// RFC 3339, in the `timezone` preference or UTC; likewise `updated_at`
+    pub created_at: String,
+    pub updated_at: String,
+    pub version: i32,
+    pub is_favorite: bool,
+    pub folder_id: Option<String>,
+    /// Whether the memo has a recording to fetch with `get_memo`
+    pub has_audio: bool,
+    pub ai_generated: AiGeneratedFlags,
+    /// Only set for memos in the trash
+    #[oai(skip_serializing_if_is_none)]
+    pub deleted_at: Option<String>,
 }
 
-// --- JWT Claims ---
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub email: String,
-    pub exp: usize,
+// The columns `get_memos` and `trash` read; the audio itself is never loaded for a list.
+#[derive(FromQueryResult)]
+struct MemoListRow {
+    id: Uuid,
+    title: String,
+    transcript: Option<String>,
+    translate: Option<String>,
+    summary: Option<String>,
+    tags: Option<String>,
+    duration: String,
+    duration_seconds: Option<i32>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    version: i32,
+    is_favorite: bool,
+    folder_id: Option<Uuid>,
+    has_audio: bool,
+    transcript_ai_generated: bool,
+    translate_ai_generated: bool,
+    summary_ai_generated: bool,
+    deleted_at: Option<NaiveDateTime>,
 }
 
-// --- Security Scheme Definition for Swagger ---
-#[derive(SecurityScheme)]
-#[oai(
-    ty = "bearer",
-    bearer_format = "JWT",
-    
-)]
-struct ApiKeyAuth(Bearer);
+#[derive(Object, Serialize)]
+pub struct MemoPage {
+    pub items: Vec<MemoListItem>,
+    /// Matching memos across all pages
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+// Search
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Serialize)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedField {
+    Title,
+    Transcript,
+    Summary,
+}
+
+#[derive(Object, Serialize)]
+pub struct SearchHit {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub memo: MemoListItem,
+    /// Which of the searched fields contain the query
+    pub matched_fields: Vec<MatchedField>,
+    /// Full-text search only: the best passages, with matched words in `<mark>`
+    #[oai(skip_serializing_if_is_none)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+#[derive(Object, Serialize)]
+pub struct SearchPage {
+    pub items: Vec<SearchHit>,
+    /// Matching memos across all pages
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+// A list row plus whether each searched field matched, computed by the same expression as the filter.
+struct SearchRow {
+    memo: MemoListRow,
+    title_matched: bool,
+    transcript_matched: bool,
+    summary_matched: bool,
+    snippet: Option<String>,
+}
+
+impl FromQueryResult for SearchRow {
+    fn from_query_result(res: &sea_orm::QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(SearchRow {
+            memo: MemoListRow::from_query_result(res, pre)?,
+            title_matched: res.try_get(pre, "title_matched")?,
+            transcript_matched: res.try_get::<Option<bool>>(pre, "transcript_matched")?.unwrap_or(false),
+            summary_matched: res.try_get::<Option<bool>>(pre, "summary_matched")?.unwrap_or(false),
+            // Only full-text search selects one
+            snippet: res.try_get(pre, "snippet").ok().flatten(),
+        })
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum TagMatch {
+    /// Memos with at least one of the tags
+    #[default]
+    Any,
+    /// Memos with every one of the tags
+    All,
+}
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum MemoSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Title,
+    /// By `duration_seconds`; memos whose duration couldn't be read come last either way
+    Duration,
+    /// Deprecated: use `created_at` with `order=asc`
+    CreatedAtAsc,
+    /// Deprecated: use `created_at`
+    CreatedAtDesc,
+    /// Deprecated: use `updated_at`
+    UpdatedAtDesc,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum MemoExportFormat {
+    #[default]
+    Markdown,
+    /// Plain text with the same sections as `markdown`
+    Txt,
+}
+
+impl MemoSort {
+    // The column and direction to sort on; without an `order`, titles go A-Z and everything else newest or longest first
+    fn resolve(self, order: Option<SortOrder>) -> (voice_memos1::Column, Order) {
+        let (column, default) = match self {
+            MemoSort::CreatedAt | MemoSort::CreatedAtDesc => (voice_memos1::Column::CreatedAt, SortOrder::Desc),
+            MemoSort::CreatedAtAsc => (voice_memos1::Column::CreatedAt, SortOrder::Asc),
+            MemoSort::UpdatedAt | MemoSort::UpdatedAtDesc => (voice_memos1::Column::UpdatedAt, SortOrder::Desc),
+            MemoSort::Title => (voice_memos1::Column::Title, SortOrder::Asc),
+            MemoSort::Duration => (voice_memos1::Column::DurationSeconds, SortOrder::Desc),
+        };
+        let order = match order.unwrap_or(default) {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        };
+        (column, order)
+    }
+}
+
+// Tags
+#[derive(Object, Serialize)]
+pub struct TagCount {
+    /// Tags differing only in case are listed once, under the casing most memos use
+    pub name: String,
+    pub count: i64,
+    /// The latest update of a memo carrying the tag; RFC 3339, in the `timezone` preference or UTC
+    pub last_used_at: String,
+}
+
+#[derive(Enum, Debug, Clone, Copy, Default, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum TagSort {
+    /// Most used first
+    #[default]
+    Count,
+    /// A-Z, ignoring case
+    Alpha,
+}
+
+// Quick Capture
+#[derive(Object, Debug, Deserialize)]
+pub struct QuickCapturePayload {
+    pub text: String,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Object, Serialize)]
+pub struct QuickCaptureResponse {
+    pub id: String,
+    pub title: String,
+    /// Present when enrichment came from the sandbox AI provider
+    #[oai(skip_serializing_if_is_none)]
+    pub sandbox: Option<bool>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct ProcessMemoPayload {
+    pub audio_bytes: Vec<u8>,
+    /// As for `/transcribe`: detected from the bytes when omitted
+    pub mime_type: Option<String>,
+    pub duration: String,
+    pub tags: Option<Vec<String>>,
+    /// Used when `generate_title` is off or title generation fails
+    pub title: Option<String>,
+    /// Generate a title from the transcript (default true)
+    pub generate_title: Option<bool>,
+    /// Generate a summary from the transcript (default true)
+    pub summarize: Option<bool>,
+}
+
+#[derive(Object, Serialize)]
+pub struct FavoriteResponse {
+    pub memo_id: String,
+    /// The state after the toggle
+    pub is_favorite: bool,
+}
+
+#[derive(Object, Debug, Deserialize)]
+pub struct MoveMemoPayload {
+    /// One of the caller's folders; null or `""` takes the memo out of its folder
+    pub folder_id: Option<String>,
+}
+
+// Bulk Delete
+#[derive(Object, Debug, Deserialize)]
+pub struct DeleteMemosPayload {
+    pub ids: Vec<String>,
+}
+
+#[derive(Object, Serialize)]
+pub struct DeleteMemosResponse {
+    /// How many memos were moved to the trash
+    pub deleted: u64,
+    /// Which memos were moved to the trash
+    pub deleted_ids: Vec<String>,
+    /// IDs that aren't UUIDs
+    pub invalid_ids: Vec<String>,
+    /// IDs of memos that don't exist, belong to someone else or are already in the trash
+    pub not_found_ids: Vec<String>,
+}
+
+// Memo Links
+#[derive(Object, Debug, Deserialize)]
+pub struct MemoLinkPayload {
+    pub target_id: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct LinkedMemo {
+    pub id: String,
+    pub title: String,
+}
+
+impl From<voice_memos1::Model> for MemoOutput {
+    fn from(memo: voice_memos1::Model) -> Self {
+        MemoOutput::in_timezone(memo, None)
+    }
+}
+
+impl MemoOutput {
+    /// Timestamps are shown in `tz`, or in UTC for `None`.
+    fn in_timezone(memo: voice_memos1::Model, tz: Option<Tz>) -> Self {
+        MemoOutput {
+            id: memo.id.to_string(),
+            title: memo.title,
+            transcript: memo.transcript,
+            translate: memo.translate,
+            summary: memo.summary,
+            // Deserialize tags from JSON string back to a vector
+            tags: memo.tags.and_then(|json_str| serde_json::from_str(&json_str).ok()),
+            duration: memo.duration,
+            duration_seconds: memo.duration_seconds,
+            created_at: format_timestamp(memo.created_at, tz),
+            updated_at: format_timestamp(memo.updated_at, tz),
+            version: memo.version,
+            is_favorite: memo.is_favorite,
+            folder_id: memo.folder_id.map(|id| id.to_string()),
+            audio_blob: memo.audio_blob,
+            audio_url: None,
+            ai_generated: AiGeneratedFlags {
+                transcript: memo.transcript_ai_generated,
+                translate: memo.translate_ai_generated,
+                summary: memo.summary_ai_generated,
+            },
+            deleted_at: memo.deleted_at.map(|at| format_timestamp(at, tz)),
+            links: None,
+            backlinks: None,
+        }
+    }
+
+    /// Drops every field flagged as AI-generated, for exports that must contain human-written text only.
+    pub fn without_ai_content(mut self) -> Self {
+        if self.ai_generated.transcript {
+            self.transcript = None;
+        }
+        if self.ai_generated.translate {
+            self.translate = None;
+        }
+        if self.ai_generated.summary {
+            self.summary = None;
+        }
+        self
+    }
+}
+
+impl MemoListItem {
+    fn new(row: MemoListRow, tz: Option<Tz>) -> Self {
+        MemoListItem {
+            id: row.id.to_string(),
+            title: row.title,
+            transcript: row.transcript,
+            translate: row.translate,
+            summary: row.summary,
+            tags: row.tags.and_then(|json_str| serde_json::from_str(&json_str).ok()),
+            duration: row.duration,
+            duration_seconds: row.duration_seconds,
+            created_at: format_timestamp(row.created_at, tz),
+            updated_at: format_timestamp(row.updated_at, tz),
+            version: row.version,
+            is_favorite: row.is_favorite,
+            folder_id: row.folder_id.map(|id| id.to_string()),
+            has_audio: row.has_audio,
+            ai_generated: AiGeneratedFlags {
+                transcript: row.transcript_ai_generated,
+                translate: row.translate_ai_generated,
+                summary: row.summary_ai_generated,
+            },
+            deleted_at: row.deleted_at.map(|at| format_timestamp(at, tz)),
+        }
+    }
+
+    /// Same as `MemoOutput::without_ai_content`.
+    fn without_ai_content(mut self) -> Self {
+        if self.ai_generated.transcript {
+            self.transcript = None;
+        }
+        if self.ai_generated.translate {
+            self.translate = None;
+        }
+        if self.ai_generated.summary {
+            self.summary = None;
+        }
+        self
+    }
+}
+
+// --- API Responses ---
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)] // Built once per request and sent straight away, so boxing buys nothing
+enum MemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 409)]
+    Conflict(Json<MemoConflictResponse>),
+    #[oai(status = 413)]
+    PayloadTooLarge(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum GetMemosApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoPage>),
+    /// `tags` can't be parsed or names too many tags, `from`/`to` are invalid, or `folder_id` is malformed
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum SearchMemosApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<SearchPage>),
+    /// `q` is empty, `tags` can't be parsed or names too many tags, or `from`/`to` are invalid
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)] // Built once per request and sent straight away, so boxing buys nothing
+enum GetMemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoOutput>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)] // Built once per request and sent straight away, so boxing buys nothing
+enum DuplicateMemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoOutput>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The memo is in the trash
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum TrashApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoPage>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum RestoreMemoApiResponse {
+    /// The restored memo
+    #[oai(status = 200)]
+    Ok(Json<MemoListItem>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The memo isn't in the trash
+    #[oai(status = 409)]
+    Conflict(Json<MemoConflictResponse>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum QuickCaptureApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<QuickCaptureResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)] // Built once per request and sent straight away, so boxing buys nothing
+enum ProcessMemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoOutput>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// The audio is over `MAX_AUDIO_MB`
+    #[oai(status = 413)]
+    PayloadTooLarge(Json<ErrorBody>),
+    /// The daily AI quota is used up
+    #[oai(status = 429)]
+    TooManyRequests(Json<ErrorBody>),
+    /// Transcription failed upstream; nothing was saved
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)] // Built once per request and sent straight away, so boxing buys nothing
+enum ReprocessMemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoOutput>, #[oai(header = "X-AI-Sandbox")] Option<bool>),
+    /// Invalid memo ID, the memo has no transcript, or there is no Gemini key
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The memo was edited while the new title and summary were being generated; nothing was saved
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    /// The daily AI quota is used up
+    #[oai(status = 429)]
+    TooManyRequests(Json<ErrorBody>),
+    /// Title or summary generation failed upstream; nothing was saved
+    #[oai(status = 502)]
+    BadGateway(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum MemoAudioApiResponse {
+    #[oai(status = 200)]
+    Ok(
+        Binary<Vec<u8>>,
+        #[oai(header = "Content-Type")] String,
+        #[oai(header = "Content-Disposition")] String,
+        #[oai(header = "Accept-Ranges")] String,
+    ),
+    /// The part asked for with `Range`
+    #[oai(status = 206)]
+    PartialContent(
+        Binary<Vec<u8>>,
+        #[oai(header = "Content-Type")] String,
+        #[oai(header = "Content-Range")] String,
+        #[oai(header = "Accept-Ranges")] String,
+    ),
+    /// The recording is in object storage; `Location` is a short-lived URL that serves it, ranges included
+    #[oai(status = 307)]
+    TemporaryRedirect(#[oai(header = "Location")] String),
+    /// The range starts past the end; `Content-Range` gives the actual size
+    #[oai(status = 416)]
+    RangeNotSatisfiable(#[oai(header = "Content-Range")] String),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// No such memo, or it has no audio
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum TagsApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<TagCount>>, #[oai(header = "ETag")] String),
+    /// Unchanged since the `ETag` sent in `If-None-Match`
+    #[oai(status = 304)]
+    NotModified(#[oai(header = "ETag")] String),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum MemoExportApiResponse {
+    #[oai(status = 200)]
+    Ok(
+        Binary<Vec<u8>>,
+        #[oai(header = "Content-Type")] String,
+        #[oai(header = "Content-Disposition")] String,
+    ),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum FavoriteApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<FavoriteResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteMemosApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<DeleteMemosResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum MemoLinkApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MemoResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- Constants ---
+// Quick capture is text only, so it gets a much smaller body limit than audio uploads
+const QUICK_CAPTURE_MAX_BODY_BYTES: usize = 16 * 1024;
+const MAX_BULK_DELETE_IDS: usize = 500;
+const INVALID_DURATION: &str = "duration must be seconds, M:SS or H:MM:SS";
+const DEFAULT_PAGE_SIZE: u64 = 25;
+const MAX_PAGE_SIZE: u64 = 100;
+const MAX_TAG_FILTERS: usize = 20;
 
 // --- API Definition ---
 pub struct MemoApi;
 
 #[OpenApi]
 impl MemoApi {
-    #[oai(path = "/save_memo", method = "post")]
+    #[oai(path = "/save_memo", method = "post", transform = "upload_limit")]
     async fn save_memo(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAuth,
         db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
         Json(payload): Json<MemoInput>,
-    ) -> Json<MemoResponse> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(c) => c,
-            Err(msg) => return Json(MemoResponse { message: msg, memo_id: "".to_string() }),
-        };
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
 
-        let user_id = match Uuid::parse_str(&claims.sub) {
-            Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid user ID format".to_string(), memo_id: "".to_string() }),
-        };
-
-        if let Ok(None) = users::Entity::find_by_id(user_id).one(db.0).await {
-             return Json(MemoResponse { message: format!("User {} not found", user_id), memo_id: "".to_string() });
-        }
 
         if payload.title.trim().is_empty() || payload.duration.trim().is_empty() {
-            return Json(MemoResponse { message: "Title and duration are required".to_string(), memo_id: "".to_string() });
+            return MemoApiResponse::BadRequest(ErrorBody::new("missing_fields", "Title and duration are required"));
+        }
+        let Some(duration_seconds) = parse_duration_seconds(&payload.duration) else {
+            return MemoApiResponse::BadRequest(ErrorBody::new("invalid_duration", INVALID_DURATION));
+        };
+        // Checked before anything is stored, so an oversized recording never reaches the database
+        if let Some(audio) = &payload.audio_blob
+            && let Err(msg) = check_audio_size(audio.len())
+        {
+            return MemoApiResponse::PayloadTooLarge(ErrorBody::new("audio_too_large", msg));
         }
 
+        let folder = match resolve_folder_id(db.0, user_id, payload.folder_id.as_deref()).await {
+            Ok(folder) => folder,
+            Err(response) => return response,
+        };
         let audio_blob_bytes = payload.audio_blob;
+        let ai_generated = payload.ai_generated.unwrap_or_default();
         
         
         // Serialize tags vector into a JSON string for database storage
-        let tags_json_string = payload.tags.as_ref().and_then(|v| serde_json::to_string(v).ok());
+        let tag_names = normalize_tags(payload.tags.as_deref().unwrap_or_default());
+        let tags_json_string = payload.tags.as_ref().and_then(|_| serde_json::to_string(&tag_names).ok());
 
         // Helper to ensure empty strings for optional fields become NULL in the DB
         let clean_field = |val: Option<String>| val.and_then(|s| if s.trim().is_empty() { None } else { Some(s) });
 
         // UPDATE FLOW
-        if let Some(ref id_str) = payload.id {
-            if let Ok(memo_uuid) = Uuid::parse_str(id_str) {
-                if let Ok(Some(existing)) = voice_memos1::Entity::find_by_id(memo_uuid).one(db.0).await {
-                    if existing.user_id != user_id {
-                        return Json(MemoResponse { message: "Unauthorized".to_string(), memo_id: "".to_string() });
+        if let Some(ref id_str) = payload.id
+            && let Ok(memo_uuid) = Uuid::parse_str(id_str)
+        {
+            let existing = match voice_memos1::Entity::find_by_id(memo_uuid).one(db.0).await {
+                Ok(existing) => existing,
+                Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+            };
+
+            if let Some(existing) = existing {
+                if existing.user_id != user_id || existing.deleted_at.is_some() {
+                    return MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied"));
+                }
+
+                let Some(expected_version) = payload.version else {
+                    return MemoApiResponse::BadRequest(ErrorBody::new("version_required", "version is required when updating a memo"));
+                };
+
+                // Uploaded first, so a failure leaves the memo as it was
+                let previous_key = existing.audio_key.clone();
+                let audio = match audio_blob_bytes {
+                    Some(blob) => match store.0.put(existing.id, blob).await {
+                        Ok(stored) => Some(stored),
+                        Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::new("audio_storage_failed", format!("Failed to store audio: {}", e))),
+                    },
+                    None => None,
+                };
+                let new_key = audio.as_ref().and_then(|stored| stored.key.clone());
+
+                let mut update_model: voice_memos1::ActiveModel = existing.into();
+                update_model.title = Set(payload.title);
+                update_model.transcript = Set(clean_field(payload.transcript));
+                update_model.translate = Set(clean_field(payload.translate));
+                update_model.summary = Set(clean_field(payload.summary));
+                update_model.tags = Set(tags_json_string); // Store tags as JSON string
+                update_model.duration_seconds = Set(Some(duration_seconds));
+                update_model.duration = Set(payload.duration);
+                update_model.transcript_ai_generated = Set(ai_generated.transcript);
+                update_model.translate_ai_generated = Set(ai_generated.translate);
+                update_model.summary_ai_generated = Set(ai_generated.summary);
+                let replaces_audio = audio.is_some();
+                if let Some(stored) = audio {
+                    update_model.audio_blob = Set(stored.blob);
+                    update_model.audio_key = Set(stored.key);
+                }
+                if let Some(folder_id) = folder {
+                    update_model.folder_id = Set(folder_id);
+                }
+                update_model.updated_at = Set(Utc::now().naive_utc());
+
+                let response = async {
+                    let txn = match db.0.begin().await {
+                        Ok(txn) => txn,
+                        Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Update failed", &e)),
+                    };
+                    // Returning early drops the transaction, so the row and its tags change together or not at all
+                    let updated = match update_if_version(&txn, update_model, expected_version).await {
+                        Ok(updated) => updated,
+                        Err(response) => return response,
+                    };
+                    if let Err(e) = sync_memo_tags(&txn, user_id, updated.id, &tag_names).await {
+                        return MemoApiResponse::InternalServerError(ErrorBody::from_db("Update failed", &e));
                     }
 
-                    let mut update_model: voice_memos1::ActiveModel = existing.into();
-                    update_model.title = Set(payload.title);
-                    update_model.transcript = Set(clean_field(payload.transcript));
-                    update_model.translate = Set(clean_field(payload.translate));
-                    update_model.summary = Set(clean_field(payload.summary));
-                    update_model.tags = Set(tags_json_string); // Store tags as JSON string
-                    update_model.duration = Set(payload.duration);
-                    if let Some(blob) = audio_blob_bytes {
-                        update_model.audio_blob = Set(Some(blob));
+                    match txn.commit().await {
+                        Ok(()) => {
+                            metrics::record_memo_writes("update", 1);
+                            MemoApiResponse::Ok(Json(MemoResponse { message: "Memo updated".to_string(), memo_id: updated.id.to_string(), version: Some(updated.version) }))
+                        }
+                        Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Update failed", &e)),
                     }
+                }
+                .await;
 
-                    return match update_model.update(db.0).await {
-                        Ok(updated) => Json(MemoResponse { message: "Memo updated".to_string(), memo_id: updated.id.to_string() }),
-                        Err(e) => Json(MemoResponse { message: format!("Update failed: {}", e), memo_id: "".to_string() }),
-                    };
+                // Whichever recording the row no longer points to goes
+                match (&response, replaces_audio) {
+                    (MemoApiResponse::Ok(_), true) => store.0.discard(previous_key).await,
+                    (MemoApiResponse::Ok(_), false) => {}
+                    _ => store.0.discard(new_key).await,
                 }
+                return response;
             }
         }
 
         // INSERT FLOW
+        let memo_id = Uuid::new_v4();
+        let audio = match audio_blob_bytes {
+            Some(blob) => match store.0.put(memo_id, blob).await {
+                Ok(stored) => stored,
+                Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::new("audio_storage_failed", format!("Failed to store audio: {}", e))),
+            },
+            None => StoredAudio { blob: None, key: None },
+        };
+        let now = Utc::now().naive_utc();
         let new_memo = voice_memos1::ActiveModel {
-            id: Set(Uuid::new_v4()),
+            id: Set(memo_id),
             user_id: Set(user_id),
             title: Set(payload.title),
-            audio_blob: Set(audio_blob_bytes),
+            audio_blob: Set(audio.blob),
+            audio_key: Set(audio.key.clone()),
             transcript: Set(clean_field(payload.transcript)),
             translate: Set(clean_field(payload.translate)),
             summary: Set(clean_field(payload.summary)),
             tags: Set(tags_json_string), // Store tags as JSON string
+            duration_seconds: Set(Some(duration_seconds)),
             duration: Set(payload.duration),
-            created_at: Set(Utc::now().naive_utc()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            version: Set(1),
+            transcript_ai_generated: Set(ai_generated.transcript),
+            translate_ai_generated: Set(ai_generated.translate),
+            summary_ai_generated: Set(ai_generated.summary),
+            deleted_at: Set(None),
+            is_favorite: Set(false),
+            folder_id: Set(folder.flatten()),
         };
 
-        match new_memo.insert(db.0).await {
-            Ok(saved) => Json(MemoResponse { message: "Memo saved".to_string(), memo_id: saved.id.to_string() }),
-            Err(e) => Json(MemoResponse { message: format!("Save failed: {}", e), memo_id: "".to_string() }),
+        match insert_with_tags(db.0, new_memo, user_id, &tag_names).await {
+            Ok(saved) => {
+                metrics::record_memo_writes("create", 1);
+                MemoApiResponse::Ok(Json(MemoResponse { message: "Memo saved".to_string(), memo_id: saved.id.to_string(), version: Some(saved.version) }))
+            }
+            Err(e) => {
+                store.0.discard(audio.key).await;
+                MemoApiResponse::InternalServerError(ErrorBody::from_db("Save failed", &e))
+            }
         }
     }
 
-    #[oai(path = "/get_memos", method = "get")]
-    async fn get_memos(
+    /// Save a new memo from base64 audio, for clients that would rather not send a byte array
+    #[oai(path = "/save_audio_memo", method = "post", transform = "upload_limit")]
+    async fn save_audio_memo(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAuth,
         db: Data<&DatabaseConnection>,
-    ) -> Json<Vec<MemoOutput>> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(c) => c,
-            Err(_) => return Json(vec![]),
-        };
+        store: Data<&AudioStore>,
+        Json(payload): Json<SaveAudioMemoPayload>,
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
 
-        let user_id = match Uuid::parse_str(&claims.sub) {
-            Ok(id) => id,
-            Err(_) => return Json(vec![]),
+        if payload.title.trim().is_empty() || payload.duration.trim().is_empty() {
+            return MemoApiResponse::BadRequest(ErrorBody::new("missing_fields", "Title and duration are required"));
+        }
+        let Some(duration_seconds) = parse_duration_seconds(&payload.duration) else {
+            return MemoApiResponse::BadRequest(ErrorBody::new("invalid_duration", INVALID_DURATION));
         };
 
-        let memos = match voice_memos1::Entity::find()
-            .filter(voice_memos1::Column::UserId.eq(user_id))
-            .all(db.0)
-            .await {
-            Ok(memos) => memos,
-            Err(_) => return Json(vec![]),
+        let audio = match STANDARD.decode(payload.audio_blob.trim()) {
+            Ok(audio) if !audio.is_empty() => audio,
+            Ok(_) => return MemoApiResponse::BadRequest(ErrorBody::new("audio_required", "Audio is required")),
+            Err(e) => return MemoApiResponse::BadRequest(ErrorBody::new("invalid_base64", format!("audio_blob is not valid base64: {}", e))),
         };
+        if let Err(msg) = check_audio_size(audio.len()) {
+            return MemoApiResponse::PayloadTooLarge(ErrorBody::new("audio_too_large", msg));
+        }
 
-        let response = memos.into_iter().map(|memo| MemoOutput {
-            id: memo.id.to_string(),
-            title: memo.title,
-            transcript: memo.transcript,
-            translate: memo.translate,
-            summary: memo.summary,
-            // Deserialize tags from JSON string back to a vector
-            tags: memo.tags.and_then(|json_str| serde_json::from_str(&json_str).ok()),
-            duration: memo.duration,
-            created_at: memo.created_at.to_string(),
-            audio_blob: memo.audio_blob,
-        }).collect();
+        let memo_id = Uuid::new_v4();
+        let audio = match store.0.put(memo_id, audio).await {
+            Ok(stored) => stored,
+            Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::new("audio_storage_failed", format!("Failed to store audio: {}", e))),
+        };
+        let now = Utc::now().naive_utc();
+        let new_memo = voice_memos1::ActiveModel {
+            id: Set(memo_id),
+            user_id: Set(user_id),
+            title: Set(payload.title),
+            audio_blob: Set(audio.blob),
+            audio_key: Set(audio.key.clone()),
+            transcript: Set(None),
+            translate: Set(None),
+            summary: Set(None),
+            tags: Set(None),
+            duration_seconds: Set(Some(duration_seconds)),
+            duration: Set(payload.duration),
+            created_at: Set(now),
+            updated_at: Set(now),
+            version: Set(1),
+            transcript_ai_generated: Set(false),
+            translate_ai_generated: Set(false),
+            summary_ai_generated: Set(false),
+            deleted_at: Set(None),
+            is_favorite: Set(false),
+            folder_id: Set(None),
+        };
 
-        Json(response)
+        match new_memo.insert(db.0).await {
+            Ok(saved) => {
+                metrics::record_memo_writes("create", 1);
+                MemoApiResponse::Ok(Json(MemoResponse { message: "Memo saved".to_string(), memo_id: saved.id.to_string(), version: Some(saved.version) }))
+            }
+            Err(e) => {
+                store.0.discard(audio.key).await;
+                MemoApiResponse::InternalServerError(ErrorBody::from_db("Save failed", &e))
+            }
+        }
     }
-    
-    #[oai(path = "/get_memo/:memo_id", method = "get")]
-    async fn get_memo_by_id(
+
+    /// Save a text note in a single insert. Enrichment only runs when `enrich=true`.
+    #[oai(path = "/quick_capture", method = "post", transform = "quick_capture_limit")]
+    async fn quick_capture(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAuth,
         db: Data<&DatabaseConnection>,
-        Path(memo_id): Path<String>,
-    ) -> Result<Json<MemoOutput>> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(claims) => claims,
-            Err(e) => return Err(Unauthorized(ApiError(e))),
-        };
-        let user_id = Uuid::parse_str(&claims.sub).map_err(BadRequest)?;
-        let memo_uuid = Uuid::parse_str(&memo_id).map_err(BadRequest)?;
+        crypto: Data<&Crypto>,
+        enrich: Query<Option<bool>>,
+        Json(payload): Json<QuickCapturePayload>,
+    ) -> QuickCaptureApiResponse {
+        let user_id = auth.0.id;
 
-        let memo = voice_memos1::Entity::find_by_id(memo_uuid)
-            .filter(voice_memos1::Column::UserId.eq(user_id))
-            .one(db.0)
-            .await
-            .map_err(poem::error::InternalServerError)?
-            .ok_or_else(|| NotFound(ApiError("Memo not found or access denied".to_string())))?;
+        let text = payload.text.trim();
+        if text.is_empty() {
+            return QuickCaptureApiResponse::BadRequest(ErrorBody::new("text_required", "Text is required"));
+        }
 
-        let response = MemoOutput {
-            id: memo.id.to_string(),
-            title: memo.title,
-            transcript: memo.transcript,
-            translate: memo.translate,
-            summary: memo.summary,
-            // Deserialize tags from JSON string back to a vector
-            tags: memo.tags.and_then(|json_str| serde_json::from_str(&json_str).ok()),
-            duration: memo.duration,
-            created_at: memo.created_at.to_string(),
-            audio_blob: memo.audio_blob,
+        let tag_names = normalize_tags(payload.tags.as_deref().unwrap_or_default());
+        let mut title = format!("Quick note · {}", Utc::now().format("%H:%M"));
+        let mut summary = None;
+        let mut sandboxed = false;
+
+        if enrich.0.unwrap_or(false) {
+            let provider = AiProvider::for_user(&auth.0, db.0, crypto.0).await;
+            // Enrichment is best-effort; the note is saved either way, also once the AI quota is used up
+            if let Ok(provider) = provider {
+                if provider.meter(db.0, user_id, AiOperation::Title).await.is_ok()
+                    && let Ok(generated) = provider.title(text).await
+                {
+                    title = generated;
+                }
+                if provider.meter(db.0, user_id, AiOperation::Summary).await.is_ok() {
+                    summary = provider.summarize(text, SummaryLength::Medium).await.ok();
+                }
+                sandboxed = provider.is_sandbox();
+            }
+        }
+
+        let now = Utc::now().naive_utc();
+        let new_memo = voice_memos1::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            title: Set(title),
+            audio_blob: Set(None),
+            audio_key: Set(None),
+            transcript: Set(Some(text.to_string())),
+            translate: Set(None),
+            // Sandbox output is fake, so it never carries the real-model marker
+            summary_ai_generated: Set(summary.is_some() && !sandboxed),
+            summary: Set(summary),
+            tags: Set(payload.tags.as_ref().and_then(|_| serde_json::to_string(&tag_names).ok())),
+            duration: Set("0".to_string()),
+            duration_seconds: Set(Some(0)),
+            created_at: Set(now),
+            updated_at: Set(now),
+            version: Set(1),
+            transcript_ai_generated: Set(false),
+            translate_ai_generated: Set(false),
+            deleted_at: Set(None),
+            is_favorite: Set(false),
+            folder_id: Set(None),
         };
 
-        Ok(Json(response))
+        let saved = match insert_with_tags(db.0, new_memo, user_id, &tag_names).await {
+            Ok(saved) => saved,
+            Err(e) => return QuickCaptureApiResponse::InternalServerError(ErrorBody::from_db("Save failed", &e)),
+        };
+
+        metrics::record_memo_writes("create", 1);
+        QuickCaptureApiResponse::Ok(Json(QuickCaptureResponse {
+            id: saved.id.to_string(),
+            title: saved.title,
+            sandbox: sandboxed.then_some(true),
+        }))
     }
 
-    #[oai(path = "/update_memo/:memo_id", method = "patch")]
-    async fn update_memo(
+    /// Transcribe audio, generate a title and summary from the transcript, and save the memo, in one request.
+    /// Title and summary are best-effort: if either fails the memo is still saved without it.
+    #[oai(path = "/process_memo", method = "post", transform = "upload_limit")]
+    async fn process_memo(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAiAuth,
         db: Data<&DatabaseConnection>,
-        Path(memo_id): Path<String>,
-        Json(payload): Json<MemoUpdate>,
-    ) -> Json<MemoResponse> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(c) => c,
-            Err(msg) => return Json(MemoResponse { message: msg, memo_id: "".to_string() }),
+        crypto: Data<&Crypto>,
+        store: Data<&AudioStore>,
+        Json(payload): Json<ProcessMemoPayload>,
+    ) -> ProcessMemoApiResponse {
+        let user = auth.0;
+
+        if payload.audio_bytes.is_empty() || payload.duration.trim().is_empty() {
+            return ProcessMemoApiResponse::BadRequest(ErrorBody::new("missing_fields", "Audio and duration are required"));
+        }
+        let Some(duration_seconds) = parse_duration_seconds(&payload.duration) else {
+            return ProcessMemoApiResponse::BadRequest(ErrorBody::new("invalid_duration", INVALID_DURATION));
+        };
+        // Before transcription, so an oversized recording costs no AI quota either
+        if let Err(msg) = check_audio_size(payload.audio_bytes.len()) {
+            return ProcessMemoApiResponse::PayloadTooLarge(ErrorBody::new("audio_too_large", msg));
+        }
+        let mime_type = match audio::resolve_mime(payload.mime_type.as_deref(), &payload.audio_bytes) {
+            Ok(mime_type) => mime_type,
+            Err(msg) => return ProcessMemoApiResponse::BadRequest(ErrorBody::new("unsupported_audio_type", msg)),
         };
 
-        let user_id = match Uuid::parse_str(&claims.sub) {
-            Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid user ID".to_string(), memo_id: "".to_string() }),
+        let provider = match AiProvider::for_user(&user, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return ProcessMemoApiResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
         };
 
-        let memo_uuid = match Uuid::parse_str(&memo_id) {
-            Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid memo ID".to_string(), memo_id: "".to_string() }),
+        if let Err(msg) = provider.meter(db.0, user.id, AiOperation::Transcribe).await {
+            return ProcessMemoApiResponse::TooManyRequests(ErrorBody::new("quota_exceeded", msg));
+        }
+        let transcript = match provider.transcribe(&payload.audio_bytes, mime_type).await {
+            Ok(transcript) => transcript,
+            Err(e) => return ProcessMemoApiResponse::BadGateway(ErrorBody::new("upstream_error", format!("Transcription Error: {}", e))),
         };
 
-        let memo = match voice_memos1::Entity::find_by_id(memo_uuid)
-            .filter(voice_memos1::Column::UserId.eq(user_id))
+        // Both only need the transcript, so they run side by side
+        let want_title = payload.generate_title.unwrap_or(true)
+            && provider.meter(db.0, user.id, AiOperation::Title).await.is_ok();
+        let want_summary = payload.summarize.unwrap_or(true)
+            && provider.meter(db.0, user.id, AiOperation::Summary).await.is_ok();
+        let (generated_title, summary) = tokio::join!(
+            async { if want_title { provider.title(&transcript).await.ok() } else { None } },
+            async { if want_summary { provider.summarize(&transcript, SummaryLength::Medium).await.ok() } else { None } },
+        );
+
+        let title = generated_title
+            .or(payload.title.filter(|t| !t.trim().is_empty()))
+            .unwrap_or_else(|| format!("Voice memo · {}", Utc::now().format("%H:%M")));
+        let sandboxed = provider.is_sandbox();
+        let tag_names = normalize_tags(payload.tags.as_deref().unwrap_or_default());
+
+        let memo_id = Uuid::new_v4();
+        let audio = match store.0.put(memo_id, payload.audio_bytes).await {
+            Ok(stored) => stored,
+            Err(e) => return ProcessMemoApiResponse::InternalServerError(ErrorBody::new("audio_storage_failed", format!("Failed to store audio: {}", e))),
+        };
+        let now = Utc::now().naive_utc();
+        let new_memo = voice_memos1::ActiveModel {
+            id: Set(memo_id),
+            user_id: Set(user.id),
+            title: Set(title),
+            audio_blob: Set(audio.blob),
+            audio_key: Set(audio.key.clone()),
+            transcript: Set(Some(transcript)),
+            translate: Set(None),
+            // Sandbox output is fake, so it never carries the real-model marker
+            transcript_ai_generated: Set(!sandboxed),
+            summary_ai_generated: Set(summary.is_some() && !sandboxed),
+            summary: Set(summary),
+            tags: Set(payload.tags.as_ref().and_then(|_| serde_json::to_string(&tag_names).ok())),
+            duration_seconds: Set(Some(duration_seconds)),
+            duration: Set(payload.duration),
+            created_at: Set(now),
+            updated_at: Set(now),
+            version: Set(1),
+            translate_ai_generated: Set(false),
+            deleted_at: Set(None),
+            is_favorite: Set(false),
+            folder_id: Set(None),
+        };
+
+        let saved = match insert_with_tags(db.0, new_memo, user.id, &tag_names).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                store.0.discard(audio.key).await;
+                return ProcessMemoApiResponse::InternalServerError(ErrorBody::from_db("Save failed", &e));
+            }
+        };
+
+        metrics::record_memo_writes("create", 1);
+        ProcessMemoApiResponse::Ok(Json(MemoOutput::from(saved)), sandboxed.then_some(true))
+    }
+
+    /// Regenerate a memo's title and summary from its stored transcript, e.g. after editing the transcript.
+    #[oai(path = "/memo/:memo_id/reprocess", method = "post")]
+    async fn reprocess_memo(
+        &self,
+        auth: MemosWriteAiAuth,
+        db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
+        Path(memo_id): Path<String>,
+    ) -> ReprocessMemoApiResponse {
+        let user = auth.0;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return ReprocessMemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+        let memo = match find_owned_memo(db.0, memo_uuid, user.id).await {
+            Ok(Some(memo)) => memo,
+            Ok(None) => return ReprocessMemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return ReprocessMemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        };
+        let Some(transcript) = memo.transcript.clone().filter(|t| !t.trim().is_empty()) else {
+            return ReprocessMemoApiResponse::BadRequest(ErrorBody::new("no_transcript", "Memo has no transcript to reprocess"));
+        };
+
+        let provider = match AiProvider::for_user(&user, db.0, crypto.0).await {
+            Ok(provider) => provider,
+            Err(msg) => return ReprocessMemoApiResponse::BadRequest(ErrorBody::new("no_api_key", msg)),
+        };
+        for operation in [AiOperation::Title, AiOperation::Summary] {
+            if let Err(msg) = provider.meter(db.0, user.id, operation).await {
+                return ReprocessMemoApiResponse::TooManyRequests(ErrorBody::new("quota_exceeded", msg));
+            }
+        }
+        let (title, summary) = match tokio::try_join!(
+            provider.title(&transcript),
+            provider.summarize(&transcript, SummaryLength::Medium),
+        ) {
+            Ok(generated) => generated,
+            Err(e) => return ReprocessMemoApiResponse::BadGateway(ErrorBody::new("upstream_error", format!("Reprocessing Error: {}", e))),
+        };
+
+        let version = memo.version;
+        let sandboxed = provider.is_sandbox();
+        let mut active_memo: voice_memos1::ActiveModel = memo.into();
+        active_memo.title = Set(title);
+        active_memo.summary = Set(Some(summary));
+        // Sandbox output is fake, so it never carries the real-model marker
+        active_memo.summary_ai_generated = Set(!sandboxed);
+        active_memo.updated_at = Set(Utc::now().naive_utc());
+        active_memo.version = Set(version + 1);
+
+        // The Gemini calls take a while; an edit saved meanwhile may have changed the transcript they worked from
+        match voice_memos1::Entity::update(active_memo)
+            .filter(voice_memos1::Column::Version.eq(version))
+            .exec(db.0)
+            .await
+        {
+            Ok(updated) => {
+                metrics::record_memo_writes("update", 1);
+                ReprocessMemoApiResponse::Ok(Json(MemoOutput::from(updated)), sandboxed.then_some(true))
+            }
+            Err(DbErr::RecordNotUpdated) => ReprocessMemoApiResponse::Conflict(ErrorBody::new(
+                "version_conflict",
+                "Memo was modified while it was being reprocessed; try again",
+            )),
+            Err(e) => ReprocessMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to update memo", &e)),
+        }
+    }
+
+    /// List the caller's memos a page at a time. Audio is left out; `has_audio` says whether `get_memo` has some.
+    #[oai(path = "/get_memos", method = "get")]
+    #[allow(clippy::too_many_arguments)] // One per query parameter
+    async fn get_memos(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        /// Only return memos carrying exactly this tag
+        tag: Query<Option<String>>,
+        /// Comma-separated tags, matched exactly; quote a tag containing a comma, as in CSV (`"a,b",c`)
+        tags: Query<Option<String>>,
+        /// Whether memos need `any` (default) or `all` of `tags`
+        #[oai(name = "match")]
+        tag_match: Query<Option<TagMatch>>,
+        /// Omit fields that were generated by AI
+        exclude_ai_content: Query<Option<bool>>,
+        /// What to sort by, `created_at` by default
+        sort: Query<Option<MemoSort>>,
+        /// `asc` or `desc`; by default titles sort A-Z and everything else descending
+        order: Query<Option<SortOrder>>,
+        /// Only memos created at or after this RFC 3339 timestamp; without an offset it is taken as UTC
+        from: Query<Option<String>>,
+        /// Only memos created at or before this RFC 3339 timestamp; without an offset it is taken as UTC
+        to: Query<Option<String>>,
+        /// Only favorite memos
+        favorites_only: Query<Option<bool>>,
+        /// Only memos in this folder, or `none` for memos outside any folder
+        folder_id: Query<Option<String>>,
+        /// 1-based; past the last page gives an empty `items`
+        page: Query<Option<u64>>,
+        /// Memos per page, 25 by default and at most 100
+        page_size: Query<Option<u64>>,
+    ) -> GetMemosApiResponse {
+        let user_id = auth.0.id;
+        let filters = match ListFilters::parse(tags.0.as_deref(), tag_match.0, from.0.as_deref(), to.0.as_deref()) {
+            Ok(filters) => filters,
+            Err(error) => return GetMemosApiResponse::BadRequest(error),
+        };
+        // Only the caller's memos are searched, so another user's folder ID just matches nothing
+        let folder = match folder_id.0.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            None => None,
+            Some("none") => Some(None),
+            Some(raw) => match Uuid::parse_str(raw) {
+                Ok(id) => Some(Some(id)),
+                Err(_) => return GetMemosApiResponse::BadRequest(ErrorBody::new("invalid_folder_id", "folder_id must be a folder ID or none")),
+            },
+        };
+
+        let mut query = voice_memos1::Entity::find()
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null());
+
+        if let Some(tag) = tag.0.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            query = query.filter(voice_memos1::Column::Id.in_subquery(memo_ids_with_tag(user_id, tag)));
+        }
+        query = filters.apply(query, user_id);
+        if favorites_only.0.unwrap_or(false) {
+            query = query.filter(voice_memos1::Column::IsFavorite.eq(true));
+        }
+        query = match folder {
+            Some(Some(folder_id)) => query.filter(voice_memos1::Column::FolderId.eq(folder_id)),
+            Some(None) => query.filter(voice_memos1::Column::FolderId.is_null()),
+            None => query,
+        };
+
+        // Favorites are pinned to the top whatever the sort order
+        query = query.order_by_desc(voice_memos1::Column::IsFavorite);
+        // The ID breaks ties, so the order is total and pages never overlap
+        let (column, direction) = sort.0.unwrap_or_default().resolve(order.0);
+        query = match column {
+            voice_memos1::Column::CreatedAt | voice_memos1::Column::UpdatedAt => {
+                query.order_by(column, direction.clone()).order_by(voice_memos1::Column::Id, direction)
+            }
+            voice_memos1::Column::DurationSeconds => query
+                .order_by_with_nulls(column, direction, NullOrdering::Last)
+                .order_by_desc(voice_memos1::Column::CreatedAt)
+                .order_by_desc(voice_memos1::Column::Id),
+            // Equal titles and durations go newest first
+            _ => query
+                .order_by(column, direction)
+                .order_by_desc(voice_memos1::Column::CreatedAt)
+                .order_by_desc(voice_memos1::Column::Id),
+        };
+
+        match list_page(db.0, user_id, query, page.0, page_size.0).await {
+            Ok(mut memos) => {
+                if exclude_ai_content.0.unwrap_or(false) {
+                    memos.items = memos.items.into_iter().map(MemoListItem::without_ai_content).collect();
+                }
+                GetMemosApiResponse::Ok(Json(memos))
+            }
+            Err(e) => GetMemosApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch memos", &e)),
+        }
+    }
+    
+    /// Find the caller's memos whose title, transcript or summary contain `q`, ignoring case.
+    /// Favorites come first, then the newest; each hit says which fields matched.
+    #[oai(path = "/search_memos", method = "get")]
+    #[allow(clippy::too_many_arguments)] // One per query parameter
+    async fn search_memos(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        /// Matched literally; `%` and `_` have no special meaning
+        q: Query<String>,
+        /// Omit fields that were generated by AI
+        exclude_ai_content: Query<Option<bool>>,
+        /// Comma-separated tags, matched exactly; quote a tag containing a comma, as in CSV (`"a,b",c`)
+        tags: Query<Option<String>>,
+        /// Whether memos need `any` (default) or `all` of `tags`
+        #[oai(name = "match")]
+        tag_match: Query<Option<TagMatch>>,
+        /// Only memos created at or after this RFC 3339 timestamp; without an offset it is taken as UTC
+        from: Query<Option<String>>,
+        /// Only memos created at or before this RFC 3339 timestamp; without an offset it is taken as UTC
+        to: Query<Option<String>>,
+        /// 1-based; past the last page gives an empty `items`
+        page: Query<Option<u64>>,
+        /// Memos per page, 25 by default and at most 100
+        page_size: Query<Option<u64>>,
+    ) -> SearchMemosApiResponse {
+        let term = q.0.trim();
+        if term.is_empty() {
+            return SearchMemosApiResponse::BadRequest(ErrorBody::new("empty_query", "q must not be empty"));
+        }
+        let filters = match ListFilters::parse(tags.0.as_deref(), tag_match.0, from.0.as_deref(), to.0.as_deref()) {
+            Ok(filters) => filters,
+            Err(error) => return SearchMemosApiResponse::BadRequest(error),
+        };
+
+        let query = like_search(db.0.get_database_backend(), auth.0.id, term, &filters);
+        search_page(db.0, auth.0.id, query, page.0, page_size.0, exclude_ai_content.0.unwrap_or(false)).await
+    }
+
+    /// Full-text search over title, transcript and summary, best matches first. Words are matched
+    /// by their stem, so "running" finds "run"; each hit has a `snippet` around the matches.
+    /// Without Postgres this is the same as `search_memos`.
+    #[oai(path = "/search_memos/fts", method = "get")]
+    #[allow(clippy::too_many_arguments)] // One per query parameter
+    async fn search_memos_fts(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        /// Plain words; all of them must occur
+        q: Query<String>,
+        /// Omit fields that were generated by AI
+        exclude_ai_content: Query<Option<bool>>,
+        /// Comma-separated tags, matched exactly; quote a tag containing a comma, as in CSV (`"a,b",c`)
+        tags: Query<Option<String>>,
+        /// Whether memos need `any` (default) or `all` of `tags`
+        #[oai(name = "match")]
+        tag_match: Query<Option<TagMatch>>,
+        /// Only memos created at or after this RFC 3339 timestamp; without an offset it is taken as UTC
+        from: Query<Option<String>>,
+        /// Only memos created at or before this RFC 3339 timestamp; without an offset it is taken as UTC
+        to: Query<Option<String>>,
+        /// 1-based; past the last page gives an empty `items`
+        page: Query<Option<u64>>,
+        /// Memos per page, 25 by default and at most 100
+        page_size: Query<Option<u64>>,
+    ) -> SearchMemosApiResponse {
+        let term = q.0.trim();
+        if term.is_empty() {
+            return SearchMemosApiResponse::BadRequest(ErrorBody::new("empty_query", "q must not be empty"));
+        }
+        let filters = match ListFilters::parse(tags.0.as_deref(), tag_match.0, from.0.as_deref(), to.0.as_deref()) {
+            Ok(filters) => filters,
+            Err(error) => return SearchMemosApiResponse::BadRequest(error),
+        };
+
+        let backend = db.0.get_database_backend();
+        let query = match backend {
+            DatabaseBackend::Postgres => full_text_search(auth.0.id, term, &filters),
+            _ => like_search(backend, auth.0.id, term, &filters),
+        };
+        search_page(db.0, auth.0.id, query, page.0, page_size.0, exclude_ai_content.0.unwrap_or(false)).await
+    }
+
+    /// List the user's distinct tags with the number of memos using each, not counting the trash.
+    /// Send the last `ETag` in `If-None-Match` to get a 304 when nothing changed.
+    #[oai(path = "/tags", method = "get")]
+    async fn get_tags(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        /// `count` (default) or `alpha`
+        sort: Query<Option<TagSort>>,
+        #[oai(name = "If-None-Match")] if_none_match: Header<Option<String>>,
+    ) -> TagsApiResponse {
+        let user_id = auth.0.id;
+
+        let tags = match tag_counts(db.0, user_id).await {
+            Ok(tags) => tags,
+            Err(e) => return TagsApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        };
+
+        let tz = preferences_or_default(db.0, user_id).await.tz();
+        let mut tags: Vec<TagCount> = tags
+            .into_iter()
+            .map(|(name, count, last_used_at)| TagCount { name, count, last_used_at: format_timestamp(last_used_at, tz) })
+            .collect();
+        match sort.0.unwrap_or_default() {
+            TagSort::Count => tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))),
+            TagSort::Alpha => tags.sort_by_key(|tag| tag.name.to_lowercase()),
+        }
+
+        // From the body itself, so any change that shows in the list also changes the tag
+        let etag = etag_for(&serde_json::to_vec(&tags).unwrap_or_default());
+        if etag_matches(if_none_match.0.as_deref(), &etag) {
+            return TagsApiResponse::NotModified(etag);
+        }
+        TagsApiResponse::Ok(Json(tags), etag)
+    }
+
+    #[oai(path = "/get_memo/:memo_id", method = "get")]
+    async fn get_memo_by_id(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(memo_id): Path<String>,
+        /// Omit fields that were generated by AI
+        exclude_ai_content: Query<Option<bool>>,
+    ) -> GetMemoApiResponse {
+        let user_id = auth.0.id;
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return GetMemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        let memo = match voice_memos1::Entity::find_by_id(memo_uuid)
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .one(db.0)
+            .await
+        {
+            Ok(Some(memo)) => memo,
+            Ok(None) => return GetMemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return GetMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch memo", &e)),
+        };
+
+        let linked = async {
+            let outgoing_ids: Vec<Uuid> = memo_links::Entity::find()
+                .filter(memo_links::Column::FromMemoId.eq(memo.id))
+                .all(db.0)
+                .await?
+                .into_iter()
+                .map(|link| link.to_memo_id)
+                .collect();
+            let incoming_ids: Vec<Uuid> = memo_links::Entity::find()
+                .filter(memo_links::Column::ToMemoId.eq(memo.id))
+                .all(db.0)
+                .await?
+                .into_iter()
+                .map(|link| link.from_memo_id)
+                .collect();
+            Ok::<_, DbErr>((linked_memos(db.0, user_id, outgoing_ids).await?, linked_memos(db.0, user_id, incoming_ids).await?))
+        }
+        .await;
+        let (links, backlinks) = match linked {
+            Ok(linked) => linked,
+            Err(e) => return GetMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch memo links", &e)),
+        };
+
+        let audio_url = match memo.audio_key.as_deref().map(|key| store.0.url(key)).transpose() {
+            Ok(url) => url,
+            Err(e) => return GetMemoApiResponse::InternalServerError(ErrorBody::new("audio_unavailable", e)),
+        };
+
+        let tz = preferences_or_default(db.0, user_id).await.tz();
+        let mut response = MemoOutput::in_timezone(memo, tz);
+        response.audio_url = audio_url;
+        response.links = Some(links);
+        response.backlinks = Some(backlinks);
+        if exclude_ai_content.0.unwrap_or(false) {
+            response = response.without_ai_content();
+        }
+
+        GetMemoApiResponse::Ok(Json(response))
+    }
+
+    /// The memo's recording as raw audio, with its detected type, so it can be played directly.
+    /// Supports a single `Range: bytes=...` for seeking; for several ranges only the first is sent.
+    #[oai(path = "/memo_audio/:memo_id", method = "get")]
+    async fn get_memo_audio(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(memo_id): Path<String>,
+        #[oai(name = "Range")] range: Header<Option<String>>,
+    ) -> MemoAudioApiResponse {
+        let user_id = auth.0.id;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return MemoAudioApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        let memo = voice_memos1::Entity::find_by_id(memo_uuid)
+            .select_only()
+            .column(voice_memos1::Column::Title)
+            .column(voice_memos1::Column::AudioBlob)
+            .column(voice_memos1::Column::AudioKey)
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .into_tuple::<(String, Option<Vec<u8>>, Option<String>)>()
+            .one(db.0)
+            .await;
+
+        match memo {
+            Ok(Some((_, None, Some(key)))) => match store.0.url(&key) {
+                Ok(url) => MemoAudioApiResponse::TemporaryRedirect(url),
+                Err(e) => MemoAudioApiResponse::InternalServerError(ErrorBody::new("audio_unavailable", e)),
+            },
+            Ok(Some((title, Some(audio), _))) if !audio.is_empty() => {
+                let mime_type = audio::sniff_audio(&audio).unwrap_or(audio::DEFAULT_AUDIO_MIME);
+                match range.0.as_deref().map(|header| byte_range(header, audio.len())) {
+                    Some(ByteRange::Partial(start, end)) => MemoAudioApiResponse::PartialContent(
+                        Binary(audio[start..=end].to_vec()),
+                        mime_type.to_string(),
+                        format!("bytes {}-{}/{}", start, end, audio.len()),
+                        "bytes".to_string(),
+                    ),
+                    Some(ByteRange::Unsatisfiable) => MemoAudioApiResponse::RangeNotSatisfiable(format!("bytes */{}", audio.len())),
+                    Some(ByteRange::Full) | None => {
+                        let disposition = format!("inline; filename=\"{}.{}\"", download_name(&title), audio::file_extension(mime_type));
+                        MemoAudioApiResponse::Ok(Binary(audio), mime_type.to_string(), disposition, "bytes".to_string())
+                    }
+                }
+            }
+            Ok(Some(_)) => MemoAudioApiResponse::NotFound(ErrorBody::new("not_found", "Memo has no audio")),
+            Ok(None) => MemoAudioApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => MemoAudioApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        }
+    }
+
+    /// The memo as a document for pasting into a notes app: Markdown (the default) or plain text.
+    /// Every section notes whether it is AI-generated; `exclude_ai_content=true` leaves those out.
+    #[oai(path = "/memo/:memo_id/export", method = "get")]
+    async fn export_memo(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+        format: Query<Option<MemoExportFormat>>,
+        exclude_ai_content: Query<Option<bool>>,
+    ) -> MemoExportApiResponse {
+        let user_id = auth.0.id;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return MemoExportApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        let memo = voice_memos1::Entity::find_by_id(memo_uuid)
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .one(db.0)
+            .await;
+
+        match memo {
+            Ok(Some(memo)) => {
+                let format = format.0.unwrap_or_default();
+                let tz = preferences_or_default(db.0, user_id).await.tz();
+                let (content_type, extension) = match format {
+                    MemoExportFormat::Markdown => ("text/markdown; charset=utf-8", "md"),
+                    MemoExportFormat::Txt => ("text/plain; charset=utf-8", "txt"),
+                };
+                let disposition = format!("attachment; filename=\"{}.{}\"", download_name(&memo.title), extension);
+                let document = render_memo_document(&memo, format, tz, exclude_ai_content.0.unwrap_or(false));
+                MemoExportApiResponse::Ok(Binary(document.into_bytes()), content_type.to_string(), disposition)
+            }
+            Ok(None) => MemoExportApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => MemoExportApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        }
+    }
+
+    #[oai(path = "/update_memo/:memo_id", method = "patch")]
+    async fn update_memo(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+        Json(payload): Json<MemoUpdate>,
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return MemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        let memo = match voice_memos1::Entity::find_by_id(memo_uuid)
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
             .one(db.0)
             .await {
             Ok(Some(memo)) => memo,
-            Ok(None) => return Json(MemoResponse { message: "Memo not found or access denied".to_string(), memo_id: "".to_string() }),
-            Err(e) => return Json(MemoResponse { message: format!("DB Error: {}", e), memo_id: "".to_string() }),
+            Ok(None) => return MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        };
+
+        let folder = match resolve_folder_id(db.0, user_id, payload.folder_id.as_deref()).await {
+            Ok(folder) => folder,
+            Err(response) => return response,
         };
 
         let mut active_memo: voice_memos1::ActiveModel = memo.into();
         let clean_field = |val: String| if val.trim().is_empty() { None } else { Some(val) };
 
-        if let Some(title) = payload.title {
-            if !title.trim().is_empty() {
-                active_memo.title = Set(title);
-            }
+        if let Some(title) = payload.title
+            && !title.trim().is_empty()
+        {
+            active_memo.title = Set(title);
         }
         
+        // A manual edit makes the field human-written; the flag changes in the same UPDATE as the content
         if let Some(transcript) = payload.transcript {
             active_memo.transcript = Set(clean_field(transcript));
+            active_memo.transcript_ai_generated = Set(false);
         }
         if let Some(translate) = payload.translate {
             active_memo.translate = Set(clean_field(translate));
+            active_memo.translate_ai_generated = Set(false);
         }
         if let Some(summary) = payload.summary {
             active_memo.summary = Set(clean_field(summary));
+            active_memo.summary_ai_generated = Set(false);
         }
-        if let Some(tags_vec) = payload.tags {
+        let tag_names = payload.tags.as_deref().map(normalize_tags);
+        if let Some(ref tags_vec) = tag_names {
             // Serialize the vector to a JSON string before saving.
-            active_memo.tags = Set(serde_json::to_string(&tags_vec).ok());
+            active_memo.tags = Set(serde_json::to_string(tags_vec).ok());
         }
+        if let Some(folder_id) = folder {
+            active_memo.folder_id = Set(folder_id);
+        }
+
+        active_memo.updated_at = Set(Utc::now().naive_utc());
 
-        match active_memo.update(db.0).await {
-            Ok(updated) => Json(MemoResponse { message: "Memo updated successfully".to_string(), memo_id: updated.id.to_string() }),
-            Err(e) => Json(MemoResponse { message: format!("Failed to update memo: {}", e), memo_id: "".to_string() }),
+        let txn = match db.0.begin().await {
+            Ok(txn) => txn,
+            Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to update memo", &e)),
+        };
+        // Returning early drops the transaction, so the row and its tags change together or not at all
+        let updated = match update_if_version(&txn, active_memo, payload.version).await {
+            Ok(updated) => updated,
+            Err(response) => return response,
+        };
+
+        if let Some(tag_names) = tag_names
+            && let Err(e) = sync_memo_tags(&txn, user_id, updated.id, &tag_names).await
+        {
+            return MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to update memo", &e));
         }
+        if let Err(e) = txn.commit().await {
+            return MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to update memo", &e));
+        }
+
+        metrics::record_memo_writes("update", 1);
+        MemoApiResponse::Ok(Json(MemoResponse { message: "Memo updated successfully".to_string(), memo_id: updated.id.to_string(), version: Some(updated.version) }))
     }
 
     #[oai(path = "/delete_memo/:memo_id", method = "delete")]
     async fn delete_memo(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAuth,
         db: Data<&DatabaseConnection>,
         Path(memo_id): Path<String>,
-    ) -> Json<MemoResponse> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(c) => c,
-            Err(msg) => return Json(MemoResponse { message: msg, memo_id: "".to_string() }),
-        };
-
-        let user_id = match Uuid::parse_str(&claims.sub) {
-            Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid user ID".to_string(), memo_id: "".to_string() }),
-        };
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
         
         let memo_uuid = match Uuid::parse_str(&memo_id) {
             Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid memo ID".to_string(), memo_id: "".to_string() }),
+            Err(_) => return MemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
         };
 
-        let result = voice_memos1::Entity::delete_many()
+        // Move to trash; DELETE /purge_memo/:memo_id removes the row for good
+        let result = voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::DeletedAt, Expr::value(Utc::now().naive_utc()))
             .filter(voice_memos1::Column::Id.eq(memo_uuid))
             .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
             .exec(db.0)
             .await;
 
         match result {
-            Ok(res) if res.rows_affected > 0 => Json(MemoResponse { message: "Memo deleted".to_string(), memo_id: memo_id }),
-            Ok(_) => Json(MemoResponse { message: "Memo not found or access denied".to_string(), memo_id: "".to_string() }),
-            Err(e) => Json(MemoResponse { message: format!("Deletion failed: {}", e), memo_id: "".to_string() }),
+            Ok(res) if res.rows_affected > 0 => {
+                metrics::record_memo_writes("delete", res.rows_affected);
+                MemoApiResponse::Ok(Json(MemoResponse { message: "Memo moved to trash".to_string(), memo_id, version: None }))
+            }
+            Ok(_) => MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Deletion failed", &e)),
         }
     }
 
+    /// Move several memos to the trash at once. Unknown and malformed IDs are reported, not treated as errors.
+    #[oai(path = "/memos/bulk_delete", method = "post")]
+    async fn bulk_delete_memos(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<DeleteMemosPayload>,
+    ) -> DeleteMemosApiResponse {
+        trash_many(db.0, auth.0.id, payload.ids).await
+    }
+
+    /// Deprecated: use `POST /memos/bulk_delete`
+    #[oai(path = "/delete_memos", method = "post", deprecated)]
+    async fn delete_memos(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<DeleteMemosPayload>,
+    ) -> DeleteMemosApiResponse {
+        trash_many(db.0, auth.0.id, payload.ids).await
+    }
+
+    /// Move every memo to the trash; `DELETE /purge_all_memos` empties it
     #[oai(path = "/delete_all_memos", method = "delete")]
     async fn delete_all_memos(
         &self,
-        auth: ApiKeyAuth,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
+
+        match voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::DeletedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .exec(db.0)
+            .await
+        {
+            Ok(update_result) => {
+                metrics::record_memo_writes("delete", update_result.rows_affected);
+                MemoApiResponse::Ok(Json(MemoResponse {
+                    message: format!("Moved {} memo(s) to trash", update_result.rows_affected),
+                    memo_id: "".to_string(),
+                    version: None,
+                }))
+            }
+            Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete memos", &e)),
+        }
+    }
+
+    /// List memos in the trash a page at a time, most recently deleted first. Audio is left out, as in `get_memos`.
+    #[oai(path = "/trash", method = "get")]
+    async fn get_trash(
+        &self,
+        auth: MemosReadAuth,
+        db: Data<&DatabaseConnection>,
+        /// 1-based; past the last page gives an empty `items`
+        page: Query<Option<u64>>,
+        /// Memos per page, 25 by default and at most 100
+        page_size: Query<Option<u64>>,
+    ) -> TrashApiResponse {
+        let user_id = auth.0.id;
+
+        let query = voice_memos1::Entity::find()
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_not_null())
+            .order_by_desc(voice_memos1::Column::DeletedAt)
+            .order_by_desc(voice_memos1::Column::Id);
+
+        match list_page(db.0, user_id, query, page.0, page_size.0).await {
+            Ok(memos) => TrashApiResponse::Ok(Json(memos)),
+            Err(e) => TrashApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch trash", &e)),
+        }
+    }
+
+    /// Take a memo out of the trash and return it without its audio. 409 if it isn't in the trash.
+    #[oai(path = "/restore_memo/:memo_id", method = "post")]
+    async fn restore_memo(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+    ) -> RestoreMemoApiResponse {
+        restore_from_trash(db.0, auth.0.id, memo_id).await
+    }
+
+    /// Old path of `POST /restore_memo/:memo_id`
+    #[oai(path = "/memo/:memo_id/restore", method = "post", deprecated)]
+    async fn restore_memo_legacy(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+    ) -> RestoreMemoApiResponse {
+        restore_from_trash(db.0, auth.0.id, memo_id).await
+    }
+
+    /// Delete a memo for good, whether or not it is in the trash
+    #[oai(path = "/purge_memo/:memo_id", method = "delete")]
+    async fn purge_memo(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(memo_id): Path<String>,
+    ) -> MemoApiResponse {
+        purge(db.0, store.0, auth.0.id, memo_id).await
+    }
+
+    /// Old path of `DELETE /purge_memo/:memo_id`
+    #[oai(path = "/memo/:memo_id/permanent", method = "delete", deprecated)]
+    async fn delete_memo_permanently(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(memo_id): Path<String>,
+    ) -> MemoApiResponse {
+        purge(db.0, store.0, auth.0.id, memo_id).await
+    }
+
+    /// Empty the trash: permanently delete every trashed memo
+    #[oai(path = "/purge_all_memos", method = "delete")]
+    async fn purge_all_memos(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
+        let trashed = Condition::all()
+            .add(voice_memos1::Column::UserId.eq(user_id))
+            .add(voice_memos1::Column::DeletedAt.is_not_null());
+
+        let keys = match stored_audio_keys(db.0, trashed.clone()).await {
+            Ok(keys) => keys,
+            Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to purge memos", &e)),
+        };
+        match voice_memos1::Entity::delete_many().filter(trashed).exec(db.0).await {
+            Ok(delete_result) => {
+                store.0.discard(keys).await;
+                metrics::record_memo_writes("purge", delete_result.rows_affected);
+                MemoApiResponse::Ok(Json(MemoResponse {
+                    message: format!("Permanently deleted {} memo(s)", delete_result.rows_affected),
+                    memo_id: "".to_string(),
+                    version: None,
+                }))
+            }
+            Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to purge memos", &e)),
+        }
+    }
+
+    /// Move a memo into a folder or out of its folder. Unlike `update_memo` this needs no `version`.
+    #[oai(path = "/memo/:memo_id/folder", method = "put")]
+    async fn move_memo(
+        &self,
+        auth: MemosWriteAuth,
         db: Data<&DatabaseConnection>,
-    ) -> Json<MemoResponse> {
-        let claims = match validate_token(&auth.0.token) {
-            Ok(c) => c,
-            Err(msg) => return Json(MemoResponse { message: msg, memo_id: "".to_string() }),
+        Path(memo_id): Path<String>,
+        Json(payload): Json<MoveMemoPayload>,
+    ) -> MemoApiResponse {
+        let user_id = auth.0.id;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return MemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
         };
+        // Here a missing folder means unfiled rather than "leave it where it is"
+        let folder_id = match resolve_folder_id(db.0, user_id, Some(payload.folder_id.as_deref().unwrap_or_default())).await {
+            Ok(folder) => folder.flatten(),
+            Err(response) => return response,
+        };
+
+        // Filing is not a content change, so neither version nor updated_at move
+        let result = voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::FolderId, Expr::value(folder_id))
+            .filter(voice_memos1::Column::Id.eq(memo_uuid))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .exec_with_returning(db.0)
+            .await;
+
+        match result.map(|memos| memos.into_iter().next()) {
+            Ok(Some(memo)) => {
+                let message = if folder_id.is_some() { "Memo moved" } else { "Memo taken out of its folder" };
+                MemoApiResponse::Ok(Json(MemoResponse { message: message.to_string(), memo_id: memo.id.to_string(), version: Some(memo.version) }))
+            }
+            Ok(None) => MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Update failed", &e)),
+        }
+    }
 
-        let user_id = match Uuid::parse_str(&claims.sub) {
+    /// Copy a memo into a new one titled "<title> (copy)", e.g. to rewrite its summary while keeping the original.
+    /// Tags and folder come along; links, favorite status and version history don't.
+    #[oai(path = "/memo/:memo_id/duplicate", method = "post")]
+    async fn duplicate_memo(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(memo_id): Path<String>,
+        /// Copy the recording as well (default `true`)
+        with_audio: Query<Option<bool>>,
+    ) -> DuplicateMemoApiResponse {
+        let user_id = auth.0.id;
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
             Ok(id) => id,
-            Err(_) => return Json(MemoResponse { message: "Invalid user ID".to_string(), memo_id: "".to_string() }),
+            Err(_) => return DuplicateMemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
         };
 
-        match voice_memos1::Entity::delete_many()
+        // Trashed memos are looked up too, so they get 409 rather than the 404 of a missing memo
+        let memo = match voice_memos1::Entity::find_by_id(memo_uuid)
             .filter(voice_memos1::Column::UserId.eq(user_id))
-            .exec(db.0)
+            .one(db.0)
             .await
         {
-            Ok(delete_result) => Json(MemoResponse {
-                message: format!("Deleted {} memo(s)", delete_result.rows_affected),
-                memo_id: "".to_string(),
-            }),
-            Err(e) => Json(MemoResponse {
-                message: format!("Failed to delete memos: {}", e),
-                memo_id: "".to_string(),
-            }),
+            Ok(Some(memo)) => memo,
+            Ok(None) => return DuplicateMemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return DuplicateMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch memo", &e)),
+        };
+        if memo.deleted_at.is_some() {
+            return DuplicateMemoApiResponse::Conflict(ErrorBody::new("in_trash", "Memo is in the trash; restore it before duplicating"));
         }
-    }
-}
-
-// --- Helper Functions ---
 
-fn validate_token(token: &str) -> Result<Claims, String> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-        &Validation::new(Algorithm::HS256),
-    )
-    .map(|data| data.claims)
-    .map_err(|_| "Invalid or expired token".to_string())
+        let audio = match (with_audio.0.unwrap_or(true), &memo.audio_blob, memo.audio_key.as_deref()) {
+            (false, _, _) | (true, None, None) => None,
+            (true, Some(blob), _) => Some(blob.clone()),
+            (true, None, Some(key)) => match store.0.fetch(key).await {
+                Ok(audio) => Some(audio),
+                Err(e) => return DuplicateMemoApiResponse::InternalServerError(ErrorBody::new("audio_unavailable", e)),
+            },
+        };
+        let copy_id = Uuid::new_v4();
+        let audio = match audio {
+            Some(audio) => match store.0.put(copy_id, audio).await {
+                Ok(stored) => stored,
+                Err(e) => return DuplicateMemoApiResponse::InternalServerError(ErrorBody::new("audio_storage_failed", format!("Failed to store audio: {}", e))),
+            },
+            None => StoredAudio { blob: None, key: None },
+        };
+
+        let tag_names: Vec<String> = memo.tags.as_deref().and_then(|json| serde_json::from_str(json).ok()).unwrap_or_default();
+        let now = Utc::now().naive_utc();
+        let copy = voice_memos1::ActiveModel {
+            id: Set(copy_id),
+            user_id: Set(user_id),
+            title: Set(format!("{} (copy)", memo.title)),
+            audio_blob: Set(audio.blob),
+            audio_key: Set(audio.key.clone()),
+            transcript: Set(memo.transcript),
+            translate: Set(memo.translate),
+            summary: Set(memo.summary),
+            tags: Set(memo.tags),
+            duration: Set(memo.duration),
+            duration_seconds: Set(memo.duration_seconds),
+            created_at: Set(now),
+            updated_at: Set(now),
+            version: Set(1),
+            // The content is the same, so what was AI-generated still is
+            transcript_ai_generated: Set(memo.transcript_ai_generated),
+            translate_ai_generated: Set(memo.translate_ai_generated),
+            summary_ai_generated: Set(memo.summary_ai_generated),
+            deleted_at: Set(None),
+            is_favorite: Set(false),
+            folder_id: Set(memo.folder_id),
+        };
+
+        let saved = match insert_with_tags(db.0, copy, user_id, &tag_names).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                store.0.discard(audio.key).await;
+                return DuplicateMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to duplicate memo", &e));
+            }
+        };
+        metrics::record_memo_writes("create", 1);
+
+        let audio_url = match saved.audio_key.as_deref().map(|key| store.0.url(key)).transpose() {
+            Ok(url) => url,
+            Err(e) => return DuplicateMemoApiResponse::InternalServerError(ErrorBody::new("audio_unavailable", e)),
+        };
+        let tz = preferences_or_default(db.0, user_id).await.tz();
+        let mut response = MemoOutput::in_timezone(saved, tz);
+        response.audio_url = audio_url;
+        DuplicateMemoApiResponse::Ok(Json(response))
+    }
+
+    /// Pin or unpin a memo; favorites come first in `get_memos`
+    #[oai(path = "/memo/:memo_id/favorite", method = "post")]
+    async fn toggle_favorite(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+    ) -> FavoriteApiResponse {
+        let user_id = auth.0.id;
+
+        let memo_uuid = match Uuid::parse_str(&memo_id) {
+            Ok(id) => id,
+            Err(_) => return FavoriteApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        // Not a content change, so neither version nor updated_at move
+        let result = voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::IsFavorite, Expr::col(voice_memos1::Column::IsFavorite).not())
+            .filter(voice_memos1::Column::Id.eq(memo_uuid))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .exec_with_returning(db.0)
+            .await;
+
+        match result.map(|memos| memos.into_iter().next()) {
+            Ok(Some(memo)) => FavoriteApiResponse::Ok(Json(FavoriteResponse { memo_id: memo.id.to_string(), is_favorite: memo.is_favorite })),
+            Ok(None) => FavoriteApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => FavoriteApiResponse::InternalServerError(ErrorBody::from_db("Update failed", &e)),
+        }
+    }
+
+    #[oai(path = "/memo/:memo_id/links", method = "post")]
+    async fn create_memo_link(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+        Json(payload): Json<MemoLinkPayload>,
+    ) -> MemoLinkApiResponse {
+        let user_id = auth.0.id;
+
+        let (from_id, to_id) = match (Uuid::parse_str(&memo_id), Uuid::parse_str(&payload.target_id)) {
+            (Ok(from_id), Ok(to_id)) => (from_id, to_id),
+            _ => return MemoLinkApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        if from_id == to_id {
+            return MemoLinkApiResponse::UnprocessableEntity(ErrorBody::new("self_link", "A memo cannot link to itself"));
+        }
+
+        // Both ends of the link must belong to the caller
+        for id in [from_id, to_id] {
+            match find_owned_memo(db.0, id, user_id).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return MemoLinkApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+                Err(e) => return MemoLinkApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+            }
+        }
+
+        let existing = memo_links::Entity::find()
+            .filter(memo_links::Column::FromMemoId.eq(from_id))
+            .filter(memo_links::Column::ToMemoId.eq(to_id))
+            .one(db.0)
+            .await;
+
+        match existing {
+            Ok(Some(_)) => return MemoLinkApiResponse::Conflict(ErrorBody::new("link_exists", "Link already exists")),
+            Ok(None) => {}
+            Err(e) => return MemoLinkApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        }
+
+        let new_link = memo_links::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            from_memo_id: Set(from_id),
+            to_memo_id: Set(to_id),
+            created_at: Set(Utc::now().naive_utc()),
+        };
+
+        match new_link.insert(db.0).await {
+            Ok(_) => MemoLinkApiResponse::Ok(Json(MemoResponse { message: "Link created".to_string(), memo_id, version: None })),
+            Err(e) => MemoLinkApiResponse::InternalServerError(ErrorBody::from_db("Failed to create link", &e)),
+        }
+    }
+
+    #[oai(path = "/memo/:memo_id/links/:target_id", method = "delete")]
+    async fn delete_memo_link(
+        &self,
+        auth: MemosWriteAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+        Path(target_id): Path<String>,
+    ) -> MemoLinkApiResponse {
+        let user_id = auth.0.id;
+
+        let (from_id, to_id) = match (Uuid::parse_str(&memo_id), Uuid::parse_str(&target_id)) {
+            (Ok(from_id), Ok(to_id)) => (from_id, to_id),
+            _ => return MemoLinkApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+        };
+
+        match find_owned_memo(db.0, from_id, user_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return MemoLinkApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return MemoLinkApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+        }
+
+        let result = memo_links::Entity::delete_many()
+            .filter(memo_links::Column::FromMemoId.eq(from_id))
+            .filter(memo_links::Column::ToMemoId.eq(to_id))
+            .exec(db.0)
+            .await;
+
+        match result {
+            Ok(res) if res.rows_affected > 0 => MemoLinkApiResponse::Ok(Json(MemoResponse { message: "Link deleted".to_string(), memo_id, version: None })),
+            Ok(_) => MemoLinkApiResponse::NotFound(ErrorBody::new("not_found", "Link not found")),
+            Err(e) => MemoLinkApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete link", &e)),
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+// Compare-and-swap on `version`: the UPDATE only matches if nobody saved the memo since the client read it.
+async fn update_if_version<C: ConnectionTrait>(
+    db: &C,
+    mut memo: voice_memos1::ActiveModel,
+    expected_version: i32,
+) -> Result<voice_memos1::Model, MemoApiResponse> {
+    memo.version = Set(expected_version + 1);
+    let memo_id = memo.id.clone().unwrap();
+
+    match voice_memos1::Entity::update(memo)
+        .filter(voice_memos1::Column::Version.eq(expected_version))
+        .exec(db)
+        .await
+    {
+        Ok(updated) => Ok(updated),
+        Err(DbErr::RecordNotUpdated) => match find_list_item(db, Condition::all().add(voice_memos1::Column::Id.eq(memo_id))).await {
+            Ok(Some(current)) => Err(MemoApiResponse::Conflict(Json(MemoConflictResponse {
+                message: format!("Memo was modified (now at version {})", current.version),
+                current,
+            }))),
+            Ok(None) => Err(MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied"))),
+            Err(e) => Err(MemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e))),
+        },
+        Err(e) => Err(MemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to update memo", &e))),
+    }
+}
+
+fn quick_capture_limit(ep: impl Endpoint) -> impl Endpoint {
+    ep.with(SizeLimit::new(QUICK_CAPTURE_MAX_BODY_BYTES))
+}
+
+// The first memo matching `condition` as a list item, loaded without its audio. Timestamps are in UTC.
+async fn find_list_item<C: ConnectionTrait>(db: &C, condition: Condition) -> Result<Option<MemoListItem>, DbErr> {
+    let row = select_list_columns(voice_memos1::Entity::find().filter(condition))
+        .into_model::<MemoListRow>()
+        .one(db)
+        .await?;
+    Ok(row.map(|row| MemoListItem::new(row, None)))
+}
+
+async fn find_owned_memo(db: &DatabaseConnection, memo_id: Uuid, user_id: Uuid) -> Result<Option<voice_memos1::Model>, DbErr> {
+    voice_memos1::Entity::find_by_id(memo_id)
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .one(db)
+        .await
+}
+
+// A payload's `folder_id`: `None` when absent, `Some(None)` for `""`, otherwise one of the caller's folders.
+async fn resolve_folder_id(db: &DatabaseConnection, user_id: Uuid, folder_id: Option<&str>) -> Result<Option<Option<Uuid>>, MemoApiResponse> {
+    let Some(raw) = folder_id.map(str::trim) else {
+        return Ok(None);
+    };
+    if raw.is_empty() {
+        return Ok(Some(None));
+    }
+    let Ok(folder_uuid) = Uuid::parse_str(raw) else {
+        return Err(MemoApiResponse::BadRequest(ErrorBody::new("invalid_folder_id", "Invalid folder ID")));
+    };
+    match find_owned_folder(db, user_id, folder_uuid).await {
+        Ok(Some(_)) => Ok(Some(Some(folder_uuid))),
+        Ok(None) => Err(MemoApiResponse::BadRequest(ErrorBody::new("folder_not_found", "Folder not found"))),
+        Err(e) => Err(MemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e))),
+    }
+}
+
+// Shared by `POST /memos/bulk_delete` and its old path. Other users' memos are filtered out by the
+// lookup, so they come back as not found, exactly like IDs that don't exist.
+async fn trash_many(db: &DatabaseConnection, user_id: Uuid, raw_ids: Vec<String>) -> DeleteMemosApiResponse {
+    if raw_ids.is_empty() {
+        return DeleteMemosApiResponse::BadRequest(ErrorBody::new("no_memo_ids", "No memo IDs given"));
+    }
+    if raw_ids.len() > MAX_BULK_DELETE_IDS {
+        return DeleteMemosApiResponse::BadRequest(ErrorBody::new("too_many_ids", format!("At most {} memos can be deleted at once", MAX_BULK_DELETE_IDS)));
+    }
+
+    let mut ids = Vec::new();
+    let mut invalid_ids = Vec::new();
+    for raw in raw_ids {
+        match Uuid::parse_str(raw.trim()) {
+            Ok(id) if !ids.contains(&id) => ids.push(id),
+            Ok(_) => {}
+            Err(_) => invalid_ids.push(raw),
+        }
+    }
+
+    let owned: Vec<Uuid> = if ids.is_empty() {
+        Vec::new()
+    } else {
+        match voice_memos1::Entity::find()
+            .select_only()
+            .column(voice_memos1::Column::Id)
+            .filter(voice_memos1::Column::Id.is_in(ids.clone()))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .into_tuple()
+            .all(db)
+            .await
+        {
+            Ok(owned) => owned,
+            Err(e) => return DeleteMemosApiResponse::InternalServerError(ErrorBody::from_db("Deletion failed", &e)),
+        }
+    };
+
+    let deleted = if owned.is_empty() {
+        0
+    } else {
+        match voice_memos1::Entity::update_many()
+            .col_expr(voice_memos1::Column::DeletedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(voice_memos1::Column::Id.is_in(owned.clone()))
+            .filter(voice_memos1::Column::UserId.eq(user_id))
+            .filter(voice_memos1::Column::DeletedAt.is_null())
+            .exec(db)
+            .await
+        {
+            Ok(res) => res.rows_affected,
+            Err(e) => return DeleteMemosApiResponse::InternalServerError(ErrorBody::from_db("Deletion failed", &e)),
+        }
+    };
+
+    let not_found_ids = ids.into_iter().filter(|id| !owned.contains(id)).map(|id| id.to_string()).collect();
+
+    metrics::record_memo_writes("delete", deleted);
+    DeleteMemosApiResponse::Ok(Json(DeleteMemosResponse {
+        deleted,
+        deleted_ids: owned.iter().map(|id| id.to_string()).collect(),
+        invalid_ids,
+        not_found_ids,
+    }))
+}
+
+// Shared by `POST /restore_memo/:memo_id` and its old path
+async fn restore_from_trash(db: &DatabaseConnection, user_id: Uuid, memo_id: String) -> RestoreMemoApiResponse {
+    let memo_uuid = match Uuid::parse_str(&memo_id) {
+        Ok(id) => id,
+        Err(_) => return RestoreMemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+    };
+    let owned = Condition::all()
+        .add(voice_memos1::Column::Id.eq(memo_uuid))
+        .add(voice_memos1::Column::UserId.eq(user_id));
+
+    let result = voice_memos1::Entity::update_many()
+        .col_expr(voice_memos1::Column::DeletedAt, Expr::value(Option::<chrono::NaiveDateTime>::None))
+        .filter(owned.clone())
+        .filter(voice_memos1::Column::DeletedAt.is_not_null())
+        .exec(db)
+        .await;
+
+    // Nothing restored means the memo either isn't trashed or isn't the caller's
+    let restored = match result {
+        Ok(res) => res.rows_affected > 0,
+        Err(e) => return RestoreMemoApiResponse::InternalServerError(ErrorBody::from_db("Restore failed", &e)),
+    };
+    match find_list_item(db, owned.add(voice_memos1::Column::DeletedAt.is_null())).await {
+        Ok(Some(memo)) if restored => RestoreMemoApiResponse::Ok(Json(memo)),
+        Ok(Some(current)) => RestoreMemoApiResponse::Conflict(Json(MemoConflictResponse {
+            message: "Memo is not in the trash".to_string(),
+            current,
+        })),
+        Ok(None) => RestoreMemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+        Err(e) => RestoreMemoApiResponse::InternalServerError(ErrorBody::from_db("Database query failed", &e)),
+    }
+}
+
+// Shared by `DELETE /purge_memo/:memo_id` and its old path
+async fn purge(db: &DatabaseConnection, store: &AudioStore, user_id: Uuid, memo_id: String) -> MemoApiResponse {
+    let memo_uuid = match Uuid::parse_str(&memo_id) {
+        Ok(id) => id,
+        Err(_) => return MemoApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+    };
+    let owned = Condition::all()
+        .add(voice_memos1::Column::Id.eq(memo_uuid))
+        .add(voice_memos1::Column::UserId.eq(user_id));
+
+    let keys = match stored_audio_keys(db, owned.clone()).await {
+        Ok(keys) => keys,
+        Err(e) => return MemoApiResponse::InternalServerError(ErrorBody::from_db("Deletion failed", &e)),
+    };
+    let result = voice_memos1::Entity::delete_many().filter(owned).exec(db).await;
+
+    match result {
+        Ok(res) if res.rows_affected > 0 => {
+            store.discard(keys).await;
+            metrics::record_memo_writes("purge", res.rows_affected);
+            MemoApiResponse::Ok(Json(MemoResponse { message: "Memo permanently deleted".to_string(), memo_id, version: None }))
+        }
+        Ok(_) => MemoApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+        Err(e) => MemoApiResponse::InternalServerError(ErrorBody::from_db("Deletion failed", &e)),
+    }
+}
+
+// The caller's tags outside the trash as (name, memo count, latest memo update), with names that
+// differ only in case merged under the casing most of their memos use.
+async fn tag_counts(db: &DatabaseConnection, user_id: Uuid) -> Result<Vec<(String, i64, NaiveDateTime)>, DbErr> {
+    let tagged = tags::Entity::find()
+        .select_only()
+        .join(JoinType::InnerJoin, tags::Relation::MemoTags.def())
+        .join(JoinType::InnerJoin, memo_tags::Relation::VoiceMemos1.def())
+        .filter(tags::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null());
+
+    // A memo tagged both "Work" and "work" still counts once
+    let merged = tagged
+        .clone()
+        .column_as(Expr::cust("LOWER(tags.name)"), "folded")
+        .column_as(Expr::cust("COUNT(DISTINCT memo_tags.memo_id)"), "count")
+        .column_as(voice_memos1::Column::UpdatedAt.max(), "last_used_at")
+        .group_by(Expr::cust("LOWER(tags.name)"))
+        .into_tuple::<(String, i64, NaiveDateTime)>()
+        .all(db)
+        .await?;
+    let spellings = tagged
+        .column(tags::Column::Name)
+        .column_as(memo_tags::Column::MemoId.count(), "count")
+        .group_by(tags::Column::Name)
+        .into_tuple::<(String, i64)>()
+        .all(db)
+        .await?;
+
+    // Most used casing wins; a tie goes to the one that sorts first
+    let mut casing: HashMap<String, (String, i64)> = HashMap::new();
+    for (name, count) in spellings {
+        let entry = casing.entry(name.to_lowercase()).or_insert_with(|| (name.clone(), count));
+        if count > entry.1 || (count == entry.1 && name < entry.0) {
+            *entry = (name, count);
+        }
+    }
+
+    Ok(merged
+        .into_iter()
+        .map(|(folded, count, last_used_at)| {
+            let name = casing.remove(&folded).map(|(name, _)| name).unwrap_or(folded);
+            (name, count, last_used_at)
+        })
+        .collect())
+}
+
+/// Object keys of the recordings of the memos matching `condition`, read before the memos are
+/// deleted so the objects can be removed after.
+pub(crate) async fn stored_audio_keys<C: ConnectionTrait>(db: &C, condition: Condition) -> Result<Vec<String>, DbErr> {
+    voice_memos1::Entity::find()
+        .select_only()
+        .column(voice_memos1::Column::AudioKey)
+        .filter(condition)
+        .filter(voice_memos1::Column::AudioKey.is_not_null())
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+async fn linked_memos(db: &DatabaseConnection, user_id: Uuid, ids: Vec<Uuid>) -> Result<Vec<LinkedMemo>, DbErr> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let memos = voice_memos1::Entity::find()
+        .filter(voice_memos1::Column::Id.is_in(ids))
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .all(db)
+        .await?;
+
+    Ok(memos.into_iter().map(|memo| LinkedMemo { id: memo.id.to_string(), title: memo.title }).collect())
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Full,
+    /// First and last byte, inclusive
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+// The first range of a `Range` header for a body of `len` bytes. A header that can't be parsed is
+// ignored and the whole body sent, as HTTP requires.
+fn byte_range(header: &str, len: usize) -> ByteRange {
+    let Some(first) = header.trim().strip_prefix("bytes=").and_then(|ranges| ranges.split(',').next()) else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = first.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // `bytes=-500`: the last 500 bytes
+        ("", suffix) => match suffix.parse::<usize>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        // `bytes=1000-`: from byte 1000 to the end
+        (start, "") => match start.parse::<usize>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+
+    if len == 0 || start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(start, end)
+    }
+}
+
+// Selects exactly the columns of a `MemoListRow`.
+fn select_list_columns(query: Select<voice_memos1::Entity>) -> Select<voice_memos1::Entity> {
+    query
+        .select_only()
+        .columns([
+            voice_memos1::Column::Id,
+            voice_memos1::Column::Title,
+            voice_memos1::Column::Transcript,
+            voice_memos1::Column::Translate,
+            voice_memos1::Column::Summary,
+            voice_memos1::Column::Tags,
+            voice_memos1::Column::Duration,
+            voice_memos1::Column::DurationSeconds,
+            voice_memos1::Column::CreatedAt,
+            voice_memos1::Column::UpdatedAt,
+            voice_memos1::Column::Version,
+            voice_memos1::Column::IsFavorite,
+            voice_memos1::Column::FolderId,
+            voice_memos1::Column::TranscriptAiGenerated,
+            voice_memos1::Column::TranslateAiGenerated,
+            voice_memos1::Column::SummaryAiGenerated,
+            voice_memos1::Column::DeletedAt,
+        ])
+        .column_as(voice_memos1::Column::AudioBlob.is_not_null().or(voice_memos1::Column::AudioKey.is_not_null()), "has_audio")
+}
+
+// Memos whose title, transcript or summary contain `term`, favorites then newest first. Postgres
+// ignores case with ILIKE; SQLite's LIKE already does for ASCII.
+fn like_search(backend: DatabaseBackend, user_id: Uuid, term: &str, filters: &ListFilters) -> Selector<SelectModel<SearchRow>> {
+    let pattern = format!("%{}%", escape_like(term));
+    let matches = |column: voice_memos1::Column| match backend {
+        // Backslash already is Postgres' LIKE escape. Spelling it out doesn't work: sea-query puts the
+        // pattern and its ESCAPE clause in parentheses, which Postgres rejects
+        DatabaseBackend::Postgres => Expr::col((voice_memos1::Entity, column)).ilike(pattern.clone()),
+        _ => Expr::col((voice_memos1::Entity, column)).like(LikeExpr::new(pattern.clone()).escape('\\')),
+    };
+
+    let query = voice_memos1::Entity::find()
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .filter(
+            Condition::any()
+                .add(matches(voice_memos1::Column::Title))
+                .add(matches(voice_memos1::Column::Transcript))
+                .add(matches(voice_memos1::Column::Summary)),
+        )
+        .order_by_desc(voice_memos1::Column::IsFavorite)
+        .order_by_desc(voice_memos1::Column::CreatedAt)
+        .order_by_desc(voice_memos1::Column::Id);
+    let query = filters.apply(query, user_id);
+
+    select_list_columns(query)
+        .column_as(matches(voice_memos1::Column::Title), "title_matched")
+        .column_as(matches(voice_memos1::Column::Transcript), "transcript_matched")
+        .column_as(matches(voice_memos1::Column::Summary), "summary_matched")
+        .into_model::<SearchRow>()
+}
+
+// The text `search_vector` is generated from (see its migration), for snippets from any of the fields.
+const SEARCH_DOCUMENT: &str =
+    "coalesce(voice_memos1.title, '') || ' ' || coalesce(voice_memos1.transcript, '') || ' ' || coalesce(voice_memos1.summary, '')";
+
+// Postgres only: matches against the generated `search_vector` column (GIN indexed), ranked with
+// `ts_rank`. Per-field matches use the same stemming, so they agree with the filter.
+
+fn full_text_search(user_id: Uuid, term: &str, filters: &ListFilters) -> Selector<SelectModel<SearchRow>> {
+    let ts_query = "plainto_tsquery('english', $1)";
+    let field_matches = |column: &str| {
+        Expr::cust_with_values(format!("to_tsvector('english', coalesce(voice_memos1.{}, '')) @@ {}", column, ts_query), [term])
+    };
+
+    let query = voice_memos1::Entity::find()
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .filter(Expr::cust_with_values(format!("voice_memos1.search_vector @@ {}", ts_query), [term]))
+        .order_by(Expr::cust_with_values(format!("ts_rank(voice_memos1.search_vector, {})", ts_query), [term]), Order::Desc)
+        .order_by_desc(voice_memos1::Column::CreatedAt)
+        .order_by_desc(voice_memos1::Column::Id);
+    let query = filters.apply(query, user_id);
+
+    select_list_columns(query)
+        .column_as(field_matches("title"), "title_matched")
+        .column_as(field_matches("transcript"), "transcript_matched")
+        .column_as(field_matches("summary"), "summary_matched")
+        .column_as(
+            Expr::cust_with_values(
+                format!(
+                    "ts_headline('english', {}, {}, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5')",
+                    SEARCH_DOCUMENT, ts_query
+                ),
+                [term],
+            ),
+            "snippet",
+        )
+        .into_model::<SearchRow>()
+}
+
+// The page and page size to serve: page 1 and `DEFAULT_PAGE_SIZE` unless asked otherwise, and never more than `MAX_PAGE_SIZE`.
+fn page_bounds(page: Option<u64>, page_size: Option<u64>) -> (u64, u64) {
+    (page.unwrap_or(1).max(1), page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE))
+}
+
+// One page of an already filtered and ordered memo query, without the audio.
+async fn list_page(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    query: Select<voice_memos1::Entity>,
+    page: Option<u64>,
+    page_size: Option<u64>,
+) -> Result<MemoPage, DbErr> {
+    let (page, page_size) = page_bounds(page, page_size);
+    let paginator = select_list_columns(query).into_model::<MemoListRow>().paginate(db, page_size);
+    let total = paginator.num_items().await?;
+    let rows = paginator.fetch_page(page - 1).await?;
+
+    let tz = preferences_or_default(db, user_id).await.tz();
+    let items = rows.into_iter().map(|row| MemoListItem::new(row, tz)).collect();
+    Ok(MemoPage { items, total, page, page_size })
+}
+
+async fn search_page(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    query: Selector<SelectModel<SearchRow>>,
+    page: Option<u64>,
+    page_size: Option<u64>,
+    exclude_ai_content: bool,
+) -> SearchMemosApiResponse {
+    let (page, page_size) = page_bounds(page, page_size);
+    let paginator = query.paginate(db, page_size);
+
+    let total = match paginator.num_items().await {
+        Ok(total) => total,
+        Err(e) => return SearchMemosApiResponse::InternalServerError(ErrorBody::from_db("Failed to search memos", &e)),
+    };
+    let rows = match paginator.fetch_page(page - 1).await {
+        Ok(rows) => rows,
+        Err(e) => return SearchMemosApiResponse::InternalServerError(ErrorBody::from_db("Failed to search memos", &e)),
+    };
+
+    let tz = preferences_or_default(db, user_id).await.tz();
+    let items = rows
+        .into_iter()
+        .map(|row| {
+            let matched_fields = [
+                (row.title_matched, MatchedField::Title),
+                (row.transcript_matched, MatchedField::Transcript),
+                (row.summary_matched, MatchedField::Summary),
+            ]
+            .into_iter()
+            .filter_map(|(matched, field)| matched.then_some(field))
+            .collect();
+            let memo = MemoListItem::new(row.memo, tz);
+            let memo = if exclude_ai_content { memo.without_ai_content() } else { memo };
+            SearchHit { memo, matched_fields, snippet: row.snippet }
+        })
+        .collect();
+
+    SearchMemosApiResponse::Ok(Json(SearchPage { items, total, page, page_size }))
+}
+
+// Makes `%`, `_` and the escape character itself match literally in a LIKE pattern.
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// A title reduced to characters that are safe in a quoted header value and on any file system.
+fn download_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() { "memo".to_string() } else { name.chars().take(64).collect() }
+}
+
+// Lays out a memo for `export_memo`. Both formats share the layout, only the markup differs:
+// title, a metadata block, the tags, then one section per non-empty text field with a note on
+// whether the model or the user wrote it. `exclude_ai_content` leaves out the model's sections.
+fn render_memo_document(memo: &voice_memos1::Model, format: MemoExportFormat, tz: Option<Tz>, exclude_ai_content: bool) -> String {
+    let markdown = format == MemoExportFormat::Markdown;
+    let mut document = String::new();
+
+    if markdown {
+        document.push_str(&format!("# {}\n\n", memo.title.trim()));
+    } else {
+        document.push_str(&format!("{}\n\n", memo.title.trim()));
+    }
+
+    let duration = memo.duration_seconds.map(format_duration).unwrap_or_else(|| memo.duration.trim().to_string());
+    let metadata = [("Created", format_timestamp(memo.created_at, tz)), ("Duration", duration)];
+    for (label, value) in metadata {
+        if markdown {
+            document.push_str(&format!("- **{}:** {}\n", label, value));
+        } else {
+            document.push_str(&format!("{}: {}\n", label, value));
+        }
+    }
+
+    let tags: Vec<String> = memo.tags.as_deref().and_then(|json| serde_json::from_str(json).ok()).unwrap_or_default();
+    if !tags.is_empty() {
+        if markdown {
+            // A hashtag ends at whitespace, so multi-word tags are joined up
+            let hashtags: Vec<String> = tags.iter().map(|tag| format!("#{}", tag.split_whitespace().collect::<Vec<_>>().join("-"))).collect();
+            document.push_str(&format!("\n{}\n", hashtags.join(" ")));
+        } else {
+            document.push_str(&format!("Tags: {}\n", tags.join(", ")));
+        }
+    }
+
+    let sections = [
+        ("Transcript", &memo.transcript, memo.transcript_ai_generated),
+        ("Translation", &memo.translate, memo.translate_ai_generated),
+        ("Summary", &memo.summary, memo.summary_ai_generated),
+    ];
+    for (heading, body, ai_generated) in sections {
+        let Some(body) = body.as_deref().map(str::trim).filter(|body| !body.is_empty()) else {
+            continue;
+        };
+        if ai_generated && exclude_ai_content {
+            continue;
+        }
+        // Each section says where its text came from, so readers can tell model output from their own words
+        let provenance = if ai_generated { "AI-generated, not edited since" } else { "Written or edited by hand" };
+        if markdown {
+            document.push_str(&format!("\n## {}\n\n_{}_\n\n{}\n", heading, provenance, body));
+        } else {
+            document.push_str(&format!("\n{}\n({})\n\n{}\n", heading.to_uppercase(), provenance, body));
+        }
+    }
+
+    document
+}
+
+// `M:SS`, or `H:MM:SS` from an hour up.
+fn format_duration(seconds: i32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    if hours > 0 { format!("{}:{:02}:{:02}", hours, minutes, seconds) } else { format!("{}:{:02}", minutes, seconds) }
+}
+
+// Rejects audio over `MAX_AUDIO_MB` with a message saying by how much.
+fn check_audio_size(len: usize) -> Result<(), String> {
+    // Validated in main, so this only falls back if called outside the server
+    let max_audio_bytes = max_audio_bytes().unwrap_or(usize::MAX);
+    if len > max_audio_bytes {
+        return Err(format!("Audio is {} bytes; the limit is {} bytes", len, max_audio_bytes));
+    }
+    Ok(())
+}
+
+// Reads a client-sent duration as whole seconds: `"SS"`, `"M:SS"` or `"H:MM:SS"`, where the
+// seconds may have a fraction. Anything else is None, as is a length that doesn't fit an i32.
+pub(crate) fn parse_duration_seconds(duration: &str) -> Option<i32> {
+    let parts: Vec<&str> = duration.trim().split(':').collect();
+    let (seconds, units) = parts.split_last()?;
+    if units.len() > 2 {
+        return None;
+    }
+    if seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let mut total = seconds.parse::<f64>().ok()?.round();
+    for (unit, scale) in units.iter().rev().zip([60.0, 3600.0]) {
+        if unit.is_empty() || !unit.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        total += unit.parse::<f64>().ok()? * scale;
+    }
+    if total > i32::MAX as f64 { None } else { Some(total as i32) }
+}
+
+// Trims tags, drops blanks and removes duplicates while keeping the caller's order.
+pub(crate) fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !names.iter().any(|n| n == tag) {
+            names.push(tag.to_string());
+        }
+    }
+    names
+}
+
+/// Inserts a new memo with its tags. Returning early drops the transaction, so the memo and its
+/// tags are saved together or not at all.
+async fn insert_with_tags(db: &DatabaseConnection, memo: voice_memos1::ActiveModel, user_id: Uuid, tag_names: &[String]) -> Result<voice_memos1::Model, DbErr> {
+    let txn = db.begin().await?;
+    let saved = memo.insert(&txn).await?;
+    if !tag_names.is_empty() {
+        sync_memo_tags(&txn, user_id, saved.id, tag_names).await?;
+    }
+    txn.commit().await?;
+    Ok(saved)
+}
+
+// Replaces the memo's rows in memo_tags, creating any tag the user doesn't have yet.
+pub(crate) async fn sync_memo_tags<C: ConnectionTrait>(db: &C, user_id: Uuid, memo_id: Uuid, names: &[String]) -> Result<(), DbErr> {
+    memo_tags::Entity::delete_many()
+        .filter(memo_tags::Column::MemoId.eq(memo_id))
+        .exec(db)
+        .await?;
+
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    tags::Entity::insert_many(names.iter().map(|name| tags::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        name: Set(name.clone()),
+        created_at: Set(now),
+    }))
+    .on_conflict(OnConflict::columns([tags::Column::UserId, tags::Column::Name]).do_nothing().to_owned())
+    .do_nothing()
+    .exec(db)
+    .await?;
+
+    let tag_rows = tags::Entity::find()
+        .filter(tags::Column::UserId.eq(user_id))
+        .filter(tags::Column::Name.is_in(names.iter().cloned()))
+        .all(db)
+        .await?;
+
+    memo_tags::Entity::insert_many(tag_rows.into_iter().map(|tag| memo_tags::ActiveModel {
+        memo_id: Set(memo_id),
+        tag_id: Set(tag.id),
+    }))
+    .exec_without_returning(db)
+    .await?;
+
+    Ok(())
+}
+
+// The filters shared by the list and search endpoints.
+struct ListFilters {
+    tags: Option<TagFilter>,
+    created_from: Option<NaiveDateTime>,
+    created_to: Option<NaiveDateTime>,
+}
+
+impl ListFilters {
+    fn parse(tags: Option<&str>, tag_match: Option<TagMatch>, from: Option<&str>, to: Option<&str>) -> Result<ListFilters, Json<ErrorBody>> {
+        let tags = TagFilter::parse(tags, tag_match).map_err(|message| ErrorBody::new("invalid_tags", message))?;
+        let created_from = parse_filter_timestamp("from", from).map_err(|message| ErrorBody::new("invalid_date_range", message))?;
+        let created_to = parse_filter_timestamp("to", to).map_err(|message| ErrorBody::new("invalid_date_range", message))?;
+        if let (Some(from), Some(to)) = (created_from, created_to)
+            && from > to
+        {
+            return Err(ErrorBody::new("invalid_date_range", "from must not be later than to"));
+        }
+        Ok(ListFilters { tags, created_from, created_to })
+    }
+
+    // Both bounds are inclusive
+    fn apply(&self, mut query: Select<voice_memos1::Entity>, user_id: Uuid) -> Select<voice_memos1::Entity> {
+        if let Some(tags) = &self.tags {
+            query = tags.apply(query, user_id);
+        }
+        if let Some(from) = self.created_from {
+            query = query.filter(voice_memos1::Column::CreatedAt.gte(from));
+        }
+        if let Some(to) = self.created_to {
+            query = query.filter(voice_memos1::Column::CreatedAt.lte(to));
+        }
+        query
+    }
+}
+
+// An RFC 3339 timestamp as stored (naive UTC). One without an offset is taken to be UTC already.
+fn parse_filter_timestamp(name: &str, value: Option<&str>) -> Result<Option<NaiveDateTime>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(at.naive_utc()));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(Some)
+        .ok_or_else(|| format!("{} must be an RFC 3339 timestamp such as 2025-09-01T00:00:00Z, got {:?}", name, value))
+}
+
+// The `tags` and `match` query parameters.
+struct TagFilter {
+    tags: Vec<String>,
+    mode: TagMatch,
+}
+
+impl TagFilter {
+    /// `None` when no tags are given. The list is parsed as one CSV record, so quoted tags can contain commas.
+    fn parse(tags: Option<&str>, mode: Option<TagMatch>) -> Result<Option<TagFilter>, String> {
+        let Some(raw) = tags.filter(|raw| !raw.trim().is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).trim(csv::Trim::All).from_reader(raw.as_bytes());
+        let mut names = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| format!("tags is not a valid comma-separated list: {}", e))?;
+            names.extend(record.iter().map(str::to_string));
+        }
+        let tags = normalize_tags(&names);
+        if tags.len() > MAX_TAG_FILTERS {
+            return Err(format!("At most {} tags can be filtered on", MAX_TAG_FILTERS));
+        }
+        if tags.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(TagFilter { tags, mode: mode.unwrap_or_default() }))
+    }
+
+    // Through memo_tags, so a tag only matches its exact name (`work` never matches `workout`)
+    fn apply(&self, query: Select<voice_memos1::Entity>, user_id: Uuid) -> Select<voice_memos1::Entity> {
+        match self.mode {
+            TagMatch::Any => query.filter(voice_memos1::Column::Id.in_subquery(memo_ids_with_any_tag(user_id, &self.tags))),
+            TagMatch::All => self
+                .tags
+                .iter()
+                .fold(query, |query, tag| query.filter(voice_memos1::Column::Id.in_subquery(memo_ids_with_tag(user_id, tag)))),
+        }
+    }
+}
+
+fn memo_ids_with_any_tag(user_id: Uuid, tags: &[String]) -> sea_orm::sea_query::SelectStatement {
+    SubQuery::select()
+        .column((memo_tags::Entity, memo_tags::Column::MemoId))
+        .from(memo_tags::Entity)
+        .inner_join(
+            tags::Entity,
+            Expr::col((tags::Entity, tags::Column::Id)).equals((memo_tags::Entity, memo_tags::Column::TagId)),
+        )
+        .and_where(Expr::col((tags::Entity, tags::Column::UserId)).eq(user_id))
+        .and_where(Expr::col((tags::Entity, tags::Column::Name)).is_in(tags.iter().cloned()))
+        .to_owned()
+}
+
+// Ids of the user's memos carrying exactly this tag name.
+pub(crate) fn memo_ids_with_tag(user_id: Uuid, tag: &str) -> sea_orm::sea_query::SelectStatement {
+    SubQuery::select()
+        .column((memo_tags::Entity, memo_tags::Column::MemoId))
+        .from(memo_tags::Entity)
+        .inner_join(
+            tags::Entity,
+            Expr::col((tags::Entity, tags::Column::Id)).equals((memo_tags::Entity, memo_tags::Column::TagId)),
+        )
+        .and_where(Expr::col((tags::Entity, tags::Column::UserId)).eq(user_id))
+        .and_where(Expr::col((tags::Entity, tags::Column::Name)).eq(tag))
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::api::auth::AuthedUser;
+    use poem::endpoint::BoxEndpoint;
+    use poem::test::TestClient;
+
+    use crate::test_support::{assert_golden, bearer, login, new_memo, normalize_response, seed_user, test_app, test_db};
+
+    #[tokio::test]
+    async fn trash_lists_pages_of_memos_without_their_audio() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let deleted_at = Utc::now().naive_utc();
+        for title in ["first", "second", "third"] {
+            let mut memo = new_memo(&user, title);
+            memo.audio_blob = Set(Some(vec![0; 4096]));
+            memo.deleted_at = Set(Some(deleted_at));
+            memo.insert(&db).await.unwrap();
+        }
+        new_memo(&user, "kept").insert(&db).await.unwrap();
+
+        let resp = client
+            .get("/api/trash")
+            .query("page_size", &2)
+            .header("Authorization", bearer(&token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let body = resp.json().await;
+        let page = body.value().object();
+        page.get("total").assert_i64(3);
+        page.get("page_size").assert_i64(2);
+        let items = page.get("items").object_array();
+        assert_eq!(items.len(), 2);
+        for item in items {
+            item.get("has_audio").assert_bool(true);
+            assert!(item.get_opt("audio_blob").is_none());
+            item.get("deleted_at").string();
+        }
+    }
+
+    #[tokio::test]
+    async fn conflicts_and_restores_answer_without_the_audio() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let mut memo = new_memo(&user, "recorded");
+        memo.audio_blob = Set(Some(vec![0; 4096]));
+        memo.version = Set(3);
+        let memo = memo.insert(&db).await.unwrap();
+
+        let resp = client
+            .patch(format!("/api/update_memo/{}", memo.id))
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "version": 2, "title": "stale" }))
+            .send()
+            .await;
+        resp.assert_status(poem::http::StatusCode::CONFLICT);
+        let body = resp.json().await;
+        let current = body.value().object().get("current").object();
+        current.get("version").assert_i64(3);
+        current.get("has_audio").assert_bool(true);
+        assert!(current.get_opt("audio_blob").is_none());
+
+        let mut trashed: voice_memos1::ActiveModel = memo.into();
+        trashed.deleted_at = Set(Some(Utc::now().naive_utc()));
+        let memo = trashed.update(&db).await.unwrap();
+
+        let resp = client
+            .post(format!("/api/restore_memo/{}", memo.id))
+            .header("Authorization", bearer(&token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let body = resp.json().await;
+        let restored = body.value().object();
+        restored.get("id").assert_string(&memo.id.to_string());
+        restored.get("has_audio").assert_bool(true);
+        assert!(restored.get_opt("audio_blob").is_none());
+        assert!(restored.get_opt("deleted_at").is_none());
+    }
+
+    #[tokio::test]
+    async fn quick_capture_stays_within_two_statements() {
+        let Some(mut db) = test_db().await else { return };
+        let user = seed_user(&db).await;
+
+        // Counts what the handler itself runs; authentication happens before it and isn't part of the budget
+        let statements = Arc::new(Mutex::new(Vec::new()));
+        let recorded = statements.clone();
+        db.set_metric_callback(move |info| recorded.lock().unwrap().push(info.statement.sql.clone()));
+
+        let started = Instant::now();
+        let resp = MemoApi
+            .quick_capture(
+                MemosWriteAuth(AuthedUser(user.clone())),
+                Data(&db),
+                Data(&Crypto::with_key([7; 32])),
+                Query(None),
+                Json(QuickCapturePayload { text: "Buy oat milk".to_string(), tags: None }),
+            )
+            .await;
+        let elapsed = started.elapsed();
+
+        let QuickCaptureApiResponse::Ok(Json(saved)) = resp else { panic!("quick capture failed") };
+        let statements = statements.lock().unwrap().clone();
+        assert!(statements.len() <= 2, "quick capture ran {} statements: {:?}", statements.len(), statements);
+        assert!(elapsed < Duration::from_millis(500), "quick capture took {:?}", elapsed);
+
+        let memo = voice_memos1::Entity::find_by_id(Uuid::parse_str(&saved.id).unwrap()).one(&db).await.unwrap().unwrap();
+        assert_eq!(memo.user_id, user.id);
+        assert_eq!(memo.transcript.as_deref(), Some("Buy oat milk"));
+        assert_eq!(memo.duration_seconds, Some(0));
+        assert!(memo.title.starts_with("Quick note · "));
+    }
+
+    #[tokio::test]
+    async fn export_notes_which_sections_are_ai_generated_after_a_manual_edit() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        // What the pipeline leaves behind: a generated transcript and summary
+        let mut memo = new_memo(&user, "Standup");
+        memo.transcript = Set(Some("we ship on friday".to_string()));
+        memo.transcript_ai_generated = Set(true);
+        memo.summary = Set(Some("Shipping Friday.".to_string()));
+        memo.summary_ai_generated = Set(true);
+        let memo = memo.insert(&db).await.unwrap();
+
+        client
+            .patch(format!("/api/update_memo/{}", memo.id))
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "version": memo.version, "summary": "Shipping Monday instead." }))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let export = |exclude: bool| {
+            client
+                .get(format!("/api/memo/{}/export", memo.id))
+                .query("exclude_ai_content", &exclude)
+                .header("Authorization", bearer(&token))
+                .send()
+        };
+
+        let resp = export(false).await;
+        resp.assert_status_is_ok();
+        let document = resp.0.into_body().into_string().await.unwrap();
+        assert!(document.contains("## Transcript\n\n_AI-generated, not edited since_\n\nwe ship on friday\n"), "{}", document);
+        assert!(document.contains("## Summary\n\n_Written or edited by hand_\n\nShipping Monday instead.\n"), "{}", document);
+
+        let resp = export(true).await;
+        resp.assert_status_is_ok();
+        let document = resp.0.into_body().into_string().await.unwrap();
+        assert!(!document.contains("we ship on friday"), "{}", document);
+        assert!(!document.contains("## Transcript"), "{}", document);
+        assert!(document.contains("Shipping Monday instead."), "{}", document);
+    }
+
+    #[test]
+    fn byte_range_reads_open_ended_suffix_and_overlong_ranges() {
+        let len = 4000;
+
+        assert_eq!(byte_range("bytes=1000-", len), ByteRange::Partial(1000, 3999));
+        assert_eq!(byte_range("bytes=-500", len), ByteRange::Partial(3500, 3999));
+        assert_eq!(byte_range("bytes=0-0", len), ByteRange::Partial(0, 0));
+        assert_eq!(byte_range("bytes=0-499, 1000-", len), ByteRange::Partial(0, 499));
+
+        // Past the end: the end is clamped, a suffix longer than the body is all of it, a start past it can't be met
+        assert_eq!(byte_range("bytes=3000-9999", len), ByteRange::Partial(3000, 3999));
+        assert_eq!(byte_range("bytes=-9999", len), ByteRange::Partial(0, 3999));
+        assert_eq!(byte_range("bytes=4000-", len), ByteRange::Unsatisfiable);
+        assert_eq!(byte_range("bytes=5000-6000", len), ByteRange::Unsatisfiable);
+        assert_eq!(byte_range("bytes=-0", len), ByteRange::Unsatisfiable);
+        assert_eq!(byte_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+
+        // Anything unreadable gets the whole body
+        assert_eq!(byte_range("items=0-10", len), ByteRange::Full);
+        assert_eq!(byte_range("bytes=500-100", len), ByteRange::Full);
+        assert_eq!(byte_range("bytes=abc-", len), ByteRange::Full);
+        assert_eq!(byte_range("bytes=100", len), ByteRange::Full);
+    }
+
+    async fn search(client: &TestClient<BoxEndpoint<'static>>, token: &str, path: &str, q: &str) -> Vec<(String, Vec<String>)> {
+        let resp = client.get(path).query("q", &q).header("Authorization", bearer(token)).send().await;
+        resp.assert_status_is_ok();
+        let body = resp.json().await;
+        body.value()
+            .object()
+            .get("items")
+            .object_array()
+            .iter()
+            .map(|hit| {
+                let fields = hit.get("matched_fields").array().iter().map(|field| field.string().to_string()).collect();
+                (hit.get("title").string().to_string(), fields)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_matches_accented_text_and_summary_only_hits() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let mut cafe = new_memo(&user, "Réunion au Café");
+        cafe.transcript = Set(Some("On a parlé de la crème brûlée".to_string()));
+        cafe.insert(&db).await.unwrap();
+        let mut planning = new_memo(&user, "Monday call");
+        planning.transcript = Set(Some("we went through the numbers".to_string()));
+        planning.summary = Set(Some("Quarterly budget review".to_string()));
+        planning.insert(&db).await.unwrap();
+        new_memo(&user, "Cafe without an accent").insert(&db).await.unwrap();
+
+        // Accents are kept, case is not
+        assert_eq!(search(&client, &token, "/api/search_memos", "café").await, [("Réunion au Café".to_string(), vec!["title".to_string()])]);
+        assert_eq!(search(&client, &token, "/api/search_memos", "CRÈME BRÛLÉE").await, [("Réunion au Café".to_string(), vec!["transcript".to_string()])]);
+        assert_eq!(search(&client, &token, "/api/search_memos", "réunion").await.len(), 1);
+
+        assert_eq!(
+            search(&client, &token, "/api/search_memos", "quarterly BUDGET").await,
+            [("Monday call".to_string(), vec!["summary".to_string()])]
+        );
+
+        // `%` and `_` are plain characters
+        new_memo(&user, "Sprint 100% done").insert(&db).await.unwrap();
+        assert_eq!(search(&client, &token, "/api/search_memos", "0% d").await.len(), 1);
+        assert!(search(&client, &token, "/api/search_memos", "_").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn full_text_search_matches_stems_and_only_the_callers_memos() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let other = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        // A word that only these memos contain, so memos of other tests can't interfere
+        let marker = format!("zq{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let mut mine = new_memo(&user, "Morning run");
+        mine.transcript = Set(Some(format!("I run by the river every day {}", marker)));
+        mine.insert(&db).await.unwrap();
+        let mut theirs = new_memo(&other, "Their run");
+        theirs.transcript = Set(Some(format!("They run too {}", marker)));
+        theirs.insert(&db).await.unwrap();
+
+        let hits = search(&client, &token, "/api/search_memos/fts", &format!("running {}", marker)).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "Morning run");
+        assert!(hits[0].1.contains(&"transcript".to_string()));
+
+        // The other user's memo matches just as well, but never shows up
+        let other_token = login(&client, &other).await;
+        let hits = search(&client, &other_token, "/api/search_memos/fts", &format!("runs {}", marker)).await;
+        assert_eq!(hits.iter().map(|(title, _)| title.as_str()).collect::<Vec<_>>(), ["Their run"]);
+        assert!(search(&client, &other_token, "/api/search_memos/fts", &format!("river {}", marker)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn date_range_includes_memos_created_exactly_at_its_ends() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let at = |rfc3339: &str| DateTime::parse_from_rfc3339(rfc3339).unwrap().naive_utc();
+        for (title, created_at) in [
+            ("before from", "2024-05-31T23:59:59.999999Z"),
+            ("at from", "2024-06-01T00:00:00Z"),
+            ("at to", "2024-06-07T23:59:59.500Z"),
+            ("after to", "2024-06-07T23:59:59.500001Z"),
+        ] {
+            let mut memo = new_memo(&user, &format!("Range {}", title));
+            memo.created_at = Set(at(created_at));
+            memo.insert(&db).await.unwrap();
+        }
+
+        // The same instants as offsets, and `to` without one, which is read as UTC
+        let (from, to) = ("2024-06-01T02:00:00+02:00", "2024-06-07T23:59:59.500");
+        let resp = client
+            .get("/api/get_memos")
+            .query("from", &from)
+            .query("to", &to)
+            .header("Authorization", bearer(&token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let body = resp.json().await;
+        let titles: Vec<String> = body.value().object().get("items").object_array().iter().map(|item| item.get("title").string().to_string()).collect();
+        assert_eq!(titles, ["Range at to", "Range at from"]);
+
+        let resp = client
+            .get("/api/search_memos")
+            .query("q", &"Range")
+            .query("from", &from)
+            .query("to", &to)
+            .header("Authorization", bearer(&token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("total").assert_i64(2);
+    }
+
+    #[tokio::test]
+    async fn memo_list_stays_small_for_a_memo_with_audio() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        // About two minutes of 16 kHz mono WAV
+        let mut memo = new_memo(&user, "Long recording");
+        memo.audio_blob = Set(Some(vec![0x7f; 4 * 1024 * 1024]));
+        memo.transcript = Set(Some("A short transcript".to_string()));
+        memo.insert(&db).await.unwrap();
+
+        let resp = client.get("/api/get_memos").header("Authorization", bearer(&token)).send().await;
+        resp.assert_status_is_ok();
+        let body = resp.0.into_body().into_bytes().await.unwrap();
+        assert!(body.len() < 4 * 1024, "list payload is {} bytes", body.len());
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let item = &body["items"][0];
+        assert_eq!(item["title"], "Long recording");
+        assert_eq!(item["has_audio"], true);
+        assert!(item.get("audio_blob").is_none());
+    }
+
+    // A small library whose responses are pinned by the golden files: the user, the memo with every
+    // field set, and the IDs to normalize
+    async fn seed_library(db: &DatabaseConnection) -> (entity::users::Model, Uuid, Vec<(String, &'static str)>) {
+        let user = seed_user(db).await;
+        let recorded = Utc::now().naive_utc() - chrono::Duration::days(1);
+
+        let mut full = new_memo(&user, "Standup notes");
+        full.audio_blob = Set(Some(vec![1, 2, 3]));
+        full.transcript = Set(Some("We shipped the importer and start on search".to_string()));
+        full.translate = Set(Some("Wir haben den Importer ausgeliefert".to_string()));
+        full.summary = Set(Some("Importer shipped; search is next".to_string()));
+        full.summary_ai_generated = Set(true);
+        full.tags = Set(Some(r#"["standup","work"]"#.to_string()));
+        full.is_favorite = Set(true);
+        let full = full.insert(db).await.unwrap();
+        let mut bare = new_memo(&user, "Untitled");
+        bare.created_at = Set(recorded);
+        bare.updated_at = Set(recorded);
+        let bare = bare.insert(db).await.unwrap();
+
+        let ids = vec![
+            (user.id.to_string(), "<user_id>"),
+            (full.id.to_string(), "<full_memo_id>"),
+            (bare.id.to_string(), "<bare_memo_id>"),
+        ];
+        (user, full.id, ids)
+    }
+
+    #[tokio::test]
+    async fn memo_responses_match_the_golden_files() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let (user, full_id, ids) = seed_library(&db).await;
+        let token = login(&client, &user).await;
+
+        let resp = client.get("/api/get_memos").header("Authorization", bearer(&token)).send().await;
+        resp.assert_status_is_ok();
+        let mut list: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await.unwrap()).unwrap();
+        normalize_response(&mut list, &ids);
+        assert_golden("get_memos.json", &list);
+
+        let resp = client.get(format!("/api/get_memo/{}", full_id)).header("Authorization", bearer(&token)).send().await;
+        resp.assert_status_is_ok();
+        let mut memo: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await.unwrap()).unwrap();
+        normalize_response(&mut memo, &ids);
+        assert_golden("get_memo_by_id.json", &memo);
+    }
+
+    fn parsed_tags(raw: &str) -> Vec<String> {
+        TagFilter::parse(Some(raw), None).unwrap().expect("tags were given").tags
+    }
+
+    #[test]
+    fn tag_filter_parses_quoted_commas_quotes_and_unicode() {
+        let cases: &[(&str, &[&str])] = &[
+            ("work, home ,work", &["work", "home"]),
+            (r#""a,b",c"#, &["a,b", "c"]),
+            (r#"c,"a,b""#, &["c", "a,b"]),
+            // A quote inside a quoted tag is doubled, as in CSV
+            (r#""say ""hi""",x"#, &[r#"say "hi""#, "x"]),
+            (r#""1,000""#, &["1,000"]),
+            ("café,日本語,🙂", &["café", "日本語", "🙂"]),
+            (r#""naïve, größer",Ünïcödé"#, &["naïve, größer", "Ünïcödé"]),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parsed_tags(raw), *expected, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn tag_filter_without_tags_is_none() {
+        for raw in [None, Some(""), Some("   "), Some(" , ,"), Some(r#""","  ""#)] {
+            assert!(TagFilter::parse(raw, Some(TagMatch::All)).unwrap().is_none(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn tag_filter_keeps_the_match_mode_and_limits_the_tags() {
+        assert_eq!(TagFilter::parse(Some("a"), None).unwrap().unwrap().mode, TagMatch::Any);
+        assert_eq!(TagFilter::parse(Some("a"), Some(TagMatch::All)).unwrap().unwrap().mode, TagMatch::All);
+
+        let too_many = (0..=MAX_TAG_FILTERS).map(|i| format!("tag{}", i)).collect::<Vec<_>>().join(",");
+        assert!(TagFilter::parse(Some(&too_many), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn tag_filter_matches_tags_with_commas_quotes_and_unicode() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let library: &[(&str, &[&str])] = &[
+            ("comma", &["a,b", "café"]),
+            ("split", &["a", "b"]),
+            ("quoted", &[r#"say "hi""#, "🙂"]),
+            ("plain", &["cafe"]),
+        ];
+        for (title, tags) in library {
+            let memo = new_memo(&user, title).insert(&db).await.unwrap();
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            sync_memo_tags(&db, user.id, memo.id, &tags).await.unwrap();
+        }
+
+        let cases: &[(&str, &str, &[&str])] = &[
+            (r#""a,b""#, "any", &["comma"]),
+            ("a,b", "any", &["split"]),
+            (r#""a,b",café"#, "all", &["comma"]),
+            ("café,🙂", "any", &["comma", "quoted"]),
+            (r#""say ""hi""""#, "any", &["quoted"]),
+            ("cafe", "any", &["plain"]),
+        ];
+        for (tags, tag_match, expected) in cases {
+            let resp = client
+                .get("/api/get_memos")
+                .query("tags", tags)
+                .query("match", tag_match)
+                .query("sort", &"title")
+                .header("Authorization", bearer(&token))
+                .send()
+                .await;
+            resp.assert_status_is_ok();
+            let body = resp.json().await;
+            let titles: Vec<String> = body
+                .value()
+                .object()
+                .get("items")
+                .object_array()
+                .iter()
+                .map(|item| item.get("title").string().to_string())
+                .collect();
+            assert_eq!(titles, *expected, "tags={} match={}", tags, tag_match);
+        }
+    }
+
+    #[tokio::test]
+    async fn equal_sort_keys_fall_back_to_the_id_across_pages() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let recorded = Utc::now().naive_utc() - chrono::Duration::hours(1);
+        let mut ids = Vec::new();
+        for _ in 0..5 {
+            let mut memo = new_memo(&user, "Same title");
+            memo.created_at = Set(recorded);
+            memo.updated_at = Set(recorded);
+            ids.push(memo.insert(&db).await.unwrap().id);
+        }
+        ids.sort();
+        let ascending: Vec<String> = ids.iter().map(Uuid::to_string).collect();
+        let descending: Vec<String> = ascending.iter().rev().cloned().collect();
+
+        let cases: &[(&str, Option<&str>, &[String])] = &[
+            ("created_at", None, &descending),
+            ("created_at", Some("asc"), &ascending),
+            ("updated_at", None, &descending),
+            // Equal titles go newest first, and equal timestamps by descending id
+            ("title", None, &descending),
+        ];
+        for (sort, order, expected) in cases {
+            let mut seen = Vec::new();
+            for page in 1..=3 {
+                let mut request = client
+                    .get("/api/get_memos")
+                    .query("sort", sort)
+                    .query("page", &page)
+                    .query("page_size", &2)
+                    .header("Authorization", bearer(&token));
+                if let Some(order) = order {
+                    request = request.query("order", order);
+                }
+                let resp = request.send().await;
+                resp.assert_status_is_ok();
+                let body = resp.json().await;
+                seen.extend(
+                    body.value().object().get("items").object_array().iter().map(|item| item.get("id").string().to_string()),
+                );
+            }
+            assert_eq!(seen, *expected, "sort={} order={:?}", sort, order);
+        }
+    }
 }
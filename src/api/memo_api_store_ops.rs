@@ -1,16 +1,20 @@
-use chrono::Utc;
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+use chrono::{Duration, Utc};
 use poem::web::Data;
-use poem_openapi::{Object, OpenApi, SecurityScheme, auth::Bearer, payload::Json, ApiResponse};
-use sea_orm::{DatabaseConnection, Set, entity::*, query::*, ActiveModelTrait};
+use poem_openapi::{Object, OpenApi, param::{Path, Query}, payload::Json, ApiResponse};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait, entity::*, query::*, sea_query::{Expr, OnConflict}, ActiveModelTrait};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::api::crypto::{encrypt, decrypt};
+use crate::api::crypto::Crypto;
+use crate::api::error::ErrorBody;
+use crate::api::auth::SessionAuth;
 
-use entity::{helper_app, users};
+use entity::{deleted_keys, helper_app};
 
 
-const JWT_SECRET: &str = "point";
+pub const PROVIDER_GEMINI: &str = "gemini";
+pub const PROVIDER_ELEVENLABS: &str = "elevenlabs";
+// How long a deleted key can still be restored
+const KEY_UNDO_WINDOW_DAYS: i64 = 7;
 
 
 #[derive(Debug, Deserialize, Serialize, Object)]
@@ -19,6 +23,15 @@ pub struct ApiKeyPayload {
     pub elevenlabs_api_key: Option<String>,
 }
 
+// "****" and the last 4 characters; a key too short to hide most of it is masked entirely.
+fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    format!("****{}", chars[chars.len() - 4..].iter().collect::<String>())
+}
+
 #[derive(Debug, Serialize, Object)]
 pub struct ApiKeyResponse {
     pub gemini_api_key: Option<String>,
@@ -50,21 +63,17 @@ enum GetApiResponse {
     #[oai(status = 200)]
     Ok(Json<ApiKeyResponse>),
     #[oai(status = 404)]
-    NotFound(Json<ApiKeyResponse>),
-    #[oai(status = 401)]
-    Unauthorized(Json<ApiKeyResponse>),
+    NotFound(Json<ErrorBody>),
     #[oai(status = 500)]
-    InternalServerError(Json<ApiKeyResponse>),
+    InternalServerError(Json<ErrorBody>),
 }
 
 #[derive(ApiResponse)]
 enum SaveApiResponse {
     #[oai(status = 200)]
     Ok(Json<ApiKeyResponse>),
-    #[oai(status = 401)]
-    Unauthorized(Json<ApiKeyResponse>),
     #[oai(status = 500)]
-    InternalServerError(Json<ApiKeyResponse>),
+    InternalServerError(Json<ErrorBody>),
 }
 
 // NEW: API Responses for helper status
@@ -72,20 +81,16 @@ enum SaveApiResponse {
 enum HelperStatusUpdateResponse {
     #[oai(status = 200)]
     Ok(Json<HelperStatusResponse>),
-    #[oai(status = 401)]
-    Unauthorized,
     #[oai(status = 500)]
-    InternalServerError(Json<String>),
+    InternalServerError(Json<ErrorBody>),
 }
 
 #[derive(ApiResponse)]
 enum HelperStatusGetResponse {
     #[oai(status = 200)]
     Ok(Json<HelperStatusResponse>),
-    #[oai(status = 401)]
-    Unauthorized,
     #[oai(status = 500)]
-    InternalServerError(Json<String>),
+    InternalServerError(Json<ErrorBody>),
 }
 
 // NEW: API Response for delete operations
@@ -93,69 +98,23 @@ enum HelperStatusGetResponse {
 enum DeleteApiResponse {
     #[oai(status = 200)]
     Ok(Json<DeleteResponse>),
-    #[oai(status = 401)]
-    Unauthorized,
     #[oai(status = 404)]
-    NotFound(Json<String>),
+    NotFound(Json<ErrorBody>),
     #[oai(status = 500)]
-    InternalServerError(Json<String>),
-}
-
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub email: String,
-    pub exp: usize,
+    InternalServerError(Json<ErrorBody>),
 }
 
-
-#[derive(SecurityScheme)]
-#[oai(ty = "bearer", bearer_format = "JWT")]
-struct ApiKeyAuth(Bearer);
-
-
-pub async fn get_user_from_token(
-    token: &str,
-    db: &DatabaseConnection
-) -> Result<users::Model, Json<ApiKeyResponse>> {
-    // 1. Validate the JWT token
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-        &Validation::new(Algorithm::HS256),
-    )
-    .map(|data| data.claims)
-    .map_err(|_| Json(ApiKeyResponse {
-        gemini_api_key: None,
-        elevenlabs_api_key: None,
-        message: "Invalid or expired token".to_string(),
-    }))?;
-
-    // 2. Parse the user ID from the token's subject
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| Json(ApiKeyResponse {
-        gemini_api_key: None,
-        elevenlabs_api_key: None,
-        message: "Invalid user ID format in token".to_string(),
-    }))?;
-
-    // 3. Find the user in the database
-    users::Entity::find_by_id(user_id)
-        .one(db)
-        .await
-        .map_err(|e| {
-            tracing::error!("Database error while fetching user: {:?}", e);
-            Json(ApiKeyResponse {
-                gemini_api_key: None,
-                elevenlabs_api_key: None,
-                message: "Failed to verify user due to a database error".to_string(),
-            })
-        })?
-        .ok_or_else(|| Json(ApiKeyResponse {
-            gemini_api_key: None,
-            elevenlabs_api_key: None,
-            message: format!("User {} not found in database", user_id),
-        }))
+// API Response for restoring a deleted key
+#[derive(ApiResponse)]
+enum UndoDeleteApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<DeleteResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
 }
 
 
@@ -167,82 +126,52 @@ impl Api {
     #[oai(path = "/api_keys/save", method = "post")]
     async fn save_api_keys(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
         Json(payload): Json<ApiKeyPayload>,
     ) -> SaveApiResponse {
       
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(error_response) => return SaveApiResponse::Unauthorized(error_response),
-        };
+        let user = auth.0;
 
         
-        let encrypted_gemini = match payload.gemini_api_key.as_deref().map(encrypt).transpose() {
+        let encrypted_gemini = match payload.gemini_api_key.as_deref().map(|k| crypto.encrypt(k)).transpose() {
             Ok(key) => key,
             Err(e) => {
                 tracing::error!("Failed to encrypt Gemini key: {}", e);
-                return SaveApiResponse::InternalServerError(Json(ApiKeyResponse {
-                    gemini_api_key: None, elevenlabs_api_key: None,
-                    message: "Failed to process Gemini API key.".to_string(),
-                }));
+                return SaveApiResponse::InternalServerError(ErrorBody::new("internal_error", "Failed to process Gemini API key."));
             }
         };
-        let encrypted_elevenlabs = match payload.elevenlabs_api_key.as_deref().map(encrypt).transpose() {
+        let encrypted_elevenlabs = match payload.elevenlabs_api_key.as_deref().map(|k| crypto.encrypt(k)).transpose() {
             Ok(key) => key,
             Err(e) => {
                 tracing::error!("Failed to encrypt ElevenLabs key: {}", e);
-                return SaveApiResponse::InternalServerError(Json(ApiKeyResponse {
-                    gemini_api_key: None, elevenlabs_api_key: None,
-                    message: "Failed to process ElevenLabs API key.".to_string(),
-                }));
+                return SaveApiResponse::InternalServerError(ErrorBody::new("internal_error", "Failed to process ElevenLabs API key."));
             }
         };
 
-        
-        let existing_record = match helper_app::Entity::find()
-            .filter(helper_app::Column::UserId.eq(user.id))
-            .one(db.0)
-            .await
-        {
-            Ok(record) => record,
-            Err(e) => {
-                tracing::error!("DB error finding API keys: {:?}", e);
-                return SaveApiResponse::InternalServerError(Json(ApiKeyResponse {
-                    gemini_api_key: None, elevenlabs_api_key: None,
-                    message: "Failed to check for existing API keys.".to_string(),
-                }));
-            }
+        // Single upsert keyed on the unique user_id, so concurrent saves can't create duplicate rows.
+        // A key left out of the payload keeps its stored value.
+        let new_model = helper_app::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            gemini_key: Set(encrypted_gemini),
+            elevenlabs_key: Set(encrypted_elevenlabs),
+            action: Set("api_keys_save".to_string()),
+            timestamp: Set(Utc::now().naive_utc()),
+            helper_status: Set(false), // Default status
         };
 
-        
-        let result = match existing_record {
-            Some(model) => {
-                
-                let mut active_model: helper_app::ActiveModel = model.into();
-                if let Some(key) = encrypted_gemini {
-                    active_model.gemini_key = Set(Some(key));
-                }
-                if let Some(key) = encrypted_elevenlabs {
-                    active_model.elevenlabs_key = Set(Some(key));
-                }
-                active_model.timestamp = Set(Utc::now().naive_utc());
-                active_model.update(db.0).await
-            }
-            None => {
-                
-                let new_model = helper_app::ActiveModel {
-                    id: Set(Uuid::new_v4()),
-                    user_id: Set(user.id),
-                    gemini_key: Set(encrypted_gemini),
-                    elevenlabs_key: Set(encrypted_elevenlabs),
-                    action: Set("api_keys_save".to_string()),
-                    timestamp: Set(Utc::now().naive_utc()),
-                    helper_status: Set(false), // Default status
-                };
-                new_model.insert(db.0).await
-            }
-        };
+        let result = helper_app::Entity::insert(new_model)
+            .on_conflict(
+                OnConflict::column(helper_app::Column::UserId)
+                    .value(helper_app::Column::GeminiKey, Expr::cust(r#"COALESCE(EXCLUDED.gemini_key, "helperApp".gemini_key)"#))
+                    .value(helper_app::Column::ElevenlabsKey, Expr::cust(r#"COALESCE(EXCLUDED.elevenlabs_key, "helperApp".elevenlabs_key)"#))
+                    .update_column(helper_app::Column::Timestamp)
+                    .to_owned(),
+            )
+            .exec_without_returning(db.0)
+            .await;
 
         match result {
             Ok(_) => SaveApiResponse::Ok(Json(ApiKeyResponse {
@@ -253,10 +182,7 @@ impl Api {
             })),
             Err(e) => {
                 tracing::error!("Failed to save API keys to DB: {:?}", e);
-                SaveApiResponse::InternalServerError(Json(ApiKeyResponse {
-                    gemini_api_key: None, elevenlabs_api_key: None,
-                    message: "Failed to save API keys.".to_string(),
-                }))
+                SaveApiResponse::InternalServerError(ErrorBody::new("internal_error", "Failed to save API keys."))
             }
         }
     }
@@ -265,14 +191,14 @@ impl Api {
     #[oai(path = "/api_keys/get", method = "get")]
     async fn get_api_keys(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
+        crypto: Data<&Crypto>,
+        /// Return only the last 4 characters of each key, for showing which key is saved
+        masked: Query<Option<bool>>,
     ) -> GetApiResponse {
       
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(error_response) => return GetApiResponse::Unauthorized(error_response),
-        };
+        let user = auth.0;
 
         let keys_record = match helper_app::Entity::find()
             .filter(helper_app::Column::UserId.eq(user.id))
@@ -280,22 +206,21 @@ impl Api {
             .await
         {
             Ok(Some(record)) => record,
-            Ok(None) => return GetApiResponse::NotFound(Json(ApiKeyResponse {
-                gemini_api_key: None, elevenlabs_api_key: None,
-                message: "No API keys found for this user.".to_string(),
-            })),
+            Ok(None) => return GetApiResponse::NotFound(ErrorBody::new("not_found", "No API keys found for this user.")),
             Err(e) => {
                 tracing::error!("Failed to fetch API keys: {:?}", e);
-                return GetApiResponse::InternalServerError(Json(ApiKeyResponse {
-                    gemini_api_key: None, elevenlabs_api_key: None,
-                    message: "Failed to fetch API keys.".to_string(),
-                }));
+                return GetApiResponse::InternalServerError(ErrorBody::new("internal_error", "Failed to fetch API keys."));
             }
         };
 
         
-        let gemini_key = keys_record.gemini_key.as_deref().and_then(|k| decrypt(k).ok());
-        let elevenlabs_key = keys_record.elevenlabs_key.as_deref().and_then(|k| decrypt(k).ok());
+        let gemini_key = keys_record.gemini_key.as_deref().and_then(|k| crypto.decrypt(k).ok());
+        let elevenlabs_key = keys_record.elevenlabs_key.as_deref().and_then(|k| crypto.decrypt(k).ok());
+        let (gemini_key, elevenlabs_key) = if masked.0.unwrap_or(false) {
+            (gemini_key.as_deref().map(mask_key), elevenlabs_key.as_deref().map(mask_key))
+        } else {
+            (gemini_key, elevenlabs_key)
+        };
 
         GetApiResponse::Ok(Json(ApiKeyResponse {
             gemini_api_key: gemini_key,
@@ -308,13 +233,10 @@ impl Api {
     #[oai(path = "/api_keys/gemini", method = "delete")]
     async fn delete_gemini_key(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
     ) -> DeleteApiResponse {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(_) => return DeleteApiResponse::Unauthorized,
-        };
+        let user = auth.0;
 
         let existing_record = match helper_app::Entity::find()
             .filter(helper_app::Column::UserId.eq(user.id))
@@ -322,18 +244,15 @@ impl Api {
             .await
         {
             Ok(Some(record)) => record,
-            Ok(None) => return DeleteApiResponse::NotFound(Json("API key record not found for user.".to_string())),
-            Err(e) => return DeleteApiResponse::InternalServerError(Json(e.to_string())),
+            Ok(None) => return DeleteApiResponse::NotFound(ErrorBody::new("not_found", "API key record not found for user.")),
+            Err(e) => return DeleteApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch API key record", &e)),
         };
 
-        let mut active_model: helper_app::ActiveModel = existing_record.into();
-        active_model.gemini_key = Set(None);
-        
-        match active_model.update(db.0).await {
+        match delete_key(db.0, existing_record, PROVIDER_GEMINI).await {
             Ok(_) => DeleteApiResponse::Ok(Json(DeleteResponse {
-                message: "Gemini API key deleted successfully".to_string(),
+                message: format!("Gemini API key deleted successfully. It can be restored within {} days.", KEY_UNDO_WINDOW_DAYS),
             })),
-            Err(e) => DeleteApiResponse::InternalServerError(Json(e.to_string())),
+            Err(e) => DeleteApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete Gemini key", &e)),
         }
     }
 
@@ -341,13 +260,10 @@ impl Api {
     #[oai(path = "/api_keys/elevenlabs", method = "delete")]
     async fn delete_elevenlabs_key(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
     ) -> DeleteApiResponse {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(_) => return DeleteApiResponse::Unauthorized,
-        };
+        let user = auth.0;
 
         let existing_record = match helper_app::Entity::find()
             .filter(helper_app::Column::UserId.eq(user.id))
@@ -355,63 +271,72 @@ impl Api {
             .await
         {
             Ok(Some(record)) => record,
-            Ok(None) => return DeleteApiResponse::NotFound(Json("API key record not found for user.".to_string())),
-            Err(e) => return DeleteApiResponse::InternalServerError(Json(e.to_string())),
+            Ok(None) => return DeleteApiResponse::NotFound(ErrorBody::new("not_found", "API key record not found for user.")),
+            Err(e) => return DeleteApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch API key record", &e)),
         };
 
-        let mut active_model: helper_app::ActiveModel = existing_record.into();
-        active_model.elevenlabs_key = Set(None);
-        
-        match active_model.update(db.0).await {
+        match delete_key(db.0, existing_record, PROVIDER_ELEVENLABS).await {
             Ok(_) => DeleteApiResponse::Ok(Json(DeleteResponse {
-                message: "ElevenLabs API key deleted successfully".to_string(),
+                message: format!("ElevenLabs API key deleted successfully. It can be restored within {} days.", KEY_UNDO_WINDOW_DAYS),
             })),
-            Err(e) => DeleteApiResponse::InternalServerError(Json(e.to_string())),
+            Err(e) => DeleteApiResponse::InternalServerError(ErrorBody::from_db("Failed to delete ElevenLabs key", &e)),
         }
     }
 
+    // Restore a key deleted within the undo window
+    #[oai(path = "/api_keys/:provider/undo_delete", method = "post")]
+    async fn undo_delete_key(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(provider): Path<String>,
+    ) -> UndoDeleteApiResponse {
+        let user = auth.0;
+
+        if provider != PROVIDER_GEMINI && provider != PROVIDER_ELEVENLABS {
+            return UndoDeleteApiResponse::BadRequest(ErrorBody::new("unknown_provider", format!("Unknown provider: {}", provider)));
+        }
+
+        match restore_key(db.0, user.id, &provider).await {
+            Ok(RestoreOutcome::Restored) => {}
+            Ok(RestoreOutcome::NoTombstone) => return UndoDeleteApiResponse::NotFound(ErrorBody::new("not_found", "No recently deleted key to restore.")),
+            Ok(RestoreOutcome::NoRecord) => return UndoDeleteApiResponse::NotFound(ErrorBody::new("not_found", "API key record not found for user.")),
+            Err(e) => return UndoDeleteApiResponse::InternalServerError(ErrorBody::from_db("Failed to restore API key", &e)),
+        }
+
+        tracing::info!(user_id = %user.id, provider = %provider, "Restored deleted API key");
+
+        UndoDeleteApiResponse::Ok(Json(DeleteResponse {
+            message: format!("{} API key restored successfully", provider),
+        }))
+    }
+
     #[oai(path = "/helper/status", method = "post")]
     async fn update_helper_status(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
         Json(payload): Json<HelperStatusPayload>,
     ) -> HelperStatusUpdateResponse {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(_) => return HelperStatusUpdateResponse::Unauthorized,
+        let user = auth.0;
+
+        let new_model = helper_app::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            helper_status: Set(payload.status),
+            action: Set("helper_status_update".to_string()),
+            timestamp: Set(Utc::now().naive_utc()),
+            ..Default::default()
         };
 
-        let existing_record = match helper_app::Entity::find()
-            .filter(helper_app::Column::UserId.eq(user.id))
-            .one(db.0)
-            .await
-        {
-            Ok(record) => record,
-            Err(e) => {
-                tracing::error!("DB error finding helper_app record: {:?}", e);
-                return HelperStatusUpdateResponse::InternalServerError(Json(e.to_string()));
-            }
-        };
-
-        let result = match existing_record {
-            Some(model) => {
-                let mut active_model: helper_app::ActiveModel = model.into();
-                active_model.helper_status = Set(payload.status);
-                active_model.update(db.0).await
-            }
-            None => {
-                let new_model = helper_app::ActiveModel {
-                    id: Set(Uuid::new_v4()),
-                    user_id: Set(user.id),
-                    helper_status: Set(payload.status),
-                    action: Set("helper_status_update".to_string()),
-                    timestamp: Set(Utc::now().naive_utc()),
-                    ..Default::default()
-                };
-                new_model.insert(db.0).await
-            }
-        };
+        let result = helper_app::Entity::insert(new_model)
+            .on_conflict(
+                OnConflict::column(helper_app::Column::UserId)
+                    .update_column(helper_app::Column::HelperStatus)
+                    .to_owned(),
+            )
+            .exec_without_returning(db.0)
+            .await;
 
         match result {
             Ok(_) => HelperStatusUpdateResponse::Ok(Json(HelperStatusResponse {
@@ -419,8 +344,7 @@ impl Api {
                 message: "Helper status updated successfully".to_string(),
             })),
             Err(e) => {
-                tracing::error!("Failed to update helper status: {:?}", e);
-                HelperStatusUpdateResponse::InternalServerError(Json(e.to_string()))
+                HelperStatusUpdateResponse::InternalServerError(ErrorBody::from_db("Failed to update helper status", &e))
             }
         }
     }
@@ -428,13 +352,10 @@ impl Api {
     #[oai(path = "/helper/status", method = "get")]
     async fn get_helper_status(
         &self,
-        auth: ApiKeyAuth,
+        auth: SessionAuth,
         db: Data<&DatabaseConnection>,
     ) -> HelperStatusGetResponse {
-        let user = match get_user_from_token(&auth.0.token, db.0).await {
-            Ok(user_model) => user_model,
-            Err(_) => return HelperStatusGetResponse::Unauthorized,
-        };
+        let user = auth.0;
 
         match helper_app::Entity::find()
             .filter(helper_app::Column::UserId.eq(user.id))
@@ -455,9 +376,320 @@ impl Api {
                 }))
             }
             Err(e) => {
-                tracing::error!("Failed to fetch helper status: {:?}", e);
-                HelperStatusGetResponse::InternalServerError(Json(e.to_string()))
+                HelperStatusGetResponse::InternalServerError(ErrorBody::from_db("Failed to fetch helper status", &e))
             }
         }
     }
 }
+
+
+// Tombstones the provider's key and clears it in one transaction, so a failure in between neither
+// loses the key nor leaves a tombstone for a key that is still set.
+async fn delete_key(db: &DatabaseConnection, record: helper_app::Model, provider: &str) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    let ciphertext = if provider == PROVIDER_GEMINI { record.gemini_key.clone() } else { record.elevenlabs_key.clone() };
+    // Keep the ciphertext around so the deletion can be undone
+    if let Some(ciphertext) = ciphertext {
+        tombstone_key(&txn, record.user_id, provider, ciphertext).await?;
+    }
+
+    let mut active_model: helper_app::ActiveModel = record.into();
+    if provider == PROVIDER_GEMINI {
+        active_model.gemini_key = Set(None);
+    } else {
+        active_model.elevenlabs_key = Set(None);
+    }
+    active_model.update(&txn).await?;
+    txn.commit().await
+}
+
+enum RestoreOutcome {
+    Restored,
+    NoTombstone,
+    NoRecord,
+}
+
+// Puts the newest live tombstone back and removes it in one transaction. The tombstone row stays
+// locked until then, so two concurrent undos can't both restore it.
+async fn restore_key(db: &DatabaseConnection, user_id: Uuid, provider: &str) -> Result<RestoreOutcome, DbErr> {
+    let txn = db.begin().await?;
+    let Some(tombstone) = deleted_keys::Entity::find()
+        .filter(deleted_keys::Column::UserId.eq(user_id))
+        .filter(deleted_keys::Column::Provider.eq(provider))
+        .filter(deleted_keys::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+        .order_by_desc(deleted_keys::Column::DeletedAt)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(RestoreOutcome::NoTombstone);
+    };
+    let Some(record) = helper_app::Entity::find()
+        .filter(helper_app::Column::UserId.eq(user_id))
+        .one(&txn)
+        .await?
+    else {
+        return Ok(RestoreOutcome::NoRecord);
+    };
+
+    let mut active_model: helper_app::ActiveModel = record.into();
+    if provider == PROVIDER_GEMINI {
+        active_model.gemini_key = Set(Some(tombstone.encrypted_key.clone()));
+    } else {
+        active_model.elevenlabs_key = Set(Some(tombstone.encrypted_key.clone()));
+    }
+    active_model.timestamp = Set(Utc::now().naive_utc());
+    active_model.update(&txn).await?;
+
+    deleted_keys::Entity::delete_by_id(tombstone.id).exec(&txn).await?;
+    txn.commit().await?;
+    Ok(RestoreOutcome::Restored)
+}
+
+async fn tombstone_key<C: ConnectionTrait>(db: &C, user_id: Uuid, provider: &str, ciphertext: String) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    deleted_keys::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        provider: Set(provider.to_string()),
+        encrypted_key: Set(ciphertext),
+        deleted_at: Set(now),
+        expires_at: Set(now + Duration::days(KEY_UNDO_WINDOW_DAYS)),
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// Removes key tombstones whose undo window has passed. Run periodically by the scheduler.
+pub async fn purge_expired_key_tombstones(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    let result = deleted_keys::Entity::delete_many()
+        .filter(deleted_keys::Column::ExpiresAt.lte(Utc::now().naive_utc()))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Rewrites every stored key of a user (live and tombstoned) that isn't under the current
+/// encryption key. Returns how many values were re-encrypted.
+pub async fn reencrypt_user_keys(db: &DatabaseConnection, crypto: &Crypto, user_id: Uuid) -> Result<u64, String> {
+    let mut count = 0;
+
+    let records = helper_app::Entity::find()
+        .filter(helper_app::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for record in records {
+        let gemini = record.gemini_key.as_deref().filter(|k| crypto.is_stale(k)).map(|k| crypto.reencrypt(k)).transpose()?;
+        let elevenlabs = record.elevenlabs_key.as_deref().filter(|k| crypto.is_stale(k)).map(|k| crypto.reencrypt(k)).transpose()?;
+        if gemini.is_none() && elevenlabs.is_none() {
+            continue;
+        }
+
+        let mut active_model: helper_app::ActiveModel = record.into();
+        if let Some(key) = gemini {
+            active_model.gemini_key = Set(Some(key));
+            count += 1;
+        }
+        if let Some(key) = elevenlabs {
+            active_model.elevenlabs_key = Set(Some(key));
+            count += 1;
+        }
+        active_model.update(db).await.map_err(|e| e.to_string())?;
+    }
+
+    let tombstones = deleted_keys::Entity::find()
+        .filter(deleted_keys::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    for tombstone in tombstones.into_iter().filter(|t| crypto.is_stale(&t.encrypted_key)) {
+        let key = crypto.reencrypt(&tombstone.encrypted_key)?;
+        let mut active_model: deleted_keys::ActiveModel = tombstone.into();
+        active_model.encrypted_key = Set(key);
+        active_model.update(db).await.map_err(|e| e.to_string())?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use poem::http::StatusCode;
+
+    use super::*;
+    use crate::api::gemini::init_test_gemini_base;
+    use crate::test_support::{assert_golden, bearer, login, normalize_response, seed_user, test_app, test_db};
+
+    // Fails every write to the user's `helperApp` row with an error that names schema details
+    async fn fail_helper_writes(db: &DatabaseConnection, user_id: Uuid, name: &str, errcode: &str) {
+        db.execute_unprepared(&format!(
+            r#"CREATE FUNCTION {name}() RETURNS trigger LANGUAGE plpgsql AS $$
+               BEGIN
+                   RAISE EXCEPTION USING ERRCODE = '{errcode}', CONSTRAINT = 'helper_secret_idx',
+                       MESSAGE = 'duplicate key value violates unique constraint "helper_secret_idx" on "helperApp"';
+               END $$;
+               CREATE TRIGGER {name} BEFORE INSERT OR UPDATE ON "helperApp"
+                   FOR EACH ROW WHEN (NEW.user_id = '{user_id}') EXECUTE FUNCTION {name}();"#
+        ))
+        .await
+        .unwrap();
+    }
+
+    async fn drop_trigger(db: &DatabaseConnection, name: &str) {
+        db.execute_unprepared(&format!(r#"DROP TRIGGER {name} ON "helperApp"; DROP FUNCTION {name}();"#))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn database_errors_never_reach_the_response_body() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        client
+            .post("/api/api_keys/save")
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "gemini_api_key": "gemini-test-key", "elevenlabs_api_key": "eleven-test-key" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let suffix = &user.id.simple().to_string()[..12];
+        let cases = [
+            ("23505", "delete", "/api/api_keys/gemini", "conflict"),
+            ("XX000", "delete", "/api/api_keys/elevenlabs", "internal_error"),
+            ("XX000", "post", "/api/helper/status", "internal_error"),
+        ];
+        for (errcode, method, path, code) in cases {
+            let trigger = format!("fail_helper_{}_{}", suffix, errcode.to_lowercase());
+            fail_helper_writes(&db, user.id, &trigger, errcode).await;
+
+            let request = match method {
+                "post" => client.post(path).body_json(&serde_json::json!({ "status": true })),
+                _ => client.delete(path),
+            };
+            let resp = request.header("Authorization", bearer(&token)).send().await;
+            drop_trigger(&db, &trigger).await;
+
+            resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+            let body = resp.0.into_body().into_string().await.unwrap();
+            for leaked in ["helper_secret_idx", "helperApp", "duplicate key", "Query Error", "Exec"] {
+                assert!(!body.contains(leaked), "{} {} leaked {:?}: {}", method, path, leaked, body);
+            }
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(body["code"], code, "{} {}", method, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn masked_api_keys_response_matches_the_golden_file() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        client
+            .post("/api/api_keys/save")
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "gemini_api_key": "gemini-test-key", "elevenlabs_api_key": "eleven-test-key" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let resp = client
+            .get("/api/api_keys/get")
+            .query("masked", &true)
+            .header("Authorization", bearer(&token))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let mut keys: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await.unwrap()).unwrap();
+        normalize_response(&mut keys, &[(user.id.to_string(), "<user_id>")]);
+        assert_golden("api_keys_get_masked.json", &keys);
+    }
+
+    #[test]
+    fn mask_key_shows_at_most_the_last_four_characters() {
+        assert_eq!(mask_key("AIzaSyD-example-key-1234"), "****1234");
+        assert_eq!(mask_key("clé-secrète-äöü"), "****-äöü");
+        assert_eq!(mask_key("short"), "****");
+        assert_eq!(mask_key("12345678"), "****");
+    }
+
+    // A stand-in for Gemini that answers with `title` only when called with `key`
+    async fn fake_gemini(key: &'static str, title: &'static str) -> String {
+        use poem::listener::{Acceptor, Listener, TcpListener};
+        use poem::web::Query;
+        use poem::{handler, post, EndpointExt, IntoResponse, Route, Server};
+
+        #[handler]
+        fn generate(Query(query): Query<HashMap<String, String>>, Data(expected): Data<&(&'static str, &'static str)>) -> poem::Response {
+            if query.get("key").map(String::as_str) != Some(expected.0) {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            poem::web::Json(serde_json::json!({ "candidates": [{ "content": { "parts": [{ "text": expected.1 }] } }] })).into_response()
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().copied().unwrap();
+        let app = Route::new().at("/models/*model", post(generate)).data((key, title));
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn restored_gemini_key_is_used_for_ai_calls() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+        init_test_gemini_base(fake_gemini("restored-gemini-key", "Groceries").await);
+
+        client
+            .post("/api/api_keys/save")
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "gemini_api_key": "restored-gemini-key" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+        let name_memo = || {
+            client
+                .post("/api/generate_memo_name")
+                .header("Authorization", bearer(&token))
+                .body_json(&serde_json::json!({ "transcript": "Milk, eggs and bread" }))
+                .send()
+        };
+
+        client.delete("/api/api_keys/gemini").header("Authorization", bearer(&token)).send().await.assert_status_is_ok();
+        let resp = name_memo().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        resp.json().await.value().object().get("code").assert_string("no_api_key");
+
+        client
+            .post("/api/api_keys/gemini/undo_delete")
+            .header("Authorization", bearer(&token))
+            .send()
+            .await
+            .assert_status_is_ok();
+        let resp = name_memo().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("Groceries").await;
+
+        // The tombstone went with the restore, so a second undo has nothing left
+        let tombstones = deleted_keys::Entity::find().filter(deleted_keys::Column::UserId.eq(user.id)).count(&db).await.unwrap();
+        assert_eq!(tombstones, 0);
+        client
+            .post("/api/api_keys/gemini/undo_delete")
+            .header("Authorization", bearer(&token))
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -1,10 +1,44 @@
+pub mod audio;
+pub mod auth;
 pub mod user;
 pub mod gemini;
 pub mod memo_api_store_ops;
 pub mod memo;
 pub mod crypto;
+pub mod error;
+pub mod jwt;
+pub mod password;
+pub mod admin;
+pub mod share;
+pub mod sandbox;
+pub mod tokens;
+pub mod sessions;
+pub mod challenge;
+pub mod limits;
+pub mod avatar;
+pub mod login_limit;
+pub mod mailer;
+pub mod usage;
+pub mod export;
+pub mod erasure;
+pub mod preferences;
+pub mod import;
+pub mod folders;
+pub mod storage;
 pub use user::UserApi;
 pub use gemini::GeminiApi;
 pub use memo::MemoApi;
+pub use admin::AdminApi;
+pub use share::ShareApi;
+pub use tokens::TokenApi;
+pub use sessions::SessionApi;
+pub use avatar::AvatarApi;
+pub use usage::UsageApi;
+pub use export::ExportApi;
+pub use erasure::ErasureApi;
+pub use preferences::PreferencesApi;
+pub use auth::AuthApi;
+pub use import::ImportApi;
+pub use folders::FolderApi;
 
 pub use memo_api_store_ops::Api;
@@ -0,0 +1,154 @@
+use std::env;
+
+use poem_openapi::Object;
+use serde::Serialize;
+use validator::ValidationError;
+
+// --- Password Policy ---
+
+/// Passwords that show up at the top of every breach list. Compared case-insensitively.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "password1", "password123", "passw0rd", "12345678", "123456789",
+    "1234567890", "qwerty123", "qwertyuiop", "iloveyou", "sunshine", "princess",
+    "football", "baseball", "welcome1", "admin123", "letmein1", "trustno1",
+    "superman", "starwars", "whatever", "dragon123", "monkey123", "abc12345",
+    "11111111", "00000000", "asdfghjk", "zaq12wsx", "1q2w3e4r", "changeme",
+];
+
+const COMMON_PASSWORD: &str = "common_password";
+
+/// Longest run of one repeated character we tolerate, e.g. "aaa" is fine, "aaaa" is not.
+const MAX_REPEATED_RUN: usize = 3;
+
+#[derive(Object, Serialize, Debug, Clone, PartialEq)]
+pub struct PasswordIssue {
+    /// Machine-readable rule identifier, e.g. `common_password`.
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct PasswordPolicyResponse {
+    pub message: String,
+    pub issues: Vec<PasswordIssue>,
+}
+
+impl PasswordIssue {
+    fn new(code: &str, message: &str) -> Self {
+        PasswordIssue { code: code.to_string(), message: message.to_string() }
+    }
+}
+
+/// Runs every password rule. Pure, so it can be exercised without a server or environment.
+pub fn validate_password(password: &str, username: &str, email: &str) -> Vec<PasswordIssue> {
+    let mut issues = Vec::new();
+    let lowered = password.to_lowercase();
+
+    if COMMON_PASSWORDS.contains(&lowered.as_str()) {
+        issues.push(PasswordIssue::new(COMMON_PASSWORD, "This password is too common"));
+    }
+
+    if longest_run(password) > MAX_REPEATED_RUN {
+        issues.push(PasswordIssue::new(
+            "repeated_characters",
+            "Password must not repeat the same character more than 3 times in a row",
+        ));
+    }
+
+    let username = username.trim().to_lowercase();
+    if username.len() >= 3 && lowered.contains(&username) {
+        issues.push(PasswordIssue::new("contains_username", "Password must not contain your username"));
+    }
+
+    let email = email.trim().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+    if !email.is_empty() && (lowered.contains(&email) || (local_part.len() >= 3 && lowered.contains(local_part))) {
+        issues.push(PasswordIssue::new("contains_email", "Password must not contain your email address"));
+    }
+
+    issues
+}
+
+/// Applies `validate_password` with the deployment's configuration. Setting
+/// `PASSWORD_REJECT_COMMON=false` lets self-hosted installs skip the common-password list.
+pub fn enforce_password_policy(password: &str, username: &str, email: &str) -> Vec<PasswordIssue> {
+    let reject_common = env::var("PASSWORD_REJECT_COMMON")
+        .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+        .unwrap_or(true);
+
+    let mut issues = validate_password(password, username, email);
+    if !reject_common {
+        issues.retain(|issue| issue.code != COMMON_PASSWORD);
+    }
+    issues
+}
+
+/// `#[validate(custom)]` rule for new passwords: at least one letter and one digit. Symbols are
+/// welcome but not required. Runs with the other field rules, so it is reported per field.
+pub fn password_complexity(password: &str) -> Result<(), ValidationError> {
+    let has_letter = password.chars().any(char::is_alphabetic);
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    if has_letter && has_digit {
+        return Ok(());
+    }
+
+    let mut error = ValidationError::new("complexity");
+    error.message = Some("Password must contain at least one letter and one digit".into());
+    Err(error)
+}
+
+fn longest_run(s: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous = None;
+    for c in s.chars() {
+        current = if Some(c) == previous { current + 1 } else { 1 };
+        longest = longest.max(current);
+        previous = Some(c);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use validator::Validate;
+
+    use super::*;
+    use crate::api::user::SignupPayload;
+
+    // What signup reports for a new password: the field rules first, then the policy
+    fn issue_codes(password: &str, username: &str, email: &str) -> Vec<String> {
+        let payload: SignupPayload =
+            serde_json::from_value(serde_json::json!({ "username": username, "email": email, "password": password })).unwrap();
+        if let Err(errors) = payload.validate() {
+            return errors.field_errors()["password"].iter().map(|error| error.code.to_string()).collect();
+        }
+        enforce_password_policy(password, username, email).into_iter().map(|issue| issue.code).collect()
+    }
+
+    #[test]
+    fn new_passwords_report_each_policy_issue() {
+        let cases: &[(&str, &[&str])] = &[
+            ("Correct-Horse-42", &[]),
+            ("Sh0rt!", &["length"]),
+            ("abc", &["length", "complexity"]),
+            ("No-Digits-Here", &["complexity"]),
+            ("1234-5678-90", &["complexity"]),
+            ("Password123", &[COMMON_PASSWORD]),
+            ("PASSW0RD", &[COMMON_PASSWORD]),
+            ("Baaaad-Horse-42", &["repeated_characters"]),
+            ("My-Alice_W-42", &["contains_username"]),
+            ("Alice.Walker42", &["contains_email"]),
+            ("alice.walker@example.test1", &["contains_email"]),
+            ("Alice_w-0000", &["repeated_characters", "contains_username"]),
+        ];
+        for (password, expected) in cases {
+            assert_eq!(issue_codes(password, "alice_w", "alice.walker@example.test"), *expected, "{}", password);
+        }
+    }
+
+    #[test]
+    fn short_usernames_and_local_parts_are_not_matched() {
+        assert!(validate_password("Correct-ab-Horse-42", "ab", "ab@example.test").is_empty());
+    }
+}
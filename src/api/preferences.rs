@@ -0,0 +1,259 @@
+use chrono::{NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use poem::web::Data;
+use poem_openapi::{payload::Json, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, sea_query::OnConflict, DatabaseConnection, DbErr};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use entity::user_preferences;
+use crate::api::auth::SessionAuth;
+use crate::api::error::ErrorBody;
+use crate::api::gemini::{gemini_model, SummaryLength, GEMINI_MODELS};
+
+// --- Constants ---
+const MAX_LANGUAGE_LEN: usize = 64;
+
+// --- API Structs ---
+
+/// The caller's preferences, with defaults filled in for anything never set.
+#[derive(Object, Serialize, Clone)]
+pub struct UserPreferences {
+    /// Used by `/translate` when the request has no `lang`
+    #[oai(skip_serializing_if_is_none)]
+    pub default_translate_lang: Option<String>,
+    /// Used by `/summary` when the request has no `length`
+    pub summary_style: SummaryLength,
+    /// The Gemini model every AI call goes to
+    pub preferred_model: String,
+    /// For clients: process new recordings right away instead of asking
+    pub auto_process: bool,
+    /// IANA name such as `Europe/Berlin`; memo timestamps are shown in UTC when unset
+    #[oai(skip_serializing_if_is_none)]
+    pub timezone: Option<String>,
+}
+
+/// Replaces all preferences; omitted fields go back to their defaults.
+#[derive(Object, Deserialize)]
+#[oai(deny_unknown_fields)]
+pub struct PreferencesPayload {
+    pub default_translate_lang: Option<String>,
+    pub summary_style: Option<SummaryLength>,
+    /// One of `GET /preferences/models`
+    pub preferred_model: Option<String>,
+    pub auto_process: Option<bool>,
+    pub timezone: Option<String>,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum PreferencesApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<UserPreferences>),
+    /// An unknown field, an unsupported model or timezone, or an overlong language
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct PreferencesApi;
+
+#[OpenApi]
+impl PreferencesApi {
+    /// The caller's preferences; defaults until they are first saved
+    #[oai(path = "/preferences", method = "get")]
+    async fn get_preferences(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> PreferencesApiResponse {
+        match load_preferences(db.0, auth.0.id).await {
+            Ok(preferences) => PreferencesApiResponse::Ok(Json(preferences)),
+            Err(e) => PreferencesApiResponse::InternalServerError(ErrorBody::from_db("Failed to load preferences", &e)),
+        }
+    }
+
+    /// Replace the caller's preferences
+    #[oai(path = "/preferences", method = "put")]
+    async fn put_preferences(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<PreferencesPayload>,
+    ) -> PreferencesApiResponse {
+        let user_id = auth.0.id;
+
+        let language = payload.default_translate_lang.map(|lang| lang.trim().to_string()).filter(|lang| !lang.is_empty());
+        if language.as_ref().is_some_and(|lang| lang.chars().count() > MAX_LANGUAGE_LEN) {
+            return PreferencesApiResponse::BadRequest(ErrorBody::new(
+                "invalid_language",
+                format!("default_translate_lang is limited to {} characters", MAX_LANGUAGE_LEN),
+            ));
+        }
+
+        let model = payload.preferred_model.map(|model| model.trim().to_string()).filter(|model| !model.is_empty());
+        if let Some(model) = &model
+            && !GEMINI_MODELS.contains(&model.as_str())
+        {
+            return PreferencesApiResponse::BadRequest(ErrorBody::new(
+                "unsupported_model",
+                format!("Unsupported model {}; supported: {}", model, GEMINI_MODELS.join(", ")),
+            ));
+        }
+
+        let timezone = payload.timezone.map(|tz| tz.trim().to_string()).filter(|tz| !tz.is_empty());
+        if let Some(timezone) = &timezone
+            && timezone.parse::<Tz>().is_err()
+        {
+            return PreferencesApiResponse::BadRequest(ErrorBody::new(
+                "invalid_timezone",
+                format!("Unknown timezone {}; use an IANA name such as Europe/Berlin", timezone),
+            ));
+        }
+
+        let preferences = user_preferences::ActiveModel {
+            user_id: Set(user_id),
+            default_translate_lang: Set(language),
+            summary_style: Set(payload.summary_style.map(|style| style.as_str().to_string())),
+            preferred_model: Set(model),
+            auto_process: Set(payload.auto_process.unwrap_or(false)),
+            timezone: Set(timezone),
+            updated_at: Set(Utc::now().naive_utc()),
+        };
+
+        let saved = user_preferences::Entity::insert(preferences)
+            .on_conflict(
+                OnConflict::column(user_preferences::Column::UserId)
+                    .update_columns([
+                        user_preferences::Column::DefaultTranslateLang,
+                        user_preferences::Column::SummaryStyle,
+                        user_preferences::Column::PreferredModel,
+                        user_preferences::Column::AutoProcess,
+                        user_preferences::Column::Timezone,
+                        user_preferences::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(db.0)
+            .await;
+
+        match saved {
+            Ok(saved) => PreferencesApiResponse::Ok(Json(UserPreferences::from(Some(saved)))),
+            Err(e) => PreferencesApiResponse::InternalServerError(ErrorBody::from_db("Failed to save preferences", &e)),
+        }
+    }
+
+    /// The models `preferred_model` can be set to, default first
+    #[oai(path = "/preferences/models", method = "get")]
+    async fn list_models(&self) -> Json<Vec<String>> {
+        Json(GEMINI_MODELS.iter().map(|model| model.to_string()).collect())
+    }
+}
+
+impl From<Option<user_preferences::Model>> for UserPreferences {
+    fn from(row: Option<user_preferences::Model>) -> Self {
+        let row = row.as_ref();
+        UserPreferences {
+            default_translate_lang: row.and_then(|row| row.default_translate_lang.clone()),
+            summary_style: row
+                .and_then(|row| row.summary_style.as_deref())
+                .and_then(SummaryLength::parse)
+                .unwrap_or_default(),
+            // A model that has since been dropped from the list falls back to the default
+            preferred_model: gemini_model(row.and_then(|row| row.preferred_model.as_deref())).to_string(),
+            auto_process: row.is_some_and(|row| row.auto_process),
+            timezone: row.and_then(|row| row.timezone.clone()),
+        }
+    }
+}
+
+impl UserPreferences {
+    /// `None` for UTC, including a stored name this build of the timezone database doesn't know.
+    pub(crate) fn tz(&self) -> Option<Tz> {
+        self.timezone.as_deref().and_then(|tz| tz.parse().ok())
+    }
+}
+
+// --- Helper Functions ---
+
+pub(crate) async fn load_preferences(db: &DatabaseConnection, user_id: Uuid) -> Result<UserPreferences, DbErr> {
+    let row = user_preferences::Entity::find_by_id(user_id).one(db).await?;
+    Ok(UserPreferences::from(row))
+}
+
+/// A stored UTC timestamp as RFC 3339 in `tz`, with that zone's offset at that instant, or with `Z` for UTC.
+pub(crate) fn format_timestamp(at: NaiveDateTime, tz: Option<Tz>) -> String {
+    match tz {
+        Some(tz) => tz.from_utc_datetime(&at).to_rfc3339_opts(SecondsFormat::AutoSi, false),
+        None => at.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    }
+}
+
+/// For AI calls: a failed lookup shouldn't fail the call, so it just means the defaults.
+pub(crate) async fn preferences_or_default(db: &DatabaseConnection, user_id: Uuid) -> UserPreferences {
+    load_preferences(db, user_id).await.unwrap_or_else(|e| {
+        tracing::warn!("Failed to load preferences, using defaults: {:?}", e);
+        UserPreferences::from(None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn utc(date: (i32, u32, u32), time: (u32, u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap().and_hms_opt(time.0, time.1, time.2).unwrap()
+    }
+
+    fn user_preferences_row() -> user_preferences::Model {
+        user_preferences::Model {
+            user_id: Uuid::new_v4(),
+            default_translate_lang: None,
+            summary_style: None,
+            preferred_model: None,
+            auto_process: false,
+            timezone: None,
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn format_timestamp_follows_the_offset_across_dst_changes() {
+        let berlin: Option<Tz> = Some(chrono_tz::Europe::Berlin);
+        // Spring forward at 01:00 UTC: local time jumps from 01:59:59 to 03:00
+        assert_eq!(format_timestamp(utc((2025, 3, 30), (0, 59, 59)), berlin), "2025-03-30T01:59:59+01:00");
+        assert_eq!(format_timestamp(utc((2025, 3, 30), (1, 0, 0)), berlin), "2025-03-30T03:00:00+02:00");
+        // Fall back at 01:00 UTC: 02:xx local happens twice, told apart by the offset
+        assert_eq!(format_timestamp(utc((2025, 10, 26), (0, 30, 0)), berlin), "2025-10-26T02:30:00+02:00");
+        assert_eq!(format_timestamp(utc((2025, 10, 26), (1, 30, 0)), berlin), "2025-10-26T02:30:00+01:00");
+
+        let new_york: Option<Tz> = Some(chrono_tz::America::New_York);
+        assert_eq!(format_timestamp(utc((2025, 3, 9), (6, 59, 59)), new_york), "2025-03-09T01:59:59-05:00");
+        assert_eq!(format_timestamp(utc((2025, 3, 9), (7, 0, 0)), new_york), "2025-03-09T03:00:00-04:00");
+        assert_eq!(format_timestamp(utc((2025, 11, 2), (5, 30, 0)), new_york), "2025-11-02T01:30:00-04:00");
+        assert_eq!(format_timestamp(utc((2025, 11, 2), (6, 30, 0)), new_york), "2025-11-02T01:30:00-05:00");
+    }
+
+    #[test]
+    fn format_timestamp_without_a_timezone_is_utc() {
+        let at = utc((2025, 3, 30), (1, 0, 0));
+        assert_eq!(format_timestamp(at, None), "2025-03-30T01:00:00Z");
+        let at_millis = at + chrono::Duration::milliseconds(250);
+        assert_eq!(format_timestamp(at_millis, None), "2025-03-30T01:00:00.250Z");
+
+        // No preferences row, or one without a zone, or with a zone that no longer parses
+        assert_eq!(UserPreferences::from(None).tz(), None);
+        let stored = |timezone: Option<&str>| user_preferences::Model {
+            timezone: timezone.map(str::to_string),
+            ..user_preferences_row()
+        };
+        assert_eq!(UserPreferences::from(Some(stored(None))).tz(), None);
+        assert_eq!(UserPreferences::from(Some(stored(Some("Mars/Olympus_Mons")))).tz(), None);
+        assert_eq!(UserPreferences::from(Some(stored(Some("Europe/Berlin")))).tz(), Some(chrono_tz::Europe::Berlin));
+    }
+}
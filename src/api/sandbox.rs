@@ -0,0 +1,80 @@
+use std::env;
+
+use entity::users;
+use crate::api::gemini::SummaryLength;
+
+// --- Sandbox AI ---
+// Deterministic stand-ins for Gemini so UI work doesn't spend real quota.
+// The same input always produces the same output.
+
+const LOREM: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit",
+    "sed", "do", "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore",
+    "magna", "aliqua", "enim", "ad", "minim", "veniam", "quis", "nostrud",
+];
+
+/// `AI_SANDBOX=true` forces sandbox mode for every user of a deployment.
+pub fn forced() -> bool {
+    env::var("AI_SANDBOX")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+pub fn enabled_for(user: &users::Model) -> bool {
+    forced() || user.sandbox_ai
+}
+
+pub fn transcribe(audio_bytes: &[u8]) -> String {
+    format!("Sandbox transcript {:04x} ({} bytes of audio)", short_hash(audio_bytes), audio_bytes.len())
+}
+
+/// What sandbox translation reports as the source language when none is given.
+pub const DETECTED_LANGUAGE: &str = "English";
+
+pub fn translate(text: &str, target_lang: &str) -> String {
+    format!("[{}] {}", target_lang, text)
+}
+
+/// Roughly a quarter of the input's length (a tenth for `short`), so layouts see realistic sizes.
+/// `bullets` splits the same words into a markdown list.
+pub fn summarize(text: &str, length: SummaryLength) -> String {
+    let input_words = text.split_whitespace().count();
+    let words = match length {
+        SummaryLength::Short => (input_words / 10).clamp(3, 20),
+        SummaryLength::Medium | SummaryLength::Bullets => (input_words / 4).clamp(3, 60),
+    };
+    let offset = fnv1a(text.as_bytes()) as usize;
+    let words: Vec<&str> = (0..words).map(|i| LOREM[(offset + i) % LOREM.len()]).collect();
+
+    if length == SummaryLength::Bullets {
+        return words.chunks(6).map(|point| format!("- {}", point.join(" "))).collect::<Vec<_>>().join("\n");
+    }
+    format!("{}.", words.join(" "))
+}
+
+pub fn title(transcript: &str) -> String {
+    format!("Sandbox Title {:04x}", short_hash(transcript.as_bytes()))
+}
+
+/// A few lorem phrases, more for longer transcripts.
+pub fn extract(transcript: &str) -> Vec<String> {
+    let items = (transcript.split_whitespace().count() / 25).clamp(1, 5);
+    let offset = fnv1a(transcript.as_bytes()) as usize;
+    (0..items)
+        .map(|i| format!("{} {}", LOREM[(offset + 2 * i) % LOREM.len()], LOREM[(offset + 2 * i + 1) % LOREM.len()]))
+        .collect()
+}
+
+/// The sandbox never claims a memo refers to another one.
+pub fn suggest_links() -> String {
+    "[]".to_string()
+}
+
+fn short_hash(bytes: &[u8]) -> u16 {
+    (fnv1a(bytes) & 0xffff) as u16
+}
+
+// FNV-1a rather than std's hasher, whose output isn't guaranteed stable across releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use poem::{web::Data, Request};
+use poem_openapi::{payload::Json, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::{Expr, OnConflict}, DatabaseConnection, DbErr};
+use serde::Serialize;
+use uuid::Uuid;
+
+use entity::{revoked_tokens, sessions};
+use crate::api::auth::{Claims, CurrentSession, CurrentSessionAuth, TokenError, TokenRejection};
+use crate::api::error::ErrorBody;
+
+// --- Constants ---
+// last_active_at is only rewritten when it is older than this, so every request isn't a write
+const ACTIVITY_RESOLUTION_MINUTES: i64 = 5;
+
+// --- API Structs ---
+
+#[derive(Object, Serialize)]
+pub struct SessionOutput {
+    pub id: String,
+    /// `extension`, `web` or `api`
+    pub client: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: String,
+    pub last_active_at: String,
+    /// The session making this request
+    pub current: bool,
+}
+
+#[derive(Object, Serialize)]
+pub struct RevokeSessionsResponse {
+    pub message: String,
+    pub revoked: u64,
+}
+
+#[derive(Object, Serialize)]
+pub struct LogoutResponse {
+    pub message: String,
+}
+
+// --- API Responses ---
+#[derive(ApiResponse)]
+enum ListSessionsApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<SessionOutput>>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum RevokeSessionsApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<RevokeSessionsResponse>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum LogoutApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<LogoutResponse>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct SessionApi;
+
+#[OpenApi]
+impl SessionApi {
+    /// List the caller's active sessions with the device they came from
+    #[oai(path = "/sessions", method = "get")]
+    async fn list_sessions(
+        &self,
+        auth: CurrentSessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> ListSessionsApiResponse {
+        let CurrentSession { user, claims } = auth.0;
+        let current = session_id(&claims);
+
+        let active = match sessions::Entity::find()
+            .filter(sessions::Column::UserId.eq(user.id))
+            .filter(sessions::Column::RevokedAt.is_null())
+            .order_by_desc(sessions::Column::LastActiveAt)
+            .all(db.0)
+            .await
+        {
+            Ok(active) => active,
+            Err(e) => return ListSessionsApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch sessions", &e)),
+        };
+
+        ListSessionsApiResponse::Ok(Json(
+            active
+                .into_iter()
+                .map(|session| SessionOutput {
+                    id: session.id.to_string(),
+                    client: session.client,
+                    user_agent: session.user_agent,
+                    ip: session.ip,
+                    created_at: session.created_at.and_utc().to_rfc3339(),
+                    last_active_at: session.last_active_at.and_utc().to_rfc3339(),
+                    current: Some(session.id) == current,
+                })
+                .collect(),
+        ))
+    }
+
+    /// Sign out every other device. The session making the request stays valid.
+    #[oai(path = "/sessions", method = "delete")]
+    async fn revoke_other_sessions(
+        &self,
+        auth: CurrentSessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> RevokeSessionsApiResponse {
+        let CurrentSession { user, claims } = auth.0;
+
+        let mut revoke = sessions::Entity::update_many()
+            .col_expr(sessions::Column::RevokedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(sessions::Column::UserId.eq(user.id))
+            .filter(sessions::Column::RevokedAt.is_null());
+        // A token from before sessions existed has no session of its own to keep
+        if let Some(current) = session_id(&claims) {
+            revoke = revoke.filter(sessions::Column::Id.ne(current));
+        }
+
+        match revoke.exec(db.0).await {
+            Ok(result) => RevokeSessionsApiResponse::Ok(Json(RevokeSessionsResponse {
+                message: "Other sessions revoked".to_string(),
+                revoked: result.rows_affected,
+            })),
+            Err(e) => RevokeSessionsApiResponse::InternalServerError(ErrorBody::from_db("Failed to revoke sessions", &e)),
+        }
+    }
+
+    /// Log out: the token making the request stops working right away, instead of at its expiry.
+    #[oai(path = "/logout", method = "post")]
+    async fn logout(
+        &self,
+        auth: CurrentSessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> LogoutApiResponse {
+        let CurrentSession { user, claims } = auth.0;
+
+        let ended = async {
+            if let Some(jti) = claims.jti.as_deref().and_then(|jti| Uuid::parse_str(jti).ok()) {
+                let expires_at = DateTime::from_timestamp(claims.exp as i64, 0)
+                    .map(|at| at.naive_utc())
+                    .unwrap_or_else(|| Utc::now().naive_utc());
+                revoke_token(db.0, user.id, jti, expires_at).await?;
+            }
+            // Also ends the session, so it drops out of the sessions list
+            if let Some(sid) = claims.sid.as_deref().and_then(|sid| Uuid::parse_str(sid).ok()) {
+                sessions::Entity::update_many()
+                    .col_expr(sessions::Column::RevokedAt, Expr::value(Utc::now().naive_utc()))
+                    .filter(sessions::Column::Id.eq(sid))
+                    .filter(sessions::Column::RevokedAt.is_null())
+                    .exec(db.0)
+                    .await?;
+            }
+            Ok::<_, DbErr>(())
+        }
+        .await;
+
+        match ended {
+            Ok(()) => LogoutApiResponse::Ok(Json(LogoutResponse { message: "Logged out".to_string() })),
+            Err(e) => LogoutApiResponse::InternalServerError(ErrorBody::from_db("Failed to log out", &e)),
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+/// Adds a token to the denylist until it would have expired anyway.
+pub async fn revoke_token(db: &DatabaseConnection, user_id: Uuid, jti: Uuid, expires_at: NaiveDateTime) -> Result<(), DbErr> {
+    let entry = revoked_tokens::ActiveModel {
+        jti: Set(jti),
+        user_id: Set(user_id),
+        revoked_at: Set(Utc::now().naive_utc()),
+        expires_at: Set(expires_at),
+    };
+    // Logging out twice with the same token is fine
+    revoked_tokens::Entity::insert(entry)
+        .on_conflict(OnConflict::column(revoked_tokens::Column::Jti).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
+
+/// Rejects tokens that were logged out.
+pub async fn check_not_revoked(db: &DatabaseConnection, jti: &str) -> Result<(), TokenError> {
+    let jti = Uuid::parse_str(jti).map_err(|_| TokenError::new(TokenRejection::InvalidToken, "Invalid token ID"))?;
+    let revoked = revoked_tokens::Entity::find_by_id(jti)
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while checking token revocation: {:?}", e);
+            TokenError::new(TokenRejection::ServerError, "Failed to verify token due to a database error")
+        })?;
+    match revoked {
+        Some(_) => Err(TokenError::new(TokenRejection::SessionRevoked, "Token has been revoked")),
+        None => Ok(()),
+    }
+}
+
+/// Drops denylist entries for tokens that have expired; the expiry check refuses those on its own.
+pub async fn purge_expired_revocations(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    let result = revoked_tokens::Entity::delete_many()
+        .filter(revoked_tokens::Column::ExpiresAt.lte(Utc::now().naive_utc()))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Deletes sessions whose token has expired and returns, per user, how many of them were still
+/// signed in (revoked sessions were ended on purpose and aren't worth telling anyone about).
+pub async fn sweep_expired_sessions(db: &DatabaseConnection) -> Result<HashMap<Uuid, u64>, DbErr> {
+    let expired = sessions::Entity::delete_many()
+        .filter(sessions::Column::ExpiresAt.lte(Utc::now().naive_utc()))
+        .exec_with_returning(db)
+        .await?;
+
+    let mut by_user: HashMap<Uuid, u64> = HashMap::new();
+    for session in expired.iter().filter(|session| session.revoked_at.is_none()) {
+        *by_user.entry(session.user_id).or_default() += 1;
+    }
+    Ok(by_user)
+}
+
+/// Records a new login with the device it came from and returns the session id for the token.
+/// The session expires with the token, after `ttl`.
+pub async fn start_session(db: &DatabaseConnection, user_id: Uuid, req: &Request, ttl: Duration) -> Result<Uuid, DbErr> {
+    let now = Utc::now().naive_utc();
+    let user_agent = header(req, "user-agent");
+
+    let session = sessions::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        client: Set(client_label(req, user_agent.as_deref()).to_string()),
+        user_agent: Set(user_agent),
+        ip: Set(client_ip(req)),
+        created_at: Set(now),
+        last_active_at: Set(now),
+        revoked_at: Set(None),
+        expires_at: Set(now + ttl),
+    }
+    .insert(db)
+    .await?;
+
+    Ok(session.id)
+}
+
+/// Rejects tokens whose session was revoked and keeps `last_active_at` roughly current.
+pub async fn check_session(db: &DatabaseConnection, sid: &str) -> Result<(), TokenError> {
+    let sid = Uuid::parse_str(sid).map_err(|_| TokenError::new(TokenRejection::InvalidToken, "Invalid session"))?;
+    let session = sessions::Entity::find_by_id(sid)
+        .filter(sessions::Column::RevokedAt.is_null())
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while checking session: {:?}", e);
+            TokenError::new(TokenRejection::ServerError, "Failed to verify session due to a database error")
+        })?
+        .ok_or_else(|| TokenError::new(TokenRejection::SessionRevoked, "Session has been revoked"))?;
+
+    let now = Utc::now().naive_utc();
+    if session.last_active_at < now - Duration::minutes(ACTIVITY_RESOLUTION_MINUTES) {
+        let touched = sessions::Entity::update_many()
+            .col_expr(sessions::Column::LastActiveAt, Expr::value(now))
+            .filter(sessions::Column::Id.eq(sid))
+            .exec(db)
+            .await;
+        if let Err(e) = touched {
+            tracing::warn!("Failed to record session activity: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+fn session_id(claims: &Claims) -> Option<Uuid> {
+    claims.sid.as_deref().and_then(|sid| Uuid::parse_str(sid).ok())
+}
+
+fn header(req: &Request, name: &str) -> Option<String> {
+    req.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+}
+
+// Coarse on purpose: it only has to be good enough to label a row in the sessions list.
+fn client_label(req: &Request, user_agent: Option<&str>) -> &'static str {
+    let origin = header(req, "origin").unwrap_or_default();
+    if origin.starts_with("chrome-extension://") || origin.starts_with("moz-extension://") {
+        "extension"
+    } else if user_agent.is_some_and(|ua| ua.starts_with("Mozilla/")) {
+        "web"
+    } else {
+        "api"
+    }
+}
+
+/// The caller's address. Behind a proxy the peer is the proxy itself, so with `TRUSTED_PROXY` on
+/// the right-most `X-Forwarded-For` entry is used: the one our proxy appended. Anything left of it
+/// was sent by the client and can say whatever it likes.
+pub fn client_ip(req: &Request) -> Option<String> {
+    client_ip_with(req, behind_trusted_proxy())
+}
+
+fn client_ip_with(req: &Request, trusted_proxy: bool) -> Option<String> {
+    let forwarded = if trusted_proxy {
+        header(req, "x-forwarded-for")
+            .and_then(|v| v.rsplit(',').next().map(|ip| ip.trim().to_string()))
+            .filter(|ip| !ip.is_empty())
+    } else {
+        None
+    };
+    forwarded.or_else(|| req.remote_addr().as_socket_addr().map(|addr| addr.ip().to_string()))
+}
+
+fn behind_trusted_proxy() -> bool {
+    std::env::var("TRUSTED_PROXY")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::http::StatusCode;
+
+    use super::*;
+    use crate::test_support::{bearer, login, seed_user, test_app, test_db};
+
+    fn forwarded(value: &str) -> Request {
+        Request::builder().header("x-forwarded-for", value).finish()
+    }
+
+    #[test]
+    fn trusted_proxy_uses_the_hop_it_appended() {
+        let req = forwarded("198.51.100.1, 203.0.113.7");
+        assert_eq!(client_ip_with(&req, true).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_header_is_ignored_without_a_trusted_proxy() {
+        let req = forwarded("198.51.100.1");
+        assert_eq!(client_ip_with(&req, false), None);
+    }
+
+    #[tokio::test]
+    async fn revoking_other_sessions_keeps_only_the_callers_token() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let mut tokens = Vec::new();
+        for _ in 0..4 {
+            tokens.push(login(&client, &user).await);
+        }
+
+        let resp = client.delete("/api/sessions").header("Authorization", bearer(&tokens[1])).send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("revoked").assert_i64(3);
+
+        for (i, token) in tokens.iter().enumerate() {
+            let resp = client.get("/api/sessions").header("Authorization", bearer(token)).send().await;
+            if i == 1 {
+                resp.assert_status_is_ok();
+                let body = resp.json().await;
+                let listed = body.value().object_array();
+                assert_eq!(listed.len(), 1);
+                listed[0].get("current").assert_bool(true);
+            } else {
+                resp.assert_status(StatusCode::UNAUTHORIZED);
+            }
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use poem::web::Data;
+use poem_openapi::{payload::{Binary, Json}, param::Path, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::Expr, DatabaseConnection, DbErr};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use entity::{memo_shares, voice_memos1};
+use crate::api::auth::SessionAuth;
+use crate::api::error::ErrorBody;
+use crate::api::storage::AudioStore;
+
+// --- Constants ---
+// An audio fetch this soon after a page load belongs to the same view
+const AUDIO_VIEW_WINDOW_MINUTES: i64 = 10;
+
+// --- API Structs ---
+
+#[derive(Object, Debug, Deserialize)]
+pub struct CreateSharePayload {
+    /// Link stops working this many hours after creation
+    pub expires_in_hours: Option<i64>,
+    /// Link stops working after this many views
+    pub max_views: Option<i32>,
+}
+
+#[derive(Object, Serialize)]
+pub struct ShareOutput {
+    pub slug: String,
+    pub path: String,
+    pub view_count: i32,
+    pub max_views: Option<i32>,
+    pub remaining_views: Option<i32>,
+    pub expires_at: Option<String>,
+    pub remaining_seconds: Option<i64>,
+    /// Set once the expiry sweep has retired the link
+    #[oai(skip_serializing_if_is_none)]
+    pub expired_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct SharedMemoOutput {
+    pub title: String,
+    pub transcript: Option<String>,
+    pub summary: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub duration: String,
+    pub created_at: String,
+    pub has_audio: bool,
+    pub remaining_views: Option<i32>,
+}
+
+// --- API Responses ---
+
+#[derive(ApiResponse)]
+enum CreateShareApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<ShareOutput>),
+    /// Malformed memo ID, or a limit that isn't positive
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    /// No such memo of the caller's
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum ListSharesApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ShareOutput>>),
+    /// Malformed memo ID
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    /// No such memo of the caller's
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum SharedMemoApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<SharedMemoOutput>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 410)]
+    Gone(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum SharedAudioApiResponse {
+    #[oai(status = 200, content_type = "audio/wav")]
+    Ok(Binary<Vec<u8>>),
+    /// The recording is in object storage; `Location` is a short-lived URL that serves it
+    #[oai(status = 307)]
+    TemporaryRedirect(#[oai(header = "Location")] String),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 410)]
+    Gone(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct ShareApi;
+
+#[OpenApi]
+impl ShareApi {
+    /// Create a public share link, optionally limited by views and/or time
+    #[oai(path = "/memo/:memo_id/shares", method = "post")]
+    async fn create_share(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+        Json(payload): Json<CreateSharePayload>,
+    ) -> CreateShareApiResponse {
+        let memo = match find_owned_memo(db.0, auth.0.id, &memo_id).await {
+            Ok(MemoLookup::Found(memo)) => memo,
+            Ok(MemoLookup::InvalidId) => return CreateShareApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+            Ok(MemoLookup::NotFound) => return CreateShareApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return CreateShareApiResponse::InternalServerError(ErrorBody::from_db("Failed to load memo", &e)),
+        };
+
+        if payload.max_views.is_some_and(|v| v < 1) || payload.expires_in_hours.is_some_and(|h| h < 1) {
+            return CreateShareApiResponse::BadRequest(ErrorBody::new(
+                "invalid_share_limits",
+                "max_views and expires_in_hours must be positive",
+            ));
+        }
+
+        let now = Utc::now().naive_utc();
+        let share = memo_shares::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            memo_id: Set(memo.id),
+            slug: Set(Uuid::new_v4().simple().to_string()),
+            max_views: Set(payload.max_views),
+            view_count: Set(0),
+            last_viewed_at: Set(None),
+            expires_at: Set(payload.expires_in_hours.map(|h| now + Duration::hours(h))),
+            expired_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(db.0)
+        .await;
+
+        match share {
+            Ok(share) => CreateShareApiResponse::Ok(Json(ShareOutput::from(share))),
+            Err(e) => CreateShareApiResponse::InternalServerError(ErrorBody::from_db("Failed to create share", &e)),
+        }
+    }
+
+    /// List a memo's share links with their remaining views and time
+    #[oai(path = "/memo/:memo_id/shares", method = "get")]
+    async fn list_shares(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(memo_id): Path<String>,
+    ) -> ListSharesApiResponse {
+        let memo = match find_owned_memo(db.0, auth.0.id, &memo_id).await {
+            Ok(MemoLookup::Found(memo)) => memo,
+            Ok(MemoLookup::InvalidId) => return ListSharesApiResponse::BadRequest(ErrorBody::new("invalid_memo_id", "Invalid memo ID")),
+            Ok(MemoLookup::NotFound) => return ListSharesApiResponse::NotFound(ErrorBody::new("not_found", "Memo not found or access denied")),
+            Err(e) => return ListSharesApiResponse::InternalServerError(ErrorBody::from_db("Failed to load memo", &e)),
+        };
+
+        let shares = memo_shares::Entity::find()
+            .filter(memo_shares::Column::MemoId.eq(memo.id))
+            .order_by_desc(memo_shares::Column::CreatedAt)
+            .all(db.0)
+            .await;
+
+        match shares {
+            Ok(shares) => ListSharesApiResponse::Ok(Json(shares.into_iter().map(ShareOutput::from).collect())),
+            Err(e) => ListSharesApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch shares", &e)),
+        }
+    }
+
+    /// Public view of a shared memo. Each successful load counts as one view.
+    #[oai(path = "/shared/:slug", method = "get")]
+    async fn get_shared_memo(
+        &self,
+        db: Data<&DatabaseConnection>,
+        Path(slug): Path<String>,
+    ) -> SharedMemoApiResponse {
+        let share = match record_view(db.0, &slug).await {
+            Ok(ViewOutcome::Counted(share)) => share,
+            Ok(ViewOutcome::Missing) => return SharedMemoApiResponse::NotFound(ErrorBody::new("not_found", "Share link not found")),
+            Ok(ViewOutcome::Exhausted) => return SharedMemoApiResponse::Gone(ErrorBody::new("share_expired", "This share link has expired")),
+            Err(e) => return SharedMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to record share view", &e)),
+        };
+
+        match shared_memo(db.0, share.memo_id).await {
+            Ok(Some(memo)) => SharedMemoApiResponse::Ok(Json(SharedMemoOutput {
+                title: memo.title,
+                transcript: memo.transcript,
+                summary: memo.summary,
+                tags: memo.tags.and_then(|json_str| serde_json::from_str(&json_str).ok()),
+                duration: memo.duration,
+                created_at: memo.created_at.and_utc().to_rfc3339(),
+                has_audio: memo.audio_blob.is_some() || memo.audio_key.is_some(),
+                remaining_views: share.max_views.map(|max| (max - share.view_count).max(0)),
+            })),
+            Ok(None) => SharedMemoApiResponse::NotFound(ErrorBody::new("not_found", "Share link not found")),
+            Err(e) => SharedMemoApiResponse::InternalServerError(ErrorBody::from_db("Failed to load shared memo", &e)),
+        }
+    }
+
+    /// Audio for a shared memo. Fetching it right after loading the page does not use up another view.
+    #[oai(path = "/shared/:slug/audio", method = "get")]
+    async fn get_shared_audio(
+        &self,
+        db: Data<&DatabaseConnection>,
+        store: Data<&AudioStore>,
+        Path(slug): Path<String>,
+    ) -> SharedAudioApiResponse {
+        let share = match memo_shares::Entity::find()
+            .filter(memo_shares::Column::Slug.eq(slug.as_str()))
+            .one(db.0)
+            .await
+        {
+            Ok(Some(share)) => share,
+            Ok(None) => return SharedAudioApiResponse::NotFound(ErrorBody::new("not_found", "Share link not found")),
+            Err(e) => return SharedAudioApiResponse::InternalServerError(ErrorBody::from_db("Failed to load share", &e)),
+        };
+
+        let now = Utc::now().naive_utc();
+        let recently_viewed = share
+            .last_viewed_at
+            .is_some_and(|at| at > now - Duration::minutes(AUDIO_VIEW_WINDOW_MINUTES));
+
+        let share = if share.expires_at.is_some_and(|at| at <= now) {
+            return SharedAudioApiResponse::Gone(ErrorBody::new("share_expired", "This share link has expired"));
+        } else if recently_viewed {
+            share
+        } else {
+            match record_view(db.0, &slug).await {
+                Ok(ViewOutcome::Counted(share)) => share,
+                Ok(ViewOutcome::Missing) => return SharedAudioApiResponse::NotFound(ErrorBody::new("not_found", "Share link not found")),
+                Ok(ViewOutcome::Exhausted) => return SharedAudioApiResponse::Gone(ErrorBody::new("share_expired", "This share link has expired")),
+                Err(e) => return SharedAudioApiResponse::InternalServerError(ErrorBody::from_db("Failed to record share view", &e)),
+            }
+        };
+
+        match shared_memo(db.0, share.memo_id).await {
+            Ok(Some(voice_memos1::Model { audio_blob: Some(audio), .. })) => SharedAudioApiResponse::Ok(Binary(audio)),
+            Ok(Some(voice_memos1::Model { audio_key: Some(key), .. })) => match store.0.url(&key) {
+                Ok(url) => SharedAudioApiResponse::TemporaryRedirect(url),
+                Err(e) => {
+                    tracing::error!("Failed to serve shared audio: {}", e);
+                    SharedAudioApiResponse::InternalServerError(ErrorBody::new("internal_error", "An internal error occurred"))
+                }
+            },
+            Ok(_) => SharedAudioApiResponse::NotFound(ErrorBody::new("not_found", "No audio for this memo")),
+            Err(e) => SharedAudioApiResponse::InternalServerError(ErrorBody::from_db("Failed to load shared audio", &e)),
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+impl From<memo_shares::Model> for ShareOutput {
+    fn from(share: memo_shares::Model) -> Self {
+        let now = Utc::now().naive_utc();
+        ShareOutput {
+            path: format!("/api/shared/{}", share.slug),
+            slug: share.slug,
+            view_count: share.view_count,
+            max_views: share.max_views,
+            remaining_views: share.max_views.map(|max| (max - share.view_count).max(0)),
+            expires_at: share.expires_at.map(|at| at.and_utc().to_rfc3339()),
+            remaining_seconds: share.expires_at.map(|at| (at - now).num_seconds().max(0)),
+            expired_at: share.expired_at.map(|at| at.and_utc().to_rfc3339()),
+            created_at: share.created_at.and_utc().to_rfc3339(),
+        }
+    }
+}
+
+/// What one owner lost in a sweep, so they get a single message per run.
+#[derive(Serialize)]
+pub struct ExpiredSharesNotice {
+    pub user_id: Uuid,
+    pub memo_titles: Vec<String>,
+}
+
+/// Marks every share that ran out of time or views as expired, grouped by memo owner.
+/// Only rows not yet marked are touched, which the partial indexes on `memo_shares` cover.
+pub async fn sweep_expired_shares(db: &DatabaseConnection) -> Result<Vec<ExpiredSharesNotice>, DbErr> {
+    let now = Utc::now().naive_utc();
+    let expired = memo_shares::Entity::update_many()
+        .col_expr(memo_shares::Column::ExpiredAt, Expr::value(now))
+        .filter(memo_shares::Column::ExpiredAt.is_null())
+        .filter(
+            Condition::any()
+                .add(
+                    Condition::all()
+                        .add(memo_shares::Column::ExpiresAt.is_not_null())
+                        .add(memo_shares::Column::ExpiresAt.lte(now)),
+                )
+                .add(
+                    Condition::all()
+                        .add(memo_shares::Column::MaxViews.is_not_null())
+                        .add(Expr::col(memo_shares::Column::ViewCount).gte(Expr::col(memo_shares::Column::MaxViews))),
+                ),
+        )
+        .exec_with_returning(db)
+        .await?;
+
+    if expired.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let memos: Vec<(Uuid, Uuid, String)> = voice_memos1::Entity::find()
+        .select_only()
+        .column(voice_memos1::Column::Id)
+        .column(voice_memos1::Column::UserId)
+        .column(voice_memos1::Column::Title)
+        .filter(voice_memos1::Column::Id.is_in(expired.iter().map(|share| share.memo_id)))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    let mut by_owner: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for share in &expired {
+        if let Some((_, user_id, title)) = memos.iter().find(|(id, _, _)| *id == share.memo_id) {
+            by_owner.entry(*user_id).or_default().push(title.clone());
+        }
+    }
+
+    Ok(by_owner
+        .into_iter()
+        .map(|(user_id, memo_titles)| ExpiredSharesNotice { user_id, memo_titles })
+        .collect())
+}
+
+enum ViewOutcome {
+    Counted(memo_shares::Model),
+    Missing,
+    Exhausted,
+}
+
+// Counts a view with a single conditional UPDATE ... RETURNING, so concurrent viewers
+// can never push view_count past max_views.
+async fn record_view(db: &DatabaseConnection, slug: &str) -> Result<ViewOutcome, DbErr> {
+    let now: NaiveDateTime = Utc::now().naive_utc();
+    let updated = memo_shares::Entity::update_many()
+        .col_expr(memo_shares::Column::ViewCount, Expr::col(memo_shares::Column::ViewCount).add(1))
+        .col_expr(memo_shares::Column::LastViewedAt, Expr::value(now))
+        .filter(memo_shares::Column::Slug.eq(slug))
+        .filter(
+            Condition::any()
+                .add(memo_shares::Column::ExpiresAt.is_null())
+                .add(memo_shares::Column::ExpiresAt.gt(now)),
+        )
+        .filter(
+            Condition::any()
+                .add(memo_shares::Column::MaxViews.is_null())
+                .add(Expr::col(memo_shares::Column::ViewCount).lt(Expr::col(memo_shares::Column::MaxViews))),
+        )
+        .exec_with_returning(db)
+        .await?;
+
+    if let Some(share) = updated.into_iter().next() {
+        return Ok(ViewOutcome::Counted(share));
+    }
+
+    let exists = memo_shares::Entity::find()
+        .filter(memo_shares::Column::Slug.eq(slug))
+        .one(db)
+        .await?
+        .is_some();
+    Ok(if exists { ViewOutcome::Exhausted } else { ViewOutcome::Missing })
+}
+
+async fn shared_memo(db: &DatabaseConnection, memo_id: Uuid) -> Result<Option<voice_memos1::Model>, DbErr> {
+    voice_memos1::Entity::find_by_id(memo_id)
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .one(db)
+        .await
+}
+
+enum MemoLookup {
+    Found(Box<voice_memos1::Model>),
+    InvalidId,
+    NotFound,
+}
+
+// The caller's memo named in the path; trashed memos can't be shared.
+async fn find_owned_memo(db: &DatabaseConnection, user_id: Uuid, memo_id: &str) -> Result<MemoLookup, DbErr> {
+    let Ok(memo_uuid) = Uuid::parse_str(memo_id) else {
+        return Ok(MemoLookup::InvalidId);
+    };
+
+    let memo = voice_memos1::Entity::find_by_id(memo_uuid)
+        .filter(voice_memos1::Column::UserId.eq(user_id))
+        .filter(voice_memos1::Column::DeletedAt.is_null())
+        .one(db)
+        .await?;
+    Ok(memo.map_or(MemoLookup::NotFound, |memo| MemoLookup::Found(Box::new(memo))))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join_all;
+    use poem::http::StatusCode;
+
+    use super::*;
+    use crate::test_support::{new_memo, seed_user, test_app, test_db};
+
+    #[tokio::test]
+    async fn simultaneous_final_views_admit_exactly_one() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let memo = new_memo(&user, "Shared once more").insert(&db).await.unwrap();
+        let share = memo_shares::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            memo_id: Set(memo.id),
+            slug: Set(Uuid::new_v4().simple().to_string()),
+            max_views: Set(Some(3)),
+            view_count: Set(2),
+            last_viewed_at: Set(None),
+            expires_at: Set(None),
+            expired_at: Set(None),
+            created_at: Set(Utc::now().naive_utc()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let path = format!("/api/shared/{}", share.slug);
+        let responses = join_all((0..8).map(|_| client.get(&path).send())).await;
+        let mut statuses: Vec<StatusCode> = responses.iter().map(|resp| resp.0.status()).collect();
+        statuses.sort();
+        let mut expected = vec![StatusCode::GONE; 7];
+        expected.insert(0, StatusCode::OK);
+        assert_eq!(statuses, expected);
+
+        let share = memo_shares::Entity::find_by_id(share.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(share.view_count, 3);
+    }
+}
@@ -0,0 +1,282 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use chrono::Utc;
+use poem::web::Data;
+use poem_openapi::{payload::Json, param::Path, ApiResponse, Enum, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::Expr, DatabaseConnection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use entity::{api_tokens, users};
+use crate::api::auth::{ensure_not_suspended, user_from_session_token, SessionAuth};
+use crate::api::error::ErrorBody;
+
+// --- Constants ---
+/// Bearer values starting with this are personal access tokens rather than JWTs
+pub const TOKEN_PREFIX: &str = "smk_";
+const SECRET_BYTES: usize = 32;
+// How much of the secret is kept in clear so users can tell their tokens apart
+const DISPLAY_PREFIX_LEN: usize = 8;
+
+// --- API Structs ---
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Scope {
+    #[oai(rename = "memos:read")]
+    MemosRead,
+    #[oai(rename = "memos:write")]
+    MemosWrite,
+    #[oai(rename = "ai:invoke")]
+    AiInvoke,
+}
+
+impl Scope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::MemosRead => "memos:read",
+            Scope::MemosWrite => "memos:write",
+            Scope::AiInvoke => "ai:invoke",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Scope> {
+        [Scope::MemosRead, Scope::MemosWrite, Scope::AiInvoke]
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+    }
+}
+
+#[derive(Object, Debug, Deserialize)]
+pub struct CreateTokenPayload {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Object, Serialize)]
+pub struct TokenOutput {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Object, Serialize)]
+pub struct CreatedTokenResponse {
+    /// The full token. It is only ever shown in this response.
+    pub token: String,
+    #[oai(flatten)]
+    pub details: TokenOutput,
+}
+
+#[derive(Object, Serialize)]
+pub struct RevokeTokenResponse {
+    pub message: String,
+}
+
+// --- API Responses ---
+#[derive(ApiResponse)]
+enum CreateTokenApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<CreatedTokenResponse>),
+    /// No name or no scopes
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum ListTokensApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<TokenOutput>>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+enum RevokeTokenApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<RevokeTokenResponse>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Missing, invalid or expired token; sent by the auth scheme before the handler runs
+    #[oai(status = 401)]
+    #[allow(dead_code)] // Only here to document the response
+    Unauthorized(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorBody>),
+}
+
+// --- API Definition ---
+pub struct TokenApi;
+
+// Managing tokens needs a real login; a personal access token can't mint or revoke tokens.
+#[OpenApi]
+impl TokenApi {
+    /// Create a named personal access token for scripts and the browser extension
+    #[oai(path = "/tokens", method = "post")]
+    async fn create_token(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<CreateTokenPayload>,
+    ) -> CreateTokenApiResponse {
+        let user = auth.0;
+
+        let name = payload.name.trim();
+        if name.is_empty() || payload.scopes.is_empty() {
+            return CreateTokenApiResponse::BadRequest(ErrorBody::new("missing_fields", "name and at least one scope are required"));
+        }
+
+        let mut secret = [0u8; SECRET_BYTES];
+        OsRng.fill_bytes(&mut secret);
+        let secret_hex: String = secret.iter().map(|b| format!("{:02x}", b)).collect();
+        let token = format!("{}{}", TOKEN_PREFIX, secret_hex);
+
+        let mut scopes: Vec<&str> = payload.scopes.iter().map(|scope| scope.as_str()).collect();
+        scopes.sort_unstable();
+        scopes.dedup();
+
+        let saved = api_tokens::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            name: Set(name.to_string()),
+            prefix: Set(token[..TOKEN_PREFIX.len() + DISPLAY_PREFIX_LEN].to_string()),
+            token_hash: Set(hash_token(&token)),
+            scopes: Set(scopes.join(",")),
+            created_at: Set(Utc::now().naive_utc()),
+            last_used_at: Set(None),
+        }
+        .insert(db.0)
+        .await;
+
+        match saved {
+            Ok(saved) => CreateTokenApiResponse::Ok(Json(CreatedTokenResponse { token, details: TokenOutput::from(saved) })),
+            Err(e) => CreateTokenApiResponse::InternalServerError(ErrorBody::from_db("Failed to create token", &e)),
+        }
+    }
+
+    /// List the caller's personal access tokens (never the secrets)
+    #[oai(path = "/tokens", method = "get")]
+    async fn list_tokens(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> ListTokensApiResponse {
+        let user = auth.0;
+
+        let tokens = api_tokens::Entity::find()
+            .filter(api_tokens::Column::UserId.eq(user.id))
+            .order_by_desc(api_tokens::Column::CreatedAt)
+            .all(db.0)
+            .await;
+
+        match tokens {
+            Ok(tokens) => ListTokensApiResponse::Ok(Json(tokens.into_iter().map(TokenOutput::from).collect())),
+            Err(e) => ListTokensApiResponse::InternalServerError(ErrorBody::from_db("Failed to fetch tokens", &e)),
+        }
+    }
+
+    /// Revoke a personal access token. It stops working immediately.
+    #[oai(path = "/tokens/:id", method = "delete")]
+    async fn revoke_token(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Path(id): Path<String>,
+    ) -> RevokeTokenApiResponse {
+        let user = auth.0;
+        let token_id = match Uuid::parse_str(&id) {
+            Ok(id) => id,
+            Err(_) => return RevokeTokenApiResponse::BadRequest(ErrorBody::new("invalid_token_id", "Invalid token ID")),
+        };
+
+        let result = api_tokens::Entity::delete_many()
+            .filter(api_tokens::Column::Id.eq(token_id))
+            .filter(api_tokens::Column::UserId.eq(user.id))
+            .exec(db.0)
+            .await;
+
+        match result {
+            Ok(result) if result.rows_affected == 0 => RevokeTokenApiResponse::NotFound(ErrorBody::new("not_found", "Token not found")),
+            Ok(_) => RevokeTokenApiResponse::Ok(Json(RevokeTokenResponse { message: "Token revoked".to_string() })),
+            Err(e) => RevokeTokenApiResponse::InternalServerError(ErrorBody::from_db("Failed to revoke token", &e)),
+        }
+    }
+}
+
+// --- Helper Functions ---
+
+impl From<api_tokens::Model> for TokenOutput {
+    fn from(token: api_tokens::Model) -> Self {
+        TokenOutput {
+            id: token.id.to_string(),
+            name: token.name,
+            prefix: token.prefix,
+            scopes: token.scopes.split(',').filter_map(Scope::parse).collect(),
+            created_at: token.created_at.and_utc().to_rfc3339(),
+            last_used_at: token.last_used_at.map(|at| at.and_utc().to_rfc3339()),
+        }
+    }
+}
+
+pub fn is_personal_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+/// Resolves the caller of an endpoint group: a session JWT, or a personal access token that carries
+/// every one of `scopes`. Handlers get this through the schemes in `auth`.
+pub async fn authorize_user_for(token: &str, db: &DatabaseConnection, scopes: &[Scope]) -> Result<users::Model, String> {
+    if !is_personal_token(token) {
+        return user_from_session_token(token, db).await;
+    }
+
+    let record = api_tokens::Entity::find()
+        .filter(api_tokens::Column::TokenHash.eq(hash_token(token)))
+        .one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while resolving access token: {:?}", e);
+            "Failed to verify token due to a database error".to_string()
+        })?
+        .ok_or_else(|| "Invalid or revoked token".to_string())?;
+
+    if let Some(missing) = scopes.iter().find(|scope| !record.scopes.split(',').any(|s| s == scope.as_str())) {
+        return Err(format!("Token lacks the {} scope", missing.as_str()));
+    }
+
+    // Best-effort bookkeeping; a failed timestamp update shouldn't fail the request
+    if let Err(e) = api_tokens::Entity::update_many()
+        .col_expr(api_tokens::Column::LastUsedAt, Expr::value(Utc::now().naive_utc()))
+        .filter(api_tokens::Column::Id.eq(record.id))
+        .exec(db)
+        .await
+    {
+        tracing::warn!("Failed to record token use: {:?}", e);
+    }
+
+    let user = users::Entity::find_by_id(record.user_id)
+        .one(db)
+        .await
+        .map_err(|_| "Failed to verify user due to a database error".to_string())?
+        .ok_or_else(|| "Invalid or revoked token".to_string())?;
+    ensure_not_suspended(&user)?;
+    Ok(user)
+}
+
+// The secret is 256 random bits, so a plain SHA-256 is enough and keeps lookups a single indexed query.
+pub(crate) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,164 @@
+use chrono::{NaiveDateTime, Utc};
+use poem::{web::Data, Result};
+use poem_openapi::{payload::Json, Object, OpenApi};
+use sea_orm::{entity::*, query::*, DatabaseConnection, DbErr};
+use serde::Serialize;
+use uuid::Uuid;
+
+use entity::ai_usage;
+use crate::api::auth::SessionAuth;
+use crate::api::limits::positive_env;
+
+// --- Constants ---
+const DEFAULT_AI_DAILY_QUOTA: usize = 500;
+
+// --- API Structs ---
+
+/// An AI call that reaches Gemini. Sandbox output is free and never counted.
+#[derive(Debug, Clone, Copy)]
+pub enum AiOperation {
+    Transcribe,
+    Translate,
+    Summary,
+    Title,
+    SuggestLinks,
+    Extract,
+}
+
+impl AiOperation {
+    fn as_str(self) -> &'static str {
+        match self {
+            AiOperation::Transcribe => "transcribe",
+            AiOperation::Translate => "translate",
+            AiOperation::Summary => "summary",
+            AiOperation::Title => "title",
+            AiOperation::SuggestLinks => "suggest_links",
+            AiOperation::Extract => "extract",
+        }
+    }
+}
+
+#[derive(Object, Serialize, Default)]
+pub struct UsageCounts {
+    pub transcribe: u64,
+    pub translate: u64,
+    pub summary: u64,
+    pub title: u64,
+    pub suggest_links: u64,
+    pub extract: u64,
+}
+
+#[derive(Object, Serialize)]
+pub struct UsageResponse {
+    /// The UTC day being counted, e.g. `2025-09-08`
+    pub date: String,
+    pub used: u64,
+    pub limit: u64,
+    pub remaining: u64,
+    /// When the count starts over
+    pub resets_at: String,
+    pub counts: UsageCounts,
+}
+
+#[derive(Debug)]
+pub enum QuotaError {
+    Exceeded { limit: u64 },
+    Db(DbErr),
+}
+
+// --- API Definition ---
+pub struct UsageApi;
+
+#[OpenApi]
+impl UsageApi {
+    /// Today's AI calls for the caller, against the daily quota
+    #[oai(path = "/usage", method = "get")]
+    async fn get_usage(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> Result<Json<UsageResponse>> {
+        let user = auth.0;
+
+        let rows: Vec<(String, i64)> = ai_usage::Entity::find()
+            .select_only()
+            .column(ai_usage::Column::Operation)
+            .column_as(ai_usage::Column::Id.count(), "count")
+            .filter(ai_usage::Column::UserId.eq(user.id))
+            .filter(ai_usage::Column::CreatedAt.gte(start_of_today()))
+            .group_by(ai_usage::Column::Operation)
+            .into_tuple()
+            .all(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?;
+
+        let mut counts = UsageCounts::default();
+        for (operation, count) in rows {
+            let slot = match operation.as_str() {
+                "transcribe" => &mut counts.transcribe,
+                "translate" => &mut counts.translate,
+                "summary" => &mut counts.summary,
+                "title" => &mut counts.title,
+                "suggest_links" => &mut counts.suggest_links,
+                "extract" => &mut counts.extract,
+                _ => continue,
+            };
+            *slot = count as u64;
+        }
+
+        let used = counts.transcribe + counts.translate + counts.summary + counts.title + counts.suggest_links + counts.extract;
+        let limit = daily_quota();
+        let today = Utc::now().date_naive();
+
+        Ok(Json(UsageResponse {
+            date: today.to_string(),
+            used,
+            limit,
+            remaining: limit.saturating_sub(used),
+            resets_at: (start_of_today() + chrono::Duration::days(1)).and_utc().to_rfc3339(),
+            counts,
+        }))
+    }
+}
+
+// --- Helper Functions ---
+
+/// Daily AI calls allowed per user, from `AI_DAILY_QUOTA` (default 500).
+pub fn daily_quota() -> u64 {
+    positive_env("AI_DAILY_QUOTA", DEFAULT_AI_DAILY_QUOTA).unwrap_or(DEFAULT_AI_DAILY_QUOTA) as u64
+}
+
+/// Records an AI call for the user, or refuses it once today's quota is used up.
+///
+/// Count and insert are separate statements, so concurrent calls can overshoot by a few; the
+/// quota protects the upstream API from runaway clients, not to the exact request.
+pub async fn consume_quota(db: &DatabaseConnection, user_id: Uuid, operation: AiOperation) -> Result<(), QuotaError> {
+    let limit = daily_quota();
+    let used = ai_usage::Entity::find()
+        .filter(ai_usage::Column::UserId.eq(user_id))
+        .filter(ai_usage::Column::CreatedAt.gte(start_of_today()))
+        .count(db)
+        .await
+        .map_err(QuotaError::Db)?;
+
+    if used >= limit {
+        return Err(QuotaError::Exceeded { limit });
+    }
+
+    ai_usage::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        operation: Set(operation.as_str().to_string()),
+        created_at: Set(Utc::now().naive_utc()),
+    }
+    .insert(db)
+    .await
+    .map_err(QuotaError::Db)?;
+
+    Ok(())
+}
+
+// Quotas reset at midnight UTC
+fn start_of_today() -> NaiveDateTime {
+    Utc::now().date_naive().and_hms_opt(0, 0, 0).expect("midnight is a valid time")
+}
@@ -1,25 +1,50 @@
 // In your Cargo.toml, you need to add the validator crate:
 // validator = { version = "0.16", features = ["derive"] }
 
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header };
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use chrono::{DateTime, Duration, Utc};
 use poem::{
-    error::{BadRequest, Conflict, Unauthorized},
+    error::{BadRequest, Conflict, NotFound, Unauthorized},
+    http::{header, StatusCode},
     web::Data,
+    IntoResponse,
+    Request,
     Result,
 };
-use poem_openapi::{ payload::Json, Object, OpenApi};
-use sea_orm::{entity::*, query::*, DatabaseConnection, Set};
+use poem_openapi::{ param::Query, payload::Json, ApiResponse, Object, OpenApi};
+use sea_orm::{entity::*, query::*, sea_query::{Expr, Func, SimpleExpr}, DatabaseConnection, DbErr, Set, SqlErr};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate; // Import the validation trait
+use validator::{Validate, ValidationError}; // Import the validation trait
 
+use entity::password_resets;
 use entity::users::{self, Entity as Users};
 use std::error::Error as StdError;
 use std::fmt;
+use std::sync::LazyLock;
 use bcrypt::{hash, DEFAULT_COST, verify};
+use crate::api::jwt;
+use crate::api::auth::SessionAuth;
+use crate::api::challenge::{ChallengeError, SignupChallenge};
+use crate::api::error::{ErrorBody, ValidationErrorResponse};
+use crate::api::mailer::{self, Mail};
+use crate::api::tokens::hash_token;
+use crate::api::login_limit::{clear_account_failures, locked_until, record_account_failure, LockoutPolicy, LoginLimiter};
+use crate::api::sessions::{client_ip, start_session};
+use crate::api::password::{enforce_password_policy, password_complexity, PasswordPolicyResponse};
 
 
+// --- Constants ---
+const EMAIL_CHANGE_TTL_HOURS: i64 = 24;
+const EMAIL_CHANGE_TOKEN_BYTES: usize = 32;
+const VERIFY_EMAIL_TTL_HOURS: i64 = 24;
+const VERIFY_EMAIL_PURPOSE: &str = "verify_email";
+const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+const PASSWORD_RESET_TOKEN_BYTES: usize = 32;
+// Checked against when no account matches a login, so a miss costs as long as a wrong password
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| hash("not-a-real-password", DEFAULT_COST).expect("bcrypt can hash a constant"));
+
 // --- Custom Error for Poem ---
 #[derive(Debug)]
 struct ApiError(String);
@@ -37,30 +62,180 @@ impl StdError for ApiError {}
 
 #[derive(Object, Deserialize, Validate)] // Derive Validate for the payload
 pub struct SignupPayload {
-    #[validate(length(min = 3, message = "Username must be at least 3 characters long"))]
+    /// Unique ignoring case, like at login; stored with the casing given, minus surrounding whitespace.
+    /// May not contain `@`, which login reserves for email addresses.
+    #[validate(
+        length(min = 3, message = "Username must be at least 3 characters long"),
+        custom = "username_format"
+    )]
     username: String,
     #[validate(email(message = "Please provide a valid email address"))]
     email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    #[validate(
+        length(min = 8, message = "Password must be at least 8 characters long"),
+        custom = "password_complexity"
+    )]
     password: String,
+    /// Also log the new user in and return a token
+    auto_login: Option<bool>,
+    /// Captcha token; required when the server has a signup challenge configured
+    challenge_token: Option<String>,
 }
 
 #[derive(Object, Serialize)]
 pub struct SignupResponse {
     message: String,
     user_id: String,
+    #[oai(skip_serializing_if_is_none)]
+    token: Option<String>,
+    #[oai(skip_serializing_if_is_none)]
+    expires_at: Option<String>,
+    /// Only returned when the server runs with `MAIL_DEV_MODE`; otherwise it is mailed
+    #[oai(skip_serializing_if_is_none)]
+    verification_token: Option<String>,
+}
+
+#[derive(Object, Serialize)]
+pub struct VerifyEmailResponse {
+    message: String,
+    email: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct ResendVerificationResponse {
+    message: String,
+    /// Only returned when the server runs with `MAIL_DEV_MODE`
+    #[oai(skip_serializing_if_is_none)]
+    verification_token: Option<String>,
 }
 
 #[derive(Object, Deserialize)]
 pub struct LoginPayload {
-    email: String,
+    /// Email address or username
+    identifier: Option<String>,
+    /// Deprecated: use `identifier`
+    email: Option<String>,
     password: String,
+    /// Issue a long-lived token for a trusted device
+    remember_me: Option<bool>,
 }
 
 #[derive(Object, Serialize)]
 pub struct LoginResponse {
     message: String,
+    /// Send as `Authorization: Bearer <token>`
     token: String,
+    /// RFC 3339; the token's `exp`
+    expires_at: String,
+    /// The logged-in user, so clients don't have to decode the token
+    user: UserProfile,
+}
+
+#[derive(Object, Serialize)]
+pub struct UserProfile {
+    id: String,
+    username: String,
+    email: String,
+    created_at: String,
+    /// False until the address is verified with `/verify_email`; clients should prompt for it
+    email_verified: bool,
+}
+
+#[derive(Object, Deserialize, Validate)]
+pub struct ChangePasswordPayload {
+    current_password: String,
+    #[validate(
+        length(min = 8, message = "Password must be at least 8 characters long"),
+        custom = "password_complexity"
+    )]
+    new_password: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct ChangePasswordResponse {
+    message: String,
+}
+
+#[derive(Object, Deserialize)]
+pub struct PasswordResetRequestPayload {
+    email: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct PasswordResetRequestResponse {
+    message: String,
+    /// Only returned when the server runs with `MAIL_DEV_MODE` and the account exists
+    #[oai(skip_serializing_if_is_none)]
+    reset_token: Option<String>,
+}
+
+#[derive(Object, Deserialize, Validate)]
+pub struct PasswordResetConfirmPayload {
+    token: String,
+    #[validate(
+        length(min = 8, message = "Password must be at least 8 characters long"),
+        custom = "password_complexity"
+    )]
+    new_password: String,
+}
+
+#[derive(Object, Deserialize, Validate)]
+pub struct UpdateMePayload {
+    /// New email address. It only takes effect once confirmed from that address.
+    #[validate(email(message = "Please provide a valid email address"))]
+    email: Option<String>,
+    /// Required when changing the email
+    current_password: Option<String>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct ConfirmEmailPayload {
+    token: String,
+}
+
+#[derive(Object, Serialize)]
+pub struct MeResponse {
+    message: String,
+    /// The address used to log in; unchanged until a pending change is confirmed
+    email: String,
+    #[oai(skip_serializing_if_is_none)]
+    pending_email: Option<String>,
+    #[oai(skip_serializing_if_is_none)]
+    pending_expires_at: Option<String>,
+    /// Only returned when the server runs with `MAIL_DEV_MODE`
+    #[oai(skip_serializing_if_is_none)]
+    confirmation_token: Option<String>,
+}
+
+#[derive(ApiResponse)]
+enum UpdateMeApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<MeResponse>),
+    #[oai(status = 422)]
+    ValidationFailed(Json<ValidationErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+enum SignupApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<SignupResponse>),
+    // Shares 400 with WeakPassword; the spec can only show one body, so WeakPassword is declared last
+    #[oai(status = 400)]
+    ChallengeFailed(Json<ErrorBody>),
+    #[oai(status = 400)]
+    WeakPassword(Json<PasswordPolicyResponse>),
+    #[oai(status = 422)]
+    ValidationFailed(Json<ValidationErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+enum ChangePasswordApiResponse {
+    #[oai(status = 200)]
+    Ok(Json<ChangePasswordResponse>),
+    #[oai(status = 400)]
+    WeakPassword(Json<PasswordPolicyResponse>),
+    #[oai(status = 422)]
+    ValidationFailed(Json<ValidationErrorResponse>),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -68,6 +243,27 @@ struct Claims {
     sub: String,
     username:String,
     email: String,
+    role: String,
+    iss: String,
+    aud: String,
+    exp: usize,
+    /// Session row this token belongs to, so it can be revoked
+    sid: String,
+    /// Unique per token, so `/logout` can revoke just this one
+    jti: String,
+    /// `users.token_version` at issuance; bumping it invalidates the token
+    ver: i32,
+}
+
+// Mailed at signup. Stateless: the email is included, so a token for an address the account
+// has since moved away from doesn't verify the new one.
+#[derive(Debug, Serialize, Deserialize)]
+struct VerifyEmailClaims {
+    sub: String,
+    email: String,
+    purpose: String,
+    iss: String,
+    aud: String,
     exp: usize,
 }
 
@@ -79,15 +275,51 @@ impl UserApi {
     #[oai(path = "/signup", method = "post")]
     async fn signup(
         &self,
+        req: &Request,
         db: Data<&DatabaseConnection>,
-        Json(payload): Json<SignupPayload>,
-    ) -> Result<Json<SignupResponse>> {
+        challenge: Data<&SignupChallenge>,
+        mail: Data<&Mail>,
+        Json(mut payload): Json<SignupPayload>,
+    ) -> Result<SignupApiResponse> {
+        // Trimmed first, so the length rule applies to what is stored
+        payload.username = payload.username.trim().to_string();
+
         // 1. Validate the incoming payload based on the rules in the struct
-        payload.validate().map_err(BadRequest)?;
+        if let Err(errors) = payload.validate() {
+            return Ok(SignupApiResponse::ValidationFailed(Json(errors.into())));
+        }
+
+        // Bot protection, when configured, before touching the database
+        if let Some(verifier) = &challenge.0.0 {
+            let Some(token) = payload.challenge_token.as_deref().filter(|t| !t.is_empty()) else {
+                return Ok(SignupApiResponse::ChallengeFailed(ErrorBody::new("challenge_required", "A challenge token is required")));
+            };
+            match verifier.verify(token, client_ip(req).as_deref()).await {
+                Ok(()) => {}
+                Err(ChallengeError::Rejected) => {
+                    return Ok(SignupApiResponse::ChallengeFailed(ErrorBody::new("challenge_failed", "Challenge verification failed")));
+                }
+                Err(ChallengeError::Unavailable(e)) => {
+                    tracing::warn!("Signup challenge provider unavailable: {}", e);
+                    return Ok(SignupApiResponse::ChallengeFailed(ErrorBody::new("challenge_unavailable", "Challenge could not be verified, please try again")));
+                }
+            }
+        }
+
+        let issues = enforce_password_policy(&payload.password, &payload.username, &payload.email);
+        if !issues.is_empty() {
+            return Ok(SignupApiResponse::WeakPassword(Json(PasswordPolicyResponse {
+                message: "Password does not meet the password policy".to_string(),
+                issues,
+            })));
+        }
+
+        // Validation ran on the raw input; from here on the email is compared and stored normalized
+        let email = normalize_email(&payload.email);
 
         // 2. Check if a user with this email already exists
         let existing_user = Users::find()
-            .filter(users::Column::Email.eq(payload.email.clone()))
+            .filter(email_matches(&email))
             .one(db.0)
             .await
             .map_err(poem::error::InternalServerError)?;
@@ -98,6 +330,9 @@ impl UserApi {
                 "User with this email already exists".to_string(),
             )));
         }
+        if username_taken(db.0, &payload.username).await.map_err(poem::error::InternalServerError)? {
+            return Err(Conflict(ApiError("Username is already taken".to_string())));
+        }
 
         // 3. Hash the password before saving
         let hashed_password = hash(&payload.password, DEFAULT_COST)
@@ -107,68 +342,993 @@ impl UserApi {
         let user = users::ActiveModel {
             id: Set(Uuid::new_v4()),
             username: Set(payload.username),
-            email: Set(payload.email),
+            email: Set(email),
             password: Set(hashed_password),
             created_at: Set(chrono::Utc::now().naive_utc()),
+            role: Set("user".to_string()),
+            sandbox_ai: Set(false),
+            token_version: Set(0),
+            failed_attempts: Set(0),
+            locked_until: Set(None),
+            pending_email: Set(None),
+            email_change_token_hash: Set(None),
+            email_change_expires_at: Set(None),
+            suspended_at: Set(None),
+            suspension_reason: Set(None),
+            is_verified: Set(false),
         };
 
-        let saved = user.insert(db.0).await.map_err(|e| {
-            poem::error::InternalServerError(ApiError(format!("Failed to create user: {}", e)))
+        // A concurrent signup can slip past the checks above; the unique indexes on LOWER(email) and
+        // LOWER(username) catch it, and the index name in the error says which one
+        let saved = user.insert(db.0).await.map_err(|e| match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(constraint)) if constraint.contains("username") => {
+                Conflict(ApiError("Username is already taken".to_string()))
+            }
+            Some(SqlErr::UniqueConstraintViolation(_)) => Conflict(ApiError(
+                "User with this email already exists".to_string(),
+            )),
+            _ => poem::error::InternalServerError(ApiError(format!("Failed to create user: {}", e))),
         })?;
 
-        Ok(Json(SignupResponse {
+        // The account exists either way; a failed mail is only logged, and verification can be resent
+        let _ = mail.deliver(&saved.email, &mailer::welcome(&saved.username)).await;
+        let verification_token = issue_verification_token(&saved)?;
+        let _ = mail.deliver(&saved.email, &mailer::verify_email(&verification_token, VERIFY_EMAIL_TTL_HOURS)).await;
+
+        let (token, expires_at) = if payload.auto_login.unwrap_or(false) {
+            let lifetimes = jwt::TokenLifetimes::from_env()
+                .map_err(|e| poem::error::InternalServerError(ApiError(e)))?;
+            let session_id = start_session(db.0, saved.id, req, lifetimes.access).await.map_err(poem::error::InternalServerError)?;
+            let (token, expires_at) = issue_token(&saved, lifetimes.access, session_id)?;
+            (Some(token), Some(expires_at.to_rfc3339()))
+        } else {
+            (None, None)
+        };
+
+        Ok(SignupApiResponse::Ok(Json(SignupResponse {
             message: "User created successfully".to_string(),
             user_id: saved.id.to_string(),
-        }))
+            token,
+            expires_at,
+            verification_token: mail.dev_mode.then_some(verification_token),
+        })))
     }
 
     #[oai(path = "/login", method = "post")]
     async fn login(
         &self,
+        req: &Request,
         db: Data<&DatabaseConnection>,
+        limiter: Data<&LoginLimiter>,
         Json(payload): Json<LoginPayload>,
     ) -> Result<Json<LoginResponse>> {
-        // Find the user by email
-        let user = Users::find()
-            .filter(users::Column::Email.eq(payload.email.clone()))
+        let identifier = payload
+            .identifier
+            .as_deref()
+            .or(payload.email.as_deref())
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| BadRequest(ApiError("identifier is required".to_string())))?;
+
+        // Locked out even if this attempt would succeed, otherwise guessing could continue through the lockout
+        let ip = client_ip(req);
+        if let Err(wait) = limiter.check(identifier, ip.as_deref()) {
+            return Err(too_many_attempts(wait.as_secs().max(1)));
+        }
+
+        // Anything with an @ is an email; signup keeps @ out of usernames. Both are unique ignoring case,
+        // so at most one account matches.
+        let filter = if identifier.contains('@') { email_matches(identifier) } else { username_matches(identifier) };
+        let account = Users::find()
+            .filter(filter)
             .one(db.0)
             .await
-            .map_err(poem::error::InternalServerError)?
-            .ok_or_else(|| Unauthorized(ApiError("Invalid email or password".to_string())))?;
+            .map_err(poem::error::InternalServerError)?;
 
-        // Verify the password hash
-        let is_valid = verify(&payload.password, &user.password).unwrap_or(false);
+        // A locked account says so rather than "Invalid credentials"
+        let lockout = LockoutPolicy::from_env().map_err(|e| poem::error::InternalServerError(ApiError(e)))?;
+        if let Some(until) = account.as_ref().and_then(locked_until) {
+            return Err(account_locked(until));
+        }
 
-        if is_valid {
-            // If the password is valid, create a JWT token
-            let expiration = Utc::now()
-                .checked_add_signed(Duration::hours(24))
-                .expect("Failed to calculate token expiration")
-                .timestamp();
-
-            let claims = Claims {
-                sub: user.id.to_string(),
-                username:user.username.clone(),
-                email: user.email.clone(),
-                exp: expiration as usize,
-            };
+        // Verify the password hash; a miss is checked against a dummy hash so it takes as long
+        let (user, failed) = match account {
+            Some(account) if password_matches(&payload.password, &account)? => (Some(account), None),
+            Some(account) => (None, Some(account.id)),
+            None => {
+                let _ = verify(&payload.password, &DUMMY_PASSWORD_HASH);
+                (None, None)
+            }
+        };
 
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret("point".as_ref()),
-            )
-            .map_err(|_| poem::error::InternalServerError(ApiError("Failed to create token".to_string())))?;
+        // Same error whichever step failed, so the response doesn't reveal which accounts exist
+        if let Some(user) = user {
+            if user.suspended_at.is_some() {
+                return Err(account_suspended());
+            }
+            limiter.record_success(identifier);
+            clear_account_failures(db.0, &user).await.map_err(poem::error::InternalServerError)?;
+
+            // If the password is valid, create a JWT token
+            let lifetimes = jwt::TokenLifetimes::from_env()
+                .map_err(|e| poem::error::InternalServerError(ApiError(e)))?;
+            let ttl = if payload.remember_me.unwrap_or(false) { lifetimes.remember_me } else { lifetimes.access };
+            let session_id = start_session(db.0, user.id, req, ttl).await.map_err(poem::error::InternalServerError)?;
+            let (token, expires_at) = issue_token(&user, ttl, session_id)?;
 
             Ok(Json(LoginResponse {
                 message: "Login Successful".to_string(),
                 token,
+                expires_at: expires_at.to_rfc3339(),
+                user: UserProfile {
+                    id: user.id.to_string(),
+                    username: user.username,
+                    email: user.email,
+                    created_at: user.created_at.and_utc().to_rfc3339(),
+                    email_verified: user.is_verified,
+                },
             }))
         } else {
+            limiter.record_failure(identifier, ip.as_deref());
+            if let Some(user_id) = failed {
+                record_account_failure(db.0, user_id, &lockout).await.map_err(poem::error::InternalServerError)?;
+            }
+
             // If the password is not valid, return an Unauthorized error
             Err(Unauthorized(ApiError(
-                "Invalid email or password".to_string(),
+                "Invalid credentials".to_string(),
             )))
         }
     }
+
+    /// Verify the caller's email address with the token mailed at signup
+    #[oai(path = "/verify_email", method = "get")]
+    async fn verify_email(
+        &self,
+        db: Data<&DatabaseConnection>,
+        token: Query<String>,
+    ) -> Result<Json<VerifyEmailResponse>> {
+        let invalid = || BadRequest(ApiError("Invalid or expired verification token".to_string()));
+        let claims = jwt::decode_claims::<VerifyEmailClaims>(token.0.trim())
+            .ok()
+            .filter(|claims| claims.purpose == VERIFY_EMAIL_PURPOSE)
+            .ok_or_else(invalid)?;
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| invalid())?;
+
+        let user = Users::find_by_id(user_id)
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .filter(|user| normalize_email(&user.email) == normalize_email(&claims.email))
+            .ok_or_else(invalid)?;
+
+        if user.is_verified {
+            return Ok(Json(VerifyEmailResponse { message: "Email address already verified".to_string(), email: user.email }));
+        }
+
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.is_verified = Set(true);
+        let user = active_user.update(db.0).await.map_err(poem::error::InternalServerError)?;
+
+        Ok(Json(VerifyEmailResponse { message: "Email address verified".to_string(), email: user.email }))
+    }
+
+    /// Mail a new verification token to the caller's address, e.g. after the first one expired
+    #[oai(path = "/me/resend_verification", method = "post")]
+    async fn resend_verification(
+        &self,
+        auth: SessionAuth,
+        mail: Data<&Mail>,
+    ) -> Result<Json<ResendVerificationResponse>> {
+        let user = auth.0.0;
+
+        if user.is_verified {
+            return Err(BadRequest(ApiError("Email address already verified".to_string())));
+        }
+
+        let token = issue_verification_token(&user)?;
+        mail.deliver(&user.email, &mailer::verify_email(&token, VERIFY_EMAIL_TTL_HOURS))
+            .await
+            .map_err(|e| poem::error::InternalServerError(ApiError(format!("Failed to send verification email: {}", e))))?;
+
+        Ok(Json(ResendVerificationResponse {
+            message: "Verification sent".to_string(),
+            verification_token: mail.dev_mode.then_some(token),
+        }))
+    }
+
+    /// Change the password of the authenticated user. Every token issued before the change,
+    /// including the one used for this request, stops working.
+    #[oai(path = "/change_password", method = "post")]
+    async fn change_password(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<ChangePasswordPayload>,
+    ) -> Result<ChangePasswordApiResponse> {
+        let user = auth.0.0;
+
+        if let Err(errors) = payload.validate() {
+            return Ok(ChangePasswordApiResponse::ValidationFailed(Json(errors.into())));
+        }
+
+        if !password_matches(&payload.current_password, &user)? {
+            return Err(Unauthorized(ApiError("Current password is incorrect".to_string())));
+        }
+
+        let issues = enforce_password_policy(&payload.new_password, &user.username, &user.email);
+        if !issues.is_empty() {
+            return Ok(ChangePasswordApiResponse::WeakPassword(Json(PasswordPolicyResponse {
+                message: "Password does not meet the password policy".to_string(),
+                issues,
+            })));
+        }
+
+        let hashed_password = hash(&payload.new_password, DEFAULT_COST)
+            .map_err(|_| poem::error::InternalServerError(ApiError("Failed to hash password".to_string())))?;
+
+        let next_version = user.token_version + 1;
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.password = Set(hashed_password);
+        active_user.token_version = Set(next_version);
+        active_user.update(db.0).await.map_err(|e| {
+            poem::error::InternalServerError(ApiError(format!("Failed to update password: {}", e)))
+        })?;
+
+        Ok(ChangePasswordApiResponse::Ok(Json(ChangePasswordResponse {
+            message: "Password changed successfully".to_string(),
+        })))
+    }
+
+    /// Start a password reset: a single-use token is mailed to the address if an account has it.
+    /// The response is the same either way, so it doesn't reveal which emails are registered.
+    #[oai(path = "/password_reset/request", method = "post")]
+    async fn request_password_reset(
+        &self,
+        db: Data<&DatabaseConnection>,
+        mail: Data<&Mail>,
+        Json(payload): Json<PasswordResetRequestPayload>,
+    ) -> Result<Json<PasswordResetRequestResponse>> {
+        let response = |reset_token| {
+            Json(PasswordResetRequestResponse {
+                message: "If an account uses this email, a reset code has been sent to it".to_string(),
+                reset_token,
+            })
+        };
+
+        let user = Users::find()
+            .filter(email_matches(&payload.email))
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?;
+        // A suspended account would only be turned away at login anyway
+        let Some(user) = user.filter(|user| user.suspended_at.is_none()) else {
+            return Ok(response(None));
+        };
+
+        let mut secret = [0u8; PASSWORD_RESET_TOKEN_BYTES];
+        OsRng.fill_bytes(&mut secret);
+        let token: String = secret.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let now = Utc::now().naive_utc();
+        password_resets::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            token_hash: Set(hash_token(&token)),
+            created_at: Set(now),
+            expires_at: Set(now + Duration::minutes(PASSWORD_RESET_TTL_MINUTES)),
+            used_at: Set(None),
+        }
+        .insert(db.0)
+        .await
+        .map_err(poem::error::InternalServerError)?;
+
+        // Failing here would tell the caller the account exists; the failure is logged by `deliver`
+        let _ = mail.deliver(&user.email, &mailer::password_reset(&token, PASSWORD_RESET_TTL_MINUTES)).await;
+
+        Ok(response(mail.dev_mode.then_some(token)))
+    }
+
+    /// Set a new password with a reset token. The token is used up, and every existing session is logged out.
+    #[oai(path = "/password_reset/confirm", method = "post")]
+    async fn confirm_password_reset(
+        &self,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<PasswordResetConfirmPayload>,
+    ) -> Result<ChangePasswordApiResponse> {
+        if let Err(errors) = payload.validate() {
+            return Ok(ChangePasswordApiResponse::ValidationFailed(Json(errors.into())));
+        }
+
+        let invalid = || BadRequest(ApiError("Invalid or expired reset token".to_string()));
+        let now = Utc::now().naive_utc();
+        let reset = password_resets::Entity::find()
+            .filter(password_resets::Column::TokenHash.eq(hash_token(payload.token.trim())))
+            .filter(password_resets::Column::ExpiresAt.gt(now))
+            .filter(password_resets::Column::UsedAt.is_null())
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .ok_or_else(invalid)?;
+        let user = Users::find_by_id(reset.user_id)
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .ok_or_else(invalid)?;
+
+        let issues = enforce_password_policy(&payload.new_password, &user.username, &user.email);
+        if !issues.is_empty() {
+            return Ok(ChangePasswordApiResponse::WeakPassword(Json(PasswordPolicyResponse {
+                message: "Password does not meet the password policy".to_string(),
+                issues,
+            })));
+        }
+
+        let hashed_password = hash(&payload.new_password, DEFAULT_COST)
+            .map_err(|_| poem::error::InternalServerError(ApiError("Failed to hash password".to_string())))?;
+
+        let txn = db.0.begin().await.map_err(poem::error::InternalServerError)?;
+        // Claiming the token first means two concurrent confirms can't both use it
+        let claimed = password_resets::Entity::update_many()
+            .col_expr(password_resets::Column::UsedAt, Expr::value(now))
+            .filter(password_resets::Column::Id.eq(reset.id))
+            .filter(password_resets::Column::UsedAt.is_null())
+            .exec(&txn)
+            .await
+            .map_err(poem::error::InternalServerError)?;
+        if claimed.rows_affected == 0 {
+            return Err(invalid());
+        }
+        // Any other outstanding tokens for the account go too
+        password_resets::Entity::delete_many()
+            .filter(password_resets::Column::UserId.eq(user.id))
+            .filter(password_resets::Column::UsedAt.is_null())
+            .exec(&txn)
+            .await
+            .map_err(poem::error::InternalServerError)?;
+
+        // Whoever forgot the password has been locked out by their own attempts; that no longer applies
+        let next_version = user.token_version + 1;
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.password = Set(hashed_password);
+        active_user.token_version = Set(next_version);
+        active_user.failed_attempts = Set(0);
+        active_user.locked_until = Set(None);
+        active_user.update(&txn).await.map_err(poem::error::InternalServerError)?;
+        txn.commit().await.map_err(poem::error::InternalServerError)?;
+
+        Ok(ChangePasswordApiResponse::Ok(Json(ChangePasswordResponse {
+            message: "Password reset successfully".to_string(),
+        })))
+    }
+
+    /// Update the caller's account. A new email is staged and a confirmation token is sent to it;
+    /// login keeps using the current email until `/me/confirm_email` is called with that token.
+    #[oai(path = "/me", method = "patch")]
+    async fn update_me(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+        mail: Data<&Mail>,
+        Json(payload): Json<UpdateMePayload>,
+    ) -> Result<UpdateMeApiResponse> {
+        let user = auth.0.0;
+
+        if let Err(errors) = payload.validate() {
+            return Ok(UpdateMeApiResponse::ValidationFailed(Json(errors.into())));
+        }
+
+        let Some(new_email) = payload.email.as_deref().map(normalize_email) else {
+            return Ok(UpdateMeApiResponse::Ok(Json(me_response("No changes", &user, None))));
+        };
+
+        // A hijacked session alone shouldn't be enough to move the account to another address
+        let password_ok = match payload.current_password.as_deref() {
+            Some(password) => password_matches(password, &user)?,
+            None => false,
+        };
+        if !password_ok {
+            return Err(Unauthorized(ApiError("Current password is incorrect".to_string())));
+        }
+
+        if new_email == user.email {
+            return Err(BadRequest(ApiError("That is already your email address".to_string())));
+        }
+        if email_taken(db.0, &new_email, user.id).await.map_err(poem::error::InternalServerError)? {
+            return Err(Conflict(ApiError("User with this email already exists".to_string())));
+        }
+
+        let mut secret = [0u8; EMAIL_CHANGE_TOKEN_BYTES];
+        OsRng.fill_bytes(&mut secret);
+        let token: String = secret.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.pending_email = Set(Some(new_email.clone()));
+        active_user.email_change_token_hash = Set(Some(hash_token(&token)));
+        active_user.email_change_expires_at = Set(Some(Utc::now().naive_utc() + Duration::hours(EMAIL_CHANGE_TTL_HOURS)));
+        let user = active_user.update(db.0).await.map_err(poem::error::InternalServerError)?;
+
+        mail.deliver(&new_email, &mailer::email_change(&token, EMAIL_CHANGE_TTL_HOURS))
+            .await
+            .map_err(|e| poem::error::InternalServerError(ApiError(format!("Failed to send confirmation email: {}", e))))?;
+
+        let token = mail.dev_mode.then_some(token);
+        Ok(UpdateMeApiResponse::Ok(Json(me_response("Confirmation sent to the new email address", &user, token))))
+    }
+
+    /// Confirm a pending email change with the token sent to the new address
+    #[oai(path = "/me/confirm_email", method = "post")]
+    async fn confirm_email(
+        &self,
+        db: Data<&DatabaseConnection>,
+        Json(payload): Json<ConfirmEmailPayload>,
+    ) -> Result<Json<MeResponse>> {
+        let user = Users::find()
+            .filter(users::Column::EmailChangeTokenHash.eq(hash_token(payload.token.trim())))
+            .filter(users::Column::EmailChangeExpiresAt.gt(Utc::now().naive_utc()))
+            .one(db.0)
+            .await
+            .map_err(poem::error::InternalServerError)?
+            .ok_or_else(|| BadRequest(ApiError("Invalid or expired confirmation token".to_string())))?;
+        let Some(new_email) = user.pending_email.clone() else {
+            return Err(BadRequest(ApiError("Invalid or expired confirmation token".to_string())));
+        };
+
+        // Someone may have signed up with the address since the change was staged
+        if email_taken(db.0, &new_email, user.id).await.map_err(poem::error::InternalServerError)? {
+            return Err(Conflict(ApiError("User with this email already exists".to_string())));
+        }
+
+        // The token came to the new address, which proves it as much as `/verify_email` would
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.email = Set(new_email);
+        active_user.is_verified = Set(true);
+        active_user.pending_email = Set(None);
+        active_user.email_change_token_hash = Set(None);
+        active_user.email_change_expires_at = Set(None);
+        let user = active_user.update(db.0).await.map_err(|e| match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Conflict(ApiError(
+                "User with this email already exists".to_string(),
+            )),
+            _ => poem::error::InternalServerError(e),
+        })?;
+
+        Ok(Json(me_response("Email address updated", &user, None)))
+    }
+
+    /// Cancel a pending email change
+    #[oai(path = "/me/pending_email", method = "delete")]
+    async fn cancel_email_change(
+        &self,
+        auth: SessionAuth,
+        db: Data<&DatabaseConnection>,
+    ) -> Result<Json<MeResponse>> {
+        let user = auth.0.0;
+
+        if user.pending_email.is_none() {
+            return Err(NotFound(ApiError("No pending email change".to_string())));
+        }
+
+        let mut active_user: users::ActiveModel = user.into();
+        active_user.pending_email = Set(None);
+        active_user.email_change_token_hash = Set(None);
+        active_user.email_change_expires_at = Set(None);
+        let user = active_user.update(db.0).await.map_err(poem::error::InternalServerError)?;
+
+        Ok(Json(me_response("Pending email change cancelled", &user, None)))
+    }
+}
+
+// --- Helper Functions ---
+
+/// Emails are stored trimmed and lowercased.
+pub(crate) fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Case-insensitive match on `users.email` (backed by the LOWER(email) unique index).
+pub(crate) fn email_matches(email: &str) -> SimpleExpr {
+    Expr::expr(Func::lower(Expr::col(users::Column::Email))).eq(normalize_email(email))
+}
+
+async fn email_taken(db: &DatabaseConnection, email: &str, except: Uuid) -> Result<bool, DbErr> {
+    let existing = Users::find()
+        .filter(email_matches(email))
+        .filter(users::Column::Id.ne(except))
+        .one(db)
+        .await?;
+    Ok(existing.is_some())
+}
+
+async fn username_taken(db: &DatabaseConnection, username: &str) -> Result<bool, DbErr> {
+    let existing = Users::find().filter(username_matches(username)).one(db).await?;
+    Ok(existing.is_some())
+}
+
+/// Drops pending email changes whose confirmation token has expired and returns each affected
+/// user with the address that was never confirmed. Covered by the partial index on
+/// `email_change_expires_at`.
+pub async fn sweep_expired_email_changes(db: &DatabaseConnection) -> Result<Vec<(Uuid, String)>, DbErr> {
+    let now = Utc::now().naive_utc();
+    let expired: Vec<(Uuid, Option<String>)> = Users::find()
+        .select_only()
+        .column(users::Column::Id)
+        .column(users::Column::PendingEmail)
+        .filter(users::Column::EmailChangeExpiresAt.lte(now))
+        .into_tuple()
+        .all(db)
+        .await?;
+    if expired.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Re-checks the expiry, so a change staged again since the select survives
+    let cleared = Users::update_many()
+        .col_expr(users::Column::PendingEmail, Expr::value(Option::<String>::None))
+        .col_expr(users::Column::EmailChangeTokenHash, Expr::value(Option::<String>::None))
+        .col_expr(users::Column::EmailChangeExpiresAt, Expr::value(Option::<chrono::NaiveDateTime>::None))
+        .filter(users::Column::Id.is_in(expired.iter().map(|(id, _)| *id)))
+        .filter(users::Column::EmailChangeExpiresAt.lte(now))
+        .exec_with_returning(db)
+        .await?;
+
+    Ok(expired
+        .into_iter()
+        .filter(|(id, _)| cleared.iter().any(|user| user.id == *id))
+        .map(|(id, pending)| (id, pending.unwrap_or_default()))
+        .collect())
+}
+
+fn me_response(message: &str, user: &users::Model, confirmation_token: Option<String>) -> MeResponse {
+    // An expired staged change is as good as none
+    let pending = user
+        .email_change_expires_at
+        .filter(|at| *at > Utc::now().naive_utc())
+        .and(user.pending_email.clone());
+
+    MeResponse {
+        message: message.to_string(),
+        email: user.email.clone(),
+        pending_expires_at: pending.as_ref().and(user.email_change_expires_at).map(|at| at.and_utc().to_rfc3339()),
+        pending_email: pending,
+        confirmation_token,
+    }
+}
+
+/// `#[validate(custom)]` rule for new usernames. Login reads any identifier with an `@` as an email,
+/// so a username with one could never be used to log in, and could pass for another account's address.
+fn username_format(username: &str) -> Result<(), ValidationError> {
+    if !username.contains('@') {
+        return Ok(());
+    }
+
+    let mut error = ValidationError::new("username_at");
+    error.message = Some("Username must not contain @".into());
+    Err(error)
+}
+
+/// Case-insensitive match on `users.username`.
+fn username_matches(username: &str) -> SimpleExpr {
+    Expr::expr(Func::lower(Expr::col(users::Column::Username))).eq(username.trim().to_lowercase())
+}
+
+/// Checks a password against the user's stored hash. An error is not a wrong password: the hash is
+/// unreadable, which only corruption explains, so it is logged and answered with 500.
+pub(crate) fn password_matches(password: &str, user: &users::Model) -> Result<bool> {
+    verify(password, &user.password).map_err(|e| {
+        tracing::error!(user_id = %user.id, "Failed to verify password hash: {}", e);
+        poem::error::InternalServerError(ApiError("Failed to verify password".to_string()))
+    })
+}
+
+/// Mints the access token used by every API. Signup (with auto_login) and login both go through here.
+fn issue_token(user: &users::Model, ttl: Duration, session_id: Uuid) -> Result<(String, DateTime<Utc>)> {
+    let expires_at = Utc::now()
+        .checked_add_signed(ttl)
+        .expect("Failed to calculate token expiration");
+
+    let claims = Claims {
+        sub: user.id.to_string(),
+        username: user.username.clone(),
+        email: user.email.clone(),
+        role: user.role.clone(),
+        iss: jwt::issuer(),
+        aud: jwt::audience(),
+        exp: expires_at.timestamp() as usize,
+        sid: session_id.to_string(),
+        jti: Uuid::new_v4().to_string(),
+        ver: user.token_version,
+    };
+
+    let token = jwt::encode_claims(&claims)
+        .map_err(|_| poem::error::InternalServerError(ApiError("Failed to create token".to_string())))?;
+
+    Ok((token, expires_at))
+}
+
+fn issue_verification_token(user: &users::Model) -> Result<String> {
+    let expires_at = Utc::now() + Duration::hours(VERIFY_EMAIL_TTL_HOURS);
+    let claims = VerifyEmailClaims {
+        sub: user.id.to_string(),
+        email: user.email.clone(),
+        purpose: VERIFY_EMAIL_PURPOSE.to_string(),
+        iss: jwt::issuer(),
+        aud: jwt::audience(),
+        exp: expires_at.timestamp() as usize,
+    };
+    jwt::encode_claims(&claims)
+        .map_err(|_| poem::error::InternalServerError(ApiError("Failed to create verification token".to_string())))
+}
+
+// 429 with Retry-After, so clients can tell the user how long to wait
+fn too_many_attempts(retry_after_secs: u64) -> poem::Error {
+    let mut response = ErrorBody::with_details(
+        "too_many_attempts",
+        format!("Too many failed login attempts. Try again in {} seconds.", retry_after_secs),
+        serde_json::json!({ "retry_after_secs": retry_after_secs }),
+    )
+    .into_response();
+    response.set_status(StatusCode::TOO_MANY_REQUESTS);
+    response.headers_mut().insert(header::RETRY_AFTER, retry_after_secs.into());
+    poem::Error::from_response(response)
+}
+
+// 403 with its own code: the password was right, but logging in again won't help
+fn account_suspended() -> poem::Error {
+    ErrorBody::error(StatusCode::FORBIDDEN, ErrorBody::new("account_suspended", "This account has been suspended"))
+}
+
+// 423 rather than 401, so the user knows waiting will help
+fn account_locked(until: chrono::NaiveDateTime) -> poem::Error {
+    let minutes = (until - Utc::now().naive_utc()).num_minutes().max(1);
+    ErrorBody::error(
+        StatusCode::LOCKED,
+        ErrorBody::with_details(
+            "account_locked",
+            format!("Account is locked after too many failed login attempts. Try again in {} minutes.", minutes),
+            serde_json::json!({ "locked_until": until.and_utc().to_rfc3339() }),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use migration::{MigrationTrait, SchemaManager};
+    use crate::test_support::{bearer, login, recording_mail, seed_user, test_app, test_app_with_mail, test_db, TEST_PASSWORD};
+
+    async fn login_as(client: &poem::test::TestClient<poem::endpoint::BoxEndpoint<'static>>, identifier: &str) -> String {
+        let resp = client
+            .post("/api/login")
+            .body_json(&serde_json::json!({ "identifier": identifier, "password": TEST_PASSWORD }))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("user").object().get("id").string().to_string()
+    }
+
+    #[tokio::test]
+    async fn signup_rejects_usernames_with_an_at_sign() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let other = seed_user(&db).await;
+
+        // Someone else's address as a username
+        let resp = client
+            .post("/api/signup")
+            .body_json(&serde_json::json!({
+                "username": other.email,
+                "email": format!("new-{}", other.email),
+                "password": "Unrelated-Passw0rd!",
+            }))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let body = resp.json().await;
+        let errors = body.value().object().get("errors").object_array();
+        assert_eq!(errors.len(), 1);
+        errors[0].get("field").assert_string("username");
+        errors[0].get("code").assert_string("username_at");
+    }
+
+    #[tokio::test]
+    async fn email_login_ignores_a_username_spelled_like_that_email() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let owner = seed_user(&db).await;
+
+        // From before signup rejected @ in usernames
+        let legacy = seed_user(&db).await;
+        let mut renamed: users::ActiveModel = legacy.clone().into();
+        renamed.username = Set(owner.email.to_uppercase());
+        renamed.update(&db).await.unwrap();
+
+        assert_eq!(login_as(&client, &owner.email).await, owner.id.to_string());
+        assert_eq!(login_as(&client, &legacy.email).await, legacy.id.to_string());
+    }
+
+    #[tokio::test]
+    async fn unreadable_password_hash_is_a_server_error() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        let mut corrupted: users::ActiveModel = user.into();
+        corrupted.password = Set("not-a-bcrypt-hash".to_string());
+        corrupted.update(&db).await.unwrap();
+
+        let resp = client
+            .post("/api/change_password")
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "current_password": TEST_PASSWORD, "new_password": "Another-Passw0rd!" }))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let resp = client
+            .patch("/api/me")
+            .header("Authorization", bearer(&token))
+            .body_json(&serde_json::json!({ "email": "moved@example.test", "current_password": TEST_PASSWORD }))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn signup_racing_another_signup_for_the_email_gets_409() {
+        let Some(db) = test_db().await else { return };
+        let Some(other_db) = test_db().await else { return };
+        let client = test_app(&db);
+        let suffix = &Uuid::new_v4().simple().to_string()[..12];
+        let email = format!("race_{}@example.test", suffix);
+
+        // The competing signup's row, inserted but not yet committed: the existence check can't see it,
+        // and the insert then waits on the unique index until the other transaction finishes
+        let txn = other_db.begin().await.unwrap();
+        let mut competitor: users::ActiveModel = seed_user(&db).await.into();
+        competitor.id = Set(Uuid::new_v4());
+        competitor.username = Set(format!("rival_{}", suffix));
+        competitor.email = Set(email.to_uppercase());
+        competitor.insert(&txn).await.unwrap();
+
+        let signup = client
+            .post("/api/signup")
+            .body_json(&serde_json::json!({
+                "username": format!("racer_{}", suffix),
+                "email": email,
+                "password": "Unrelated-Passw0rd!",
+            }))
+            .send();
+        let commit_once_blocked = async {
+            loop {
+                let waiting = other_db
+                    .query_one(Statement::from_string(
+                        other_db.get_database_backend(),
+                        "SELECT 1 FROM pg_stat_activity WHERE wait_event_type = 'Lock' AND query LIKE 'INSERT INTO \"users\"%'",
+                    ))
+                    .await
+                    .unwrap();
+                if waiting.is_some() {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            txn.commit().await.unwrap();
+        };
+        let (resp, ()) = tokio::join!(signup, commit_once_blocked);
+
+        resp.assert_status(StatusCode::CONFLICT);
+        let body = resp.json().await;
+        body.value().object().get("message").assert_string("User with this email already exists");
+    }
+
+    #[tokio::test]
+    async fn tokens_from_before_a_password_change_are_rejected() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let user = seed_user(&db).await;
+        let old_token = login(&client, &user).await;
+
+        client
+            .get("/api/get_memos")
+            .header("Authorization", bearer(&old_token))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        client
+            .post("/api/change_password")
+            .header("Authorization", bearer(&old_token))
+            .body_json(&serde_json::json!({ "current_password": TEST_PASSWORD, "new_password": "Changed-Passw0rd!" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        for path in ["/api/get_memos", "/api/me/export", "/api/sessions"] {
+            client
+                .get(path)
+                .header("Authorization", bearer(&old_token))
+                .send()
+                .await
+                .assert_status(StatusCode::UNAUTHORIZED);
+        }
+        client
+            .get("/api/auth/verify")
+            .header("Authorization", bearer(&old_token))
+            .send()
+            .await
+            .json()
+            .await
+            .value()
+            .object()
+            .get("reason")
+            .assert_string("password_changed");
+    }
+
+    #[test]
+    fn login_response_json_shape_is_stable() {
+        let response = LoginResponse {
+            message: "Login Successful".to_string(),
+            token: "header.payload.signature".to_string(),
+            expires_at: "2025-01-02T03:04:05+00:00".to_string(),
+            user: UserProfile {
+                id: "0b7c5a52-6a3e-4c5f-9b0e-7f1e2d3c4b5a".to_string(),
+                username: "ada".to_string(),
+                email: "ada@example.test".to_string(),
+                created_at: "2024-12-31T23:59:59+00:00".to_string(),
+                email_verified: true,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "message": "Login Successful",
+                "token": "header.payload.signature",
+                "expires_at": "2025-01-02T03:04:05+00:00",
+                "user": {
+                    "id": "0b7c5a52-6a3e-4c5f-9b0e-7f1e2d3c4b5a",
+                    "username": "ada",
+                    "email": "ada@example.test",
+                    "created_at": "2024-12-31T23:59:59+00:00",
+                    "email_verified": true,
+                },
+            })
+        );
+
+        // The schema clients generate from says the same
+        let spec: serde_json::Value = serde_json::from_str(&crate::app::api_service().spec()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        let mut login_fields: Vec<&str> = schemas["LoginResponse"]["required"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+        login_fields.sort();
+        assert_eq!(login_fields, ["expires_at", "message", "token", "user"]);
+        assert_eq!(schemas["LoginResponse"]["properties"]["user"]["allOf"][0]["$ref"], "#/components/schemas/UserProfile");
+        let mut profile_fields: Vec<&str> = schemas["UserProfile"]["required"].as_array().unwrap().iter().filter_map(|f| f.as_str()).collect();
+        profile_fields.sort();
+        assert_eq!(profile_fields, ["created_at", "email", "email_verified", "id", "username"]);
+    }
+
+    #[tokio::test]
+    async fn signup_reports_every_invalid_field_in_one_response() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+
+        let resp = client
+            .post("/api/signup")
+            .body_json(&serde_json::json!({
+                "username": format!("user_{}", &Uuid::new_v4().simple().to_string()[..12]),
+                "email": "not-an-email",
+                "password": "Ab1!x",
+            }))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let body = resp.json().await;
+        let errors = body.value().object().get("errors").object_array();
+        assert_eq!(errors.len(), 2);
+        errors[0].get("field").assert_string("email");
+        errors[0].get("message").assert_string("Please provide a valid email address");
+        errors[1].get("field").assert_string("password");
+        errors[1].get("message").assert_string("Password must be at least 8 characters long");
+    }
+
+    // The code in a message from `mailer`, which always follows the first ": "
+    fn mailed_code(text: &str) -> String {
+        let (_, rest) = text.split_once(": ").expect("the message carries a code");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn signup_mails_a_welcome_and_a_verification_code_that_verifies() {
+        let Some(db) = test_db().await else { return };
+        let (mut mail, outbox) = recording_mail();
+        // The code may only reach the user by mail
+        mail.dev_mode = false;
+        let client = test_app_with_mail(&db, mail);
+        let username = format!("user_{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let email = format!("{}@example.test", username);
+
+        let resp = client
+            .post("/api/signup")
+            .body_json(&serde_json::json!({ "username": username, "email": email, "password": "Quiet-Harbor-42!" }))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let body = resp.json().await;
+        assert!(body.value().object().get_opt("verification_token").is_none());
+
+        let sent = outbox.sent_to(&email);
+        let subjects: Vec<&str> = sent.iter().map(|mail| mail.subject.as_str()).collect();
+        assert_eq!(subjects, ["Welcome to Smart Memo", "Verify your email address"]);
+        assert!(sent[0].text.contains(&username));
+
+        client
+            .get("/api/verify_email")
+            .query("token", &mailed_code(&sent[1].text))
+            .send()
+            .await
+            .assert_status_is_ok();
+        let user = Users::find().filter(email_matches(&email)).one(&db).await.unwrap().unwrap();
+        assert!(user.is_verified);
+    }
+
+    #[tokio::test]
+    async fn password_reset_mails_a_token_that_confirm_accepts() {
+        let Some(db) = test_db().await else { return };
+        let (mut mail, outbox) = recording_mail();
+        mail.dev_mode = false;
+        let client = test_app_with_mail(&db, mail);
+        let user = seed_user(&db).await;
+
+        client
+            .post("/api/password_reset/request")
+            .body_json(&serde_json::json!({ "email": user.email }))
+            .send()
+            .await
+            .assert_status_is_ok();
+        let sent = outbox.sent_to(&user.email);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject, "Reset your password");
+
+        client
+            .post("/api/password_reset/confirm")
+            .body_json(&serde_json::json!({ "token": mailed_code(&sent[0].text), "new_password": "Quiet-Harbor-42!" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+        client
+            .post("/api/login")
+            .body_json(&serde_json::json!({ "identifier": user.username, "password": "Quiet-Harbor-42!" }))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn mixed_case_account_from_before_the_migrations_still_logs_in() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+        let seeded = seed_user(&db).await;
+        // The row as signup stored it before emails were normalized
+        let username = format!("Mixed_{}", &Uuid::new_v4().simple().to_string()[..12]);
+        let email = format!(" {}@Example.Test", username);
+        let mut user: users::ActiveModel = seeded.into();
+        user.username = Set(username.clone());
+        user.email = Set(email.clone());
+        let user = user.update(&db).await.unwrap();
+        assert_eq!(user.email, email);
+
+        let manager = SchemaManager::new(&db);
+        migration::LowercaseUserEmails.up(&manager).await.unwrap();
+        migration::UniqueUsernames.up(&manager).await.unwrap();
+
+        let user = Users::find_by_id(user.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(user.email, email.trim().to_lowercase());
+        assert_eq!(user.username, username);
+
+        for identifier in [email.trim().to_uppercase(), email.trim().to_string(), username.to_lowercase(), username.to_uppercase()] {
+            assert_eq!(login_as(&client, &identifier).await, user.id.to_string(), "{}", identifier);
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use poem::{endpoint::make_sync, get, web::Json, Endpoint, EndpointExt, Route, middleware::AddData};
+use poem_openapi::OpenApiService;
+use sea_orm::DbConn;
+use serde_json::{Map, Value};
+
+use crate::api::challenge::SignupChallenge;
+use crate::api::crypto::Crypto;
+use crate::api::error::error_envelope;
+use crate::api::login_limit::LoginLimiter;
+use crate::api::mailer::Mail;
+use crate::api::storage::AudioStore;
+use crate::health;
+use crate::metrics::{self, record_requests, MetricsConfig};
+use crate::request_log::log_requests;
+use crate::api::{UserApi, GeminiApi, MemoApi, AdminApi, ShareApi, TokenApi, SessionApi, AvatarApi, UsageApi, ExportApi, ErasureApi, PreferencesApi, AuthApi, ImportApi, FolderApi, Api};
+
+pub type Apis = (UserApi, GeminiApi, MemoApi, AdminApi, ShareApi, TokenApi, SessionApi, AvatarApi, UsageApi, ExportApi, ErasureApi, PreferencesApi, AuthApi, ImportApi, FolderApi, Api);
+
+/// The combined OpenAPI service. Kept separate from `build` so the spec can be
+/// generated (`api_service().spec()`) without a database or a running server.
+pub fn api_service() -> OpenApiService<Apis, ()> {
+    OpenApiService::new((UserApi, GeminiApi, MemoApi, AdminApi, ShareApi, TokenApi, SessionApi, AvatarApi, UsageApi, ExportApi, ErasureApi, PreferencesApi, AuthApi, ImportApi, FolderApi, Api), "Smart Memo API", "2.0")
+        .server("/api") // Don't hardcode localhost here, relative path is better for deployment
+}
+
+/// The operations the browser extension calls. `/ext/openapi.json` describes only these, so the
+/// extension's client is generated from a small surface that doesn't churn with the rest of the API.
+const EXT_OPERATIONS: &[(&str, &str)] = &[
+    ("/login", "post"),
+    ("/auth/verify", "get"),
+    ("/quick_capture", "post"),
+    ("/tags", "get"),
+    ("/get_memos", "get"),
+    ("/search_memos", "get"),
+];
+
+/// The spec of `api_service` cut down to `EXT_OPERATIONS` and the schemas they reference.
+pub fn ext_spec() -> Value {
+    let mut spec: Value = serde_json::from_str(&api_service().spec()).expect("the generated spec is valid JSON");
+
+    let mut paths = Map::new();
+    for (path, method) in EXT_OPERATIONS {
+        if let Some(operation) = spec.pointer(&format!("/paths/{}/{}", path.replace('/', "~1"), method)) {
+            let entry = paths.entry(path.to_string()).or_insert_with(|| Value::Object(Map::new()));
+            entry[*method] = operation.clone();
+        }
+    }
+
+    // Follow `$ref`s until no new schema turns up
+    let all_schemas = spec.pointer("/components/schemas").cloned().unwrap_or_default();
+    let mut schemas = Map::new();
+    let mut pending = Vec::new();
+    collect_refs(&Value::Object(paths.clone()), &mut pending);
+    while let Some(name) = pending.pop() {
+        if schemas.contains_key(&name) {
+            continue;
+        }
+        if let Some(schema) = all_schemas.get(&name) {
+            collect_refs(schema, &mut pending);
+            schemas.insert(name, schema.clone());
+        }
+    }
+
+    spec["paths"] = Value::Object(paths);
+    spec["components"]["schemas"] = Value::Object(schemas);
+    if let Some(info) = spec.get_mut("info") {
+        info["title"] = Value::from("Smart Memo Extension API");
+    }
+    spec
+}
+
+fn collect_refs(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(name) = map.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix("#/components/schemas/")) {
+                found.push(name.to_string());
+            }
+            map.values().for_each(|v| collect_refs(v, found));
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, found)),
+        _ => {}
+    }
+}
+
+/// The full application: the API under `/api`, unauthenticated probes for the
+/// orchestrator at `/health` and `/ready`, Prometheus metrics at `/metrics` unless they have a
+/// port of their own, the extension's spec at `/ext/openapi.json`, and Swagger UI at `/`. Every request is logged and counted, and every API
+/// error answers with an `ErrorBody`.
+pub fn build(db: DbConn, crypto: Crypto, challenge: SignupChallenge, login_limiter: LoginLimiter, mail: Mail, audio_store: AudioStore, metrics_config: MetricsConfig) -> impl Endpoint {
+    let api_service = api_service();
+    let ui = api_service.swagger_ui();
+    let ext = ext_spec();
+
+    let mut routes = Route::new()
+        .nest(
+            "/api",
+            api_service
+                .with(AddData::new(db.clone()))
+                .with(AddData::new(crypto))
+                .with(AddData::new(challenge))
+                .with(AddData::new(login_limiter))
+                .with(AddData::new(mail))
+                .with(AddData::new(audio_store))
+                .catch_all_error(error_envelope),
+        )
+        .at("/ext/openapi.json", get(make_sync(move |_| Json(ext.clone()))))
+        .at("/health", get(health::health))
+        .at("/ready", get(health::ready).with(AddData::new(db)));
+    if metrics_config.port.is_none() {
+        routes = routes.at("/metrics", get(metrics::metrics).with(AddData::new(metrics_config)));
+    }
+    let routes = routes.nest("/", ui);
+    log_requests(record_requests(routes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_support::{assert_golden, bearer, login, seed_user, test_app, test_db};
+
+    // The generated spec with the lists whose order carries no meaning sorted
+    fn normalized_spec() -> Value {
+        fn sort_required(value: &mut Value) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::Array(required)) = map.get_mut("required") {
+                        required.sort_by_key(|name| name.to_string());
+                    }
+                    map.values_mut().for_each(sort_required);
+                }
+                Value::Array(items) => items.iter_mut().for_each(sort_required),
+                _ => {}
+            }
+        }
+
+        let mut spec: Value = serde_json::from_str(&api_service().spec()).expect("the generated spec is valid JSON");
+        sort_required(&mut spec);
+        spec
+    }
+
+    #[test]
+    fn openapi_spec_matches_the_golden_file() {
+        assert_golden("openapi.json", &normalized_spec());
+    }
+
+    #[tokio::test]
+    async fn error_envelope_matches_the_golden_file() {
+        let Some(db) = test_db().await else { return };
+        let client = test_app(&db);
+
+        let user = seed_user(&db).await;
+        let token = login(&client, &user).await;
+
+        // Envelopes that handlers return, and ones `error_envelope` builds from poem's own errors
+        let mut envelopes = serde_json::Map::new();
+        for (name, path, authorization) in [
+            ("unauthenticated", "/api/get_memos", None),
+            ("invalid_filter", "/api/get_memos?from=yesterday", Some(bearer(&token))),
+            ("unknown_memo", "/api/get_memo/00000000-0000-0000-0000-000000000000", Some(bearer(&token))),
+            ("unknown_route", "/api/no_such_route", None),
+        ] {
+            let mut request = client.get(path);
+            if let Some(authorization) = authorization {
+                request = request.header("Authorization", authorization);
+            }
+            let resp = request.send().await;
+            let status = resp.0.status().as_u16();
+            let body: Value = serde_json::from_str(&resp.0.into_body().into_string().await.unwrap()).unwrap();
+            envelopes.insert(name.to_string(), json!({ "status": status, "body": body }));
+        }
+        assert_golden("error_envelope.json", &Value::Object(envelopes));
+    }
+
+    #[test]
+    fn ext_spec_has_only_the_extension_operations_and_their_schemas() {
+        let spec = ext_spec();
+
+        let paths = spec["paths"].as_object().unwrap();
+        let mut operations: Vec<(String, String)> = paths
+            .iter()
+            .flat_map(|(path, item)| item.as_object().unwrap().keys().map(move |method| (path.clone(), method.clone())))
+            .collect();
+        operations.sort();
+        let mut expected: Vec<(String, String)> = EXT_OPERATIONS.iter().map(|(p, m)| (p.to_string(), m.to_string())).collect();
+        expected.sort();
+        assert_eq!(operations, expected);
+
+        // Every reference resolves, and nothing unreferenced came along
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        for name in &refs {
+            assert!(schemas.contains_key(name), "dangling reference to {}", name);
+        }
+        for name in schemas.keys() {
+            assert!(refs.contains(name), "unreferenced schema {}", name);
+        }
+        assert!(schemas.contains_key("QuickCapturePayload"));
+        assert!(!schemas.contains_key("AdminUser"));
+    }
+}
@@ -0,0 +1,21 @@
+use poem::{handler, http::StatusCode, web::{Data, Json}, IntoResponse, Response};
+use sea_orm::DbConn;
+use serde_json::json;
+
+/// Liveness: the process is up. Never touches the database.
+#[handler]
+pub fn health() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: the database answers. Returns 503 until it does.
+#[handler]
+pub async fn ready(db: Data<&DbConn>) -> Response {
+    match db.0.ping().await {
+        Ok(()) => Json(json!({ "status": "ok" })).into_response(),
+        Err(e) => {
+            tracing::warn!("Readiness check failed: {:?}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "status": "unavailable" }))).into_response()
+        }
+    }
+}
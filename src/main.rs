@@ -1,31 +1,77 @@
 use std::env;
-use poem::{listener::TcpListener, Route, EndpointExt, middleware::AddData};
-use poem_openapi::OpenApiService;
+use poem::listener::TcpListener;
+use poem::EndpointExt;
 use sea_orm::DbConn;
 
 mod api;
+mod app;
 mod db;
-
-use api::{UserApi, GeminiApi, MemoApi, Api};
+mod health;
+mod metrics;
+mod request_log;
+mod scheduler;
+mod shutdown;
+#[cfg(test)]
+mod test_support;
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
     // Initialize tracing (optional)
     tracing_subscriber::fmt::init();
 
+    // Fail fast on broken token lifetime settings instead of minting unusable tokens
+    api::jwt::TokenLifetimes::from_env().expect("Invalid token lifetime configuration");
+    api::jwt::init_keys().expect("Invalid JWT key configuration");
+
+    // Stored API keys are unreadable without the right key, so refuse to start without one
+    let crypto = api::crypto::Crypto::from_env().expect("Invalid encryption key configuration");
+
     // Connect to DB
-    let db: DbConn = db::connect().await.expect("Database connection failed");
+    let pool = db::PoolSettings::from_env().expect("Invalid database pool configuration");
+    let db: DbConn = db::connect(&pool).await.expect("Database connection failed");
+
+    // Before anything touches the schema, and before the listener is bound
+    if db::migrate_on_startup() {
+        db::run_migrations(&db).await.expect("Database migration failed");
+    }
+
+    // Promote ADMIN_EMAIL (if set) so the first admin can exist
+    api::admin::bootstrap_admin(&db).await.expect("Admin bootstrap failed");
 
-    // OpenAPI service (combined APIs)
-    let api_service = OpenApiService::new((UserApi, GeminiApi, MemoApi, Api), "Smart Memo API", "1.0")
-        .server("/api"); // Don't hardcode localhost here, relative path is better for deployment
+    // A half-configured bucket should stop the boot rather than quietly keep audio in the database
+    let audio_store = api::storage::AudioStore::from_env().expect("Invalid object storage configuration");
 
-    let ui = api_service.swagger_ui();
+    let mail = api::mailer::Mail::from_env().expect("Invalid SMTP configuration");
+
+    // Background maintenance (expired tombstones, shares, sessions etc.)
+    scheduler::spawn(db.clone(), mail.clone());
+    let trash_retention = scheduler::TrashRetention::from_env().expect("Invalid trash retention configuration");
+    scheduler::spawn_trash_purge(db.clone(), audio_store.clone(), trash_retention);
+
+    // Optional captcha on signup; a half-configured provider should stop the boot, not fail open
+    let challenge = api::challenge::SignupChallenge::from_env().expect("Invalid signup challenge configuration");
+    api::limits::max_upload_bytes().expect("Invalid upload limit configuration");
+    api::limits::max_avatar_bytes().expect("Invalid avatar limit configuration");
+    api::limits::max_audio_bytes().expect("Invalid audio limit configuration");
+    let login_limiter = api::login_limit::LoginLimiter::from_env().expect("Invalid login rate limit configuration");
+    api::login_limit::LockoutPolicy::from_env().expect("Invalid account lockout configuration");
+    let drain = shutdown::drain_timeout().expect("Invalid shutdown drain configuration");
+    let metrics = metrics::MetricsConfig::from_env().expect("Invalid metrics configuration");
+
+    // Metrics on their own port stay off the public listener; the process exiting stops this one
+    if let Some(metrics_port) = metrics.port {
+        let metrics_app = poem::get(metrics::metrics).with(poem::middleware::AddData::new(metrics.clone()));
+        tokio::spawn(async move {
+            let listener = TcpListener::bind(format!("0.0.0.0:{}", metrics_port));
+            if let Err(e) = poem::Server::new(listener).run(poem::Route::new().at("/metrics", metrics_app)).await {
+                tracing::error!("Metrics server failed: {}", e);
+            }
+        });
+    }
 
     // Build application
-    let app = Route::new()
-        .nest("/api", api_service.with(AddData::new(db)))
-        .nest("/", ui);
+    let in_flight = shutdown::InFlight::default();
+    let app = in_flight.track(app::build(db, crypto, challenge, login_limiter, mail, audio_store, metrics));
 
     // Get PORT from environment variable (Render sets this automatically)
     let port = env::var("PORT").unwrap_or_else(|_| "4000".to_string());
@@ -34,6 +80,12 @@ async fn main() -> Result<(), std::io::Error> {
     println!("🚀 Starting server on {}", addr);
 
     poem::Server::new(TcpListener::bind(addr))
-        .run(app)
-        .await
+        .run_with_graceful_shutdown(app, shutdown::signal(in_flight.clone(), drain), Some(drain))
+        .await?;
+
+    match in_flight.count() {
+        0 => tracing::info!(drained = in_flight.drained(), "Shutdown complete"),
+        cut_off => tracing::warn!(drained = in_flight.drained(), cut_off, "Drain timeout reached, requests were cut off"),
+    }
+    Ok(())
 }
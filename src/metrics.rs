@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as _;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use poem::http::{header, StatusCode};
+use poem::web::Data;
+use poem::{handler, Endpoint, EndpointExt, IntoResponse, PathPattern, Request, Response};
+
+// --- Prometheus Metrics ---
+// Kept in process and rendered in the Prometheus text format at `GET /metrics`: request counts and
+// latency by route, Gemini calls by model, and memo writes. Counters reset when the process restarts.
+
+// Seconds; wide enough for Gemini transcriptions of long recordings
+const LATENCY_BUCKETS: [f64; 13] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Default)]
+struct Histogram {
+    // Per bucket, not cumulative; the last slot is +Inf
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let slot = LATENCY_BUCKETS.iter().position(|le| seconds <= *le).unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[slot] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    // (method, route, status)
+    requests: HashMap<(String, String, u16), u64>,
+    // (method, route)
+    request_latency: HashMap<(String, String), Histogram>,
+    // (model, outcome)
+    gemini_calls: HashMap<(String, &'static str), u64>,
+    gemini_latency: HashMap<String, Histogram>,
+    memo_writes: HashMap<&'static str, u64>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Default::default);
+
+fn with_registry(f: impl FnOnce(&mut Registry)) {
+    // A panic while recording leaves nothing half-written worth refusing to count over
+    let mut registry = REGISTRY.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut registry);
+}
+
+/// Records one call to the Gemini API, successful or not.
+pub fn record_gemini_call(model: &str, ok: bool, elapsed: Duration) {
+    with_registry(|registry| {
+        *registry.gemini_calls.entry((model.to_string(), if ok { "ok" } else { "error" })).or_default() += 1;
+        registry.gemini_latency.entry(model.to_string()).or_default().observe(elapsed);
+    });
+}
+
+/// Records `count` memos being created, updated, deleted (trashed) or purged (`operation`).
+pub fn record_memo_writes(operation: &'static str, count: u64) {
+    with_registry(|registry| *registry.memo_writes.entry(operation).or_default() += count);
+}
+
+/// Counts and times every request by its route pattern (`/api/memo/:memo_id`), so IDs in paths
+/// don't create a series per memo. Requests that match no route are grouped as `unmatched`.
+pub fn record_requests<E: Endpoint + 'static>(ep: E) -> impl Endpoint + use<E> {
+    ep.around(|ep, req| async move {
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let resp = ep.get_response(req).await;
+        let elapsed = started.elapsed();
+
+        let route = resp.data::<PathPattern>().map(|pattern| pattern.0.to_string()).unwrap_or_else(|| "unmatched".to_string());
+        let status = resp.status().as_u16();
+        with_registry(|registry| {
+            *registry.requests.entry((method.clone(), route.clone(), status)).or_default() += 1;
+            registry.request_latency.entry((method, route)).or_default().observe(elapsed);
+        });
+        Ok(resp)
+    })
+}
+
+/// Where and how `/metrics` is served.
+#[derive(Clone)]
+pub struct MetricsConfig {
+    /// `METRICS_TOKEN`: when set, `/metrics` needs `Authorization: Bearer <token>`
+    token: Option<String>,
+    /// `METRICS_PORT`: when set, `/metrics` is served only on this port instead of next to the API
+    pub port: Option<u16>,
+}
+
+impl MetricsConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let token = env::var("METRICS_TOKEN").ok().map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+        let port = match env::var("METRICS_PORT").ok().filter(|p| !p.trim().is_empty()) {
+            Some(raw) => Some(raw.trim().parse::<u16>().map_err(|_| format!("METRICS_PORT must be a port number, got {:?}", raw))?),
+            None => None,
+        };
+        Ok(MetricsConfig { token, port })
+    }
+}
+
+/// The scrape endpoint. Unauthenticated unless `METRICS_TOKEN` is set.
+#[handler]
+pub fn metrics(req: &Request, config: Data<&MetricsConfig>) -> Response {
+    if let Some(token) = &config.token {
+        let presented = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if presented != Some(token.as_str()) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    render()
+        .with_content_type("text/plain; version=0.0.4; charset=utf-8")
+        .into_response()
+}
+
+fn render() -> String {
+    let mut out = String::new();
+    with_registry(|registry| {
+        out.push_str("# HELP smartmemo_http_requests_total HTTP requests by method, route and status.\n");
+        out.push_str("# TYPE smartmemo_http_requests_total counter\n");
+        for ((method, route, status), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "smartmemo_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP smartmemo_http_request_duration_seconds HTTP request latency by method and route.\n");
+        out.push_str("# TYPE smartmemo_http_request_duration_seconds histogram\n");
+        for ((method, route), histogram) in &registry.request_latency {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            write_histogram(&mut out, "smartmemo_http_request_duration_seconds", &labels, histogram);
+        }
+
+        out.push_str("# HELP smartmemo_gemini_calls_total Calls to the Gemini API by model and outcome.\n");
+        out.push_str("# TYPE smartmemo_gemini_calls_total counter\n");
+        for ((model, outcome), count) in &registry.gemini_calls {
+            let _ = writeln!(out, "smartmemo_gemini_calls_total{{model=\"{}\",outcome=\"{}\"}} {}", escape(model), outcome, count);
+        }
+
+        out.push_str("# HELP smartmemo_gemini_call_duration_seconds Gemini API latency by model.\n");
+        out.push_str("# TYPE smartmemo_gemini_call_duration_seconds histogram\n");
+        for (model, histogram) in &registry.gemini_latency {
+            let labels = format!("model=\"{}\"", escape(model));
+            write_histogram(&mut out, "smartmemo_gemini_call_duration_seconds", &labels, histogram);
+        }
+
+        out.push_str("# HELP smartmemo_memo_writes_total Memos created, updated, trashed and purged.\n");
+        out.push_str("# TYPE smartmemo_memo_writes_total counter\n");
+        for (operation, count) in &registry.memo_writes {
+            let _ = writeln!(out, "smartmemo_memo_writes_total{{operation=\"{}\"}} {}", operation, count);
+        }
+    });
+    out
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (le, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+        cumulative += count;
+        let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, le, cumulative);
+    }
+    let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, histogram.count);
+    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+}
+
+// Label values are quoted, so backslashes, quotes and newlines must be escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
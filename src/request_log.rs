@@ -0,0 +1,59 @@
+use std::time::Instant;
+
+use poem::http::{header, HeaderMap, HeaderValue};
+use poem::{Endpoint, EndpointExt, IntoResponse};
+use tracing::Instrument;
+use uuid::Uuid;
+
+// --- Request Logging ---
+// One info line per request with method, path, status and latency. Each request gets an ID,
+// returned in `X-Request-Id` and attached to everything logged while handling it, so a user's
+// report can be matched to the server logs.
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Never written to the logs, even at debug level
+const REDACTED_HEADERS: [header::HeaderName; 3] = [header::AUTHORIZATION, header::COOKIE, header::SET_COOKIE];
+
+/// Wraps the whole app. Errors are turned into their responses here so their status is logged too.
+pub fn log_requests<E: Endpoint + 'static>(ep: E) -> impl Endpoint + use<E> {
+    ep.around(|ep, req| async move {
+        let request_id = Uuid::new_v4().to_string();
+        let method = req.method().clone();
+        // The query is left out; share links and OAuth callbacks carry secrets there
+        let path = req.uri().path().to_string();
+        let span = tracing::info_span!("request", request_id = %request_id);
+        tracing::debug!(parent: &span, headers = ?redacted(req.headers()), "Request headers");
+
+        let started = Instant::now();
+        let mut resp = ep.get_response(req).instrument(span.clone()).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        tracing::info!(
+            parent: &span,
+            method = %method,
+            path = %path,
+            status = resp.status().as_u16(),
+            latency_ms,
+            "Request finished"
+        );
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        Ok(resp.into_response())
+    })
+}
+
+fn redacted(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(name) {
+                "[redacted]".to_string()
+            } else {
+                value.to_str().unwrap_or("[binary]").to_string()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DbConn, DbErr, EntityTrait, QueryFilter, QuerySelect, Set};
+use uuid::Uuid;
+
+use entity::{maintenance_runs, users, voice_memos1};
+use crate::api::limits::positive_env;
+use crate::api::mailer::{self, Mail};
+use crate::api::memo::stored_audio_keys;
+use crate::api::memo_api_store_ops::purge_expired_key_tombstones;
+use crate::api::sessions::{purge_expired_revocations, sweep_expired_sessions};
+use crate::api::share::sweep_expired_shares;
+use crate::api::storage::AudioStore;
+use crate::api::user::sweep_expired_email_changes;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_TRASH_RETENTION_DAYS: usize = 30;
+const DEFAULT_TRASH_PURGE_INTERVAL_SECS: usize = 60 * 60;
+
+/// How long memos stay in the trash, from `TRASH_RETENTION_DAYS` (default 30), and how often
+/// older ones are purged, from `TRASH_PURGE_INTERVAL_SECS` (default hourly).
+#[derive(Clone, Copy, Debug)]
+pub struct TrashRetention {
+    pub days: u64,
+    pub interval: Duration,
+}
+
+impl TrashRetention {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(TrashRetention {
+            days: positive_env("TRASH_RETENTION_DAYS", DEFAULT_TRASH_RETENTION_DAYS)? as u64,
+            interval: Duration::from_secs(positive_env("TRASH_PURGE_INTERVAL_SECS", DEFAULT_TRASH_PURGE_INTERVAL_SECS)? as u64),
+        })
+    }
+}
+
+/// Spawns the periodic maintenance jobs. Each job logs its own failures and never stops the loop.
+pub fn spawn(db: DbConn, mail: Mail) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            match purge_expired_key_tombstones(&db).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    tracing::info!("Purged {} expired key tombstone(s)", n);
+                    record_run(&db, "purge_key_tombstones", n, None, None).await;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to purge key tombstones: {:?}", e);
+                    record_run(&db, "purge_key_tombstones", 0, None, Some(e.to_string())).await;
+                }
+            }
+
+            match purge_expired_revocations(&db).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    tracing::info!("Purged {} expired token revocation(s)", n);
+                    record_run(&db, "purge_revoked_tokens", n, None, None).await;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to purge token revocations: {:?}", e);
+                    record_run(&db, "purge_revoked_tokens", 0, None, Some(e.to_string())).await;
+                }
+            }
+
+            sweep_expirations(&db, &mail).await;
+        }
+    });
+}
+
+/// Everything of one user that expired in a sweep, so they get a single message per run.
+#[derive(Default)]
+pub struct ExpiryNotice {
+    pub memo_titles: Vec<String>,
+    pub sessions: u64,
+    pub pending_email: Option<String>,
+}
+
+/// Expires shares, sessions and pending email changes, records each job in `maintenance_runs`,
+/// then mails every affected user one summary. Returns the notices by user.
+pub async fn sweep_expirations(db: &DbConn, mail: &Mail) -> BTreeMap<Uuid, ExpiryNotice> {
+    let mut notices: BTreeMap<Uuid, ExpiryNotice> = BTreeMap::new();
+
+    match sweep_expired_shares(db).await {
+        Ok(shares) if shares.is_empty() => {}
+        Ok(shares) => {
+            let affected = shares.iter().map(|n| n.memo_titles.len() as u64).sum();
+            record_run(db, "expire_shares", affected, serde_json::to_string(&shares).ok(), None).await;
+            for share in shares {
+                notices.entry(share.user_id).or_default().memo_titles = share.memo_titles;
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to sweep expired shares: {:?}", e);
+            record_run(db, "expire_shares", 0, None, Some(e.to_string())).await;
+        }
+    }
+
+    match sweep_expired_sessions(db).await {
+        Ok(sessions) if sessions.is_empty() => {}
+        Ok(sessions) => {
+            let affected = sessions.values().sum();
+            record_run(db, "expire_sessions", affected, serde_json::to_string(&sessions).ok(), None).await;
+            for (user_id, count) in sessions {
+                notices.entry(user_id).or_default().sessions = count;
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to sweep expired sessions: {:?}", e);
+            record_run(db, "expire_sessions", 0, None, Some(e.to_string())).await;
+        }
+    }
+
+    match sweep_expired_email_changes(db).await {
+        Ok(changes) if changes.is_empty() => {}
+        Ok(changes) => {
+            let users: Vec<Uuid> = changes.iter().map(|(user_id, _)| *user_id).collect();
+            record_run(db, "expire_email_changes", changes.len() as u64, serde_json::to_string(&users).ok(), None).await;
+            for (user_id, pending_email) in changes {
+                notices.entry(user_id).or_default().pending_email = Some(pending_email);
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to sweep expired email changes: {:?}", e);
+            record_run(db, "expire_email_changes", 0, None, Some(e.to_string())).await;
+        }
+    }
+
+    notify_expired(db, mail, &notices).await;
+    notices
+}
+
+// Best effort: the sweeps are done either way, and a missed summary isn't worth a retry.
+async fn notify_expired(db: &DbConn, mail: &Mail, notices: &BTreeMap<Uuid, ExpiryNotice>) {
+    if notices.is_empty() {
+        return;
+    }
+    let recipients: Vec<(Uuid, String)> = match users::Entity::find()
+        .select_only()
+        .column(users::Column::Id)
+        .column(users::Column::Email)
+        .filter(users::Column::Id.is_in(notices.keys().copied()))
+        .into_tuple()
+        .all(db)
+        .await
+    {
+        Ok(recipients) => recipients,
+        Err(e) => {
+            tracing::error!("Failed to look up users to notify of expirations: {:?}", e);
+            return;
+        }
+    };
+
+    for (user_id, email) in recipients {
+        let Some(notice) = notices.get(&user_id) else { continue };
+        let digest = mailer::expiry_digest(&notice.memo_titles, notice.sessions, notice.pending_email.as_deref());
+        let _ = mail.deliver(&email, &digest).await;
+    }
+}
+
+/// Spawns the trash purge on its own interval. A failed run is logged and retried on the next tick.
+pub fn spawn_trash_purge(db: DbConn, store: AudioStore, retention: TrashRetention) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(retention.interval);
+        loop {
+            interval.tick().await;
+            let _ = purge_trash(&db, &store, retention).await;
+        }
+    });
+}
+
+/// Permanently deletes memos that have been in the trash longer than the retention period.
+/// Also run on demand by `POST /admin/maintenance/purge_trash`.
+pub async fn purge_trash(db: &DbConn, store: &AudioStore, retention: TrashRetention) -> Result<u64, DbErr> {
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::days(retention.days as i64);
+    let expired = Condition::all().add(voice_memos1::Column::DeletedAt.lt(cutoff));
+    let result = match stored_audio_keys(db, expired.clone()).await {
+        Ok(keys) => voice_memos1::Entity::delete_many()
+            .filter(expired)
+            .exec(db)
+            .await
+            .map(|res| (res, keys)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((res, keys)) => {
+            store.discard(keys).await;
+            if res.rows_affected > 0 {
+                tracing::info!(retention_days = retention.days, "Purged {} trashed memo(s)", res.rows_affected);
+                record_run(db, "purge_trash", res.rows_affected, None, None).await;
+            }
+            Ok(res.rows_affected)
+        }
+        Err(e) => {
+            tracing::error!("Failed to purge trashed memos: {:?}", e);
+            record_run(db, "purge_trash", 0, None, Some(e.to_string())).await;
+            Err(e)
+        }
+    }
+}
+
+// Runs that changed nothing aren't recorded, to keep the table small.
+async fn record_run(db: &DbConn, job: &str, affected: u64, summary: Option<String>, error: Option<String>) {
+    let run = maintenance_runs::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        job: Set(job.to_string()),
+        affected: Set(affected as i64),
+        summary: Set(summary),
+        error: Set(error),
+        ran_at: Set(Utc::now().naive_utc()),
+    };
+    if let Err(e) = run.insert(db).await {
+        tracing::error!("Failed to record maintenance run for {}: {:?}", job, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration as ChronoDuration, Utc};
+    use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
+    use uuid::Uuid;
+
+    use entity::{maintenance_runs, memo_shares, sessions, users};
+    use crate::test_support::{new_memo, recording_mail, seed_user, test_db};
+    use super::sweep_expirations;
+
+    async fn insert_session(db: &sea_orm::DbConn, user: &users::Model, expires_in: ChronoDuration) -> Uuid {
+        let now = Utc::now().naive_utc();
+        sessions::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            user_agent: Set(None),
+            client: Set("web".to_string()),
+            ip: Set(None),
+            created_at: Set(now),
+            last_active_at: Set(now),
+            revoked_at: Set(None),
+            expires_at: Set(now + expires_in),
+        }
+        .insert(db)
+        .await
+        .unwrap()
+        .id
+    }
+
+    async fn insert_share(db: &sea_orm::DbConn, user: &users::Model, title: &str, expires_in: ChronoDuration) -> Uuid {
+        let memo = new_memo(user, title).insert(db).await.unwrap();
+        let now = Utc::now().naive_utc();
+        memo_shares::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            memo_id: Set(memo.id),
+            slug: Set(Uuid::new_v4().simple().to_string()),
+            max_views: Set(None),
+            view_count: Set(0),
+            last_viewed_at: Set(None),
+            expires_at: Set(Some(now + expires_in)),
+            expired_at: Set(None),
+            created_at: Set(now),
+        }
+        .insert(db)
+        .await
+        .unwrap()
+        .id
+    }
+
+    async fn stage_email_change(db: &sea_orm::DbConn, user: &users::Model, expires_in: ChronoDuration) -> users::Model {
+        let mut active: users::ActiveModel = user.clone().into();
+        active.pending_email = Set(Some(format!("new.{}", user.email)));
+        active.email_change_token_hash = Set(Some(Uuid::new_v4().simple().to_string()));
+        active.email_change_expires_at = Set(Some(Utc::now().naive_utc() + expires_in));
+        active.update(db).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn sweeps_expired_rows_only_and_sends_one_message_per_user() {
+        let Some(db) = test_db().await else { return };
+        let started = Utc::now().naive_utc();
+        let (mail, outbox) = recording_mail();
+        let expired = ChronoDuration::minutes(-1);
+        let live = ChronoDuration::hours(1);
+
+        // One user with something expired in every sweep, another with nothing expired at all
+        let lapsed = seed_user(&db).await;
+        let lapsed_old_session = insert_session(&db, &lapsed, expired).await;
+        let lapsed_live_session = insert_session(&db, &lapsed, live).await;
+        let lapsed_old_share = insert_share(&db, &lapsed, "Old share", expired).await;
+        let lapsed_live_share = insert_share(&db, &lapsed, "Live share", live).await;
+        stage_email_change(&db, &lapsed, expired).await;
+
+        let current = seed_user(&db).await;
+        let current_session = insert_session(&db, &current, live).await;
+        let current_share = insert_share(&db, &current, "Current share", live).await;
+        let current = stage_email_change(&db, &current, live).await;
+
+        let notices = sweep_expirations(&db, &mail).await;
+
+        let session = |id| sessions::Entity::find_by_id(id).one(&db);
+        assert!(session(lapsed_old_session).await.unwrap().is_none());
+        assert!(session(lapsed_live_session).await.unwrap().is_some());
+        assert!(session(current_session).await.unwrap().is_some());
+
+        let share = |id| memo_shares::Entity::find_by_id(id).one(&db);
+        assert!(share(lapsed_old_share).await.unwrap().unwrap().expired_at.is_some());
+        assert!(share(lapsed_live_share).await.unwrap().unwrap().expired_at.is_none());
+        assert!(share(current_share).await.unwrap().unwrap().expired_at.is_none());
+
+        let lapsed_now = users::Entity::find_by_id(lapsed.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(lapsed_now.pending_email, None);
+        assert_eq!(lapsed_now.email_change_token_hash, None);
+        let current_now = users::Entity::find_by_id(current.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(current_now, current);
+
+        let notice = &notices[&lapsed.id];
+        assert_eq!(notice.memo_titles, vec!["Old share".to_string()]);
+        assert_eq!(notice.sessions, 1);
+        assert_eq!(notice.pending_email, Some(format!("new.{}", lapsed.email)));
+        assert!(!notices.contains_key(&current.id));
+
+        let sent = outbox.sent_to(&lapsed.email);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject, "Some of your Smart Memo access has expired");
+        assert!(sent[0].text.contains("Old share"));
+        assert!(sent[0].text.contains(&format!("new.{}", lapsed.email)));
+        assert!(outbox.sent_to(&current.email).is_empty());
+
+        for job in ["expire_shares", "expire_sessions", "expire_email_changes"] {
+            let run = maintenance_runs::Entity::find()
+                .filter(maintenance_runs::Column::Job.eq(job))
+                .filter(maintenance_runs::Column::RanAt.gte(started))
+                .one(&db)
+                .await
+                .unwrap();
+            assert!(run.is_some_and(|run| run.affected >= 1 && run.error.is_none()), "no run recorded for {}", job);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use poem::{Endpoint, EndpointExt};
+
+use crate::api::limits::positive_env;
+
+// --- Graceful Shutdown ---
+// On SIGTERM (sent by the platform on every deploy) or Ctrl-C the server stops accepting
+// connections and gives requests already running up to `SHUTDOWN_DRAIN_SECS` to finish.
+
+const DEFAULT_DRAIN_SECS: usize = 25;
+
+/// How long running requests get to finish, from `SHUTDOWN_DRAIN_SECS` (default 25, inside the
+/// usual 30 second window before the platform kills the process).
+pub fn drain_timeout() -> Result<Duration, String> {
+    Ok(Duration::from_secs(positive_env("SHUTDOWN_DRAIN_SECS", DEFAULT_DRAIN_SECS)? as u64))
+}
+
+/// Counts requests currently being handled, so shutdown can report what it waited for.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    active: Arc<AtomicUsize>,
+    at_shutdown: Arc<AtomicUsize>,
+}
+
+impl InFlight {
+    pub fn count(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Requests that were running when shutdown started and have finished since.
+    pub fn drained(&self) -> usize {
+        self.at_shutdown.load(Ordering::SeqCst).saturating_sub(self.count())
+    }
+
+    pub fn track<E: Endpoint + 'static>(&self, ep: E) -> impl Endpoint + use<E> {
+        let counter = self.active.clone();
+        ep.around(move |ep, req| {
+            let guard = InFlightGuard::enter(counter.clone());
+            async move {
+                let result = ep.call(req).await;
+                drop(guard);
+                result
+            }
+        })
+    }
+}
+
+// Decrements on drop, so requests that fail or are cancelled are counted out too.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn enter(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(counter)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolves on the first SIGTERM or Ctrl-C.
+pub async fn signal(in_flight: InFlight, drain: Duration) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    let running = in_flight.count();
+    in_flight.at_shutdown.store(running, Ordering::SeqCst);
+    tracing::info!(
+        in_flight = running,
+        drain_secs = drain.as_secs(),
+        "Shutdown started, no longer accepting connections"
+    );
+}
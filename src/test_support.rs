@@ -0,0 +1,270 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bcrypt::hash;
+use poem::endpoint::BoxEndpoint;
+use poem::test::TestClient;
+use poem::EndpointExt;
+use sea_orm::{ActiveModelTrait, ConnectOptions, Database, DatabaseConnection, Set};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use entity::{users, voice_memos1};
+use crate::api::challenge::SignupChallenge;
+use crate::api::crypto::Crypto;
+use crate::api::jwt;
+use crate::api::login_limit::LoginLimiter;
+use crate::api::mailer::{Mail, Mailer};
+use crate::api::storage::AudioStore;
+use crate::metrics::MetricsConfig;
+use crate::{app, db};
+
+// --- Test Support ---
+// Tests that need Postgres run against `TEST_DATABASE_URL` and skip when it is unset, so
+// `cargo test` still passes where no database is available. Every test seeds its own users,
+// which keeps them independent of each other and of whatever the database already holds.
+
+/// The password of every seeded user.
+pub const TEST_PASSWORD: &str = "Seeded-Passw0rd!";
+
+static MIGRATED: OnceCell<()> = OnceCell::const_new();
+
+/// A connection to `TEST_DATABASE_URL` with all migrations applied, or `None` when it is unset.
+pub async fn test_db() -> Option<DatabaseConnection> {
+    let url = env::var("TEST_DATABASE_URL").ok().filter(|url| !url.trim().is_empty())?;
+
+    let mut options = ConnectOptions::new(url);
+    options.max_connections(4).sqlx_logging(false);
+    let db = Database::connect(options).await.expect("TEST_DATABASE_URL is not reachable");
+
+    MIGRATED
+        .get_or_init(|| async { db::run_migrations(&db).await.expect("Test database migration failed") })
+        .await;
+    Some(db)
+}
+
+/// The application as `main` builds it, minus anything that talks to the outside world.
+pub fn test_app(db: &DatabaseConnection) -> TestClient<BoxEndpoint<'static>> {
+    test_app_with(db, SignupChallenge(None))
+}
+
+/// `test_app` with a signup challenge.
+pub fn test_app_with(db: &DatabaseConnection, challenge: SignupChallenge) -> TestClient<BoxEndpoint<'static>> {
+    build_test_app(db, challenge, Mail { mailer: Arc::new(NoMail), dev_mode: true })
+}
+
+/// `test_app` sending mail through `mail`, e.g. one from `recording_mail`.
+pub fn test_app_with_mail(db: &DatabaseConnection, mail: Mail) -> TestClient<BoxEndpoint<'static>> {
+    build_test_app(db, SignupChallenge(None), mail)
+}
+
+fn build_test_app(db: &DatabaseConnection, challenge: SignupChallenge, mail: Mail) -> TestClient<BoxEndpoint<'static>> {
+    jwt::init_test_keys();
+    let app = app::build(
+        db.clone(),
+        Crypto::with_key([7; 32]),
+        challenge,
+        LoginLimiter::from_env().expect("Invalid login rate limit configuration"),
+        mail,
+        AudioStore::default(),
+        MetricsConfig::from_env().expect("Invalid metrics configuration"),
+    );
+    TestClient::new(app.map_to_response().boxed())
+}
+
+/// A verified user with `TEST_PASSWORD` and a unique username and email.
+pub async fn seed_user(db: &DatabaseConnection) -> users::Model {
+    let username = format!("user_{}", &Uuid::new_v4().simple().to_string()[..12]);
+    users::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        email: Set(format!("{}@example.test", username)),
+        username: Set(username),
+        // Minimum cost; the hash only has to verify, not resist attack
+        password: Set(hash(TEST_PASSWORD, 4).expect("bcrypt can hash the test password")),
+        created_at: Set(chrono::Utc::now().naive_utc()),
+        role: Set("user".to_string()),
+        sandbox_ai: Set(false),
+        token_version: Set(0),
+        failed_attempts: Set(0),
+        locked_until: Set(None),
+        pending_email: Set(None),
+        email_change_token_hash: Set(None),
+        email_change_expires_at: Set(None),
+        suspended_at: Set(None),
+        suspension_reason: Set(None),
+        is_verified: Set(true),
+    }
+    .insert(db)
+    .await
+    .expect("Failed to seed user")
+}
+
+/// A memo of `user` with every column set, for a test to adjust before inserting it.
+pub fn new_memo(user: &users::Model, title: &str) -> voice_memos1::ActiveModel {
+    let now = chrono::Utc::now().naive_utc();
+    voice_memos1::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user.id),
+        title: Set(title.to_string()),
+        audio_blob: Set(None),
+        audio_key: Set(None),
+        transcript: Set(None),
+        translate: Set(None),
+        summary: Set(None),
+        tags: Set(None),
+        duration: Set("0:30".to_string()),
+        duration_seconds: Set(Some(30)),
+        created_at: Set(now),
+        updated_at: Set(now),
+        version: Set(1),
+        transcript_ai_generated: Set(false),
+        translate_ai_generated: Set(false),
+        summary_ai_generated: Set(false),
+        deleted_at: Set(None),
+        is_favorite: Set(false),
+        folder_id: Set(None),
+    }
+}
+
+/// Logs `user` in through `/api/login` and returns the session token.
+pub async fn login(client: &TestClient<BoxEndpoint<'static>>, user: &users::Model) -> String {
+    let resp = client
+        .post("/api/login")
+        .body_json(&serde_json::json!({ "identifier": user.username, "password": TEST_PASSWORD }))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().object().get("token").string().to_string()
+}
+
+/// The `Authorization` header value for a token.
+pub fn bearer(token: &str) -> String {
+    format!("Bearer {}", token)
+}
+
+/// A `Mail` that keeps every message in the returned outbox instead of sending it.
+pub fn recording_mail() -> (Mail, Arc<Outbox>) {
+    let outbox = Arc::new(Outbox::default());
+    (Mail { mailer: outbox.clone(), dev_mode: true }, outbox)
+}
+
+/// A message `recording_mail` kept.
+#[derive(Clone, Debug)]
+pub struct SentMail {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct Outbox(Mutex<Vec<SentMail>>);
+
+impl Outbox {
+    /// Every message sent to `to`, oldest first.
+    pub fn sent_to(&self, to: &str) -> Vec<SentMail> {
+        self.0.lock().unwrap().iter().filter(|mail| mail.to == to).cloned().collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for Outbox {
+    async fn send(&self, to: &str, subject: &str, _body_html: &str, body_text: &str) -> Result<(), String> {
+        self.0.lock().unwrap().push(SentMail { to: to.to_string(), subject: subject.to_string(), text: body_text.to_string() });
+        Ok(())
+    }
+}
+
+/// Drops every message; tests that check mail use `recording_mail`.
+struct NoMail;
+
+#[async_trait]
+impl Mailer for NoMail {
+    async fn send(&self, _to: &str, _subject: &str, _body_html: &str, _body_text: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// --- Golden Files ---
+// Snapshots of the public contract are checked in under `src/testdata`. A test whose output no
+// longer matches fails with every difference listed; `UPDATE_GOLDEN=1 cargo test` rewrites the
+// files instead, so a contract change is always a deliberate, reviewable diff.
+
+/// Asserts that `actual` equals the golden file `src/testdata/<name>`, or rewrites the file when
+/// `UPDATE_GOLDEN=1`.
+pub fn assert_golden(name: &str, actual: &Value) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/testdata").join(name);
+    if env::var("UPDATE_GOLDEN").is_ok_and(|update| update == "1") {
+        let text = serde_json::to_string_pretty(actual).expect("a JSON value serializes") + "\n";
+        fs::write(&path, text).unwrap_or_else(|e| panic!("Failed to write {}: {}", path.display(), e));
+        return;
+    }
+
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("{} is missing; run with UPDATE_GOLDEN=1 to create it", path.display()));
+    let expected: Value = serde_json::from_str(&text).unwrap_or_else(|e| panic!("{} is not valid JSON: {}", path.display(), e));
+    let mut differences = Vec::new();
+    diff_json("", &expected, actual, &mut differences);
+    assert!(
+        differences.is_empty(),
+        "{} no longer matches; rerun with UPDATE_GOLDEN=1 if the change is intended:\n{}",
+        name,
+        differences.join("\n")
+    );
+}
+
+// One line per difference, keyed by JSON pointer: `-` only in the golden, `+` only in the
+// output, `~` changed.
+fn diff_json(at: &str, expected: &Value, actual: &Value, differences: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let at = format!("{}/{}", at, key.replace('~', "~0").replace('/', "~1"));
+                match actual.get(key) {
+                    Some(actual) => diff_json(&at, value, actual, differences),
+                    None => differences.push(format!("- {}: {}", at, value)),
+                }
+            }
+            for (key, value) in actual.iter().filter(|(key, _)| !expected.contains_key(*key)) {
+                differences.push(format!("+ {}/{}: {}", at, key.replace('~', "~0").replace('/', "~1"), value));
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for index in 0..expected.len().max(actual.len()) {
+                let at = format!("{}/{}", at, index);
+                match (expected.get(index), actual.get(index)) {
+                    (Some(expected), Some(actual)) => diff_json(&at, expected, actual, differences),
+                    (Some(expected), None) => differences.push(format!("- {}: {}", at, expected)),
+                    (None, Some(actual)) => differences.push(format!("+ {}: {}", at, actual)),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if expected != actual => differences.push(format!("~ {}: {} -> {}", at, expected, actual)),
+        _ => {}
+    }
+}
+
+/// Makes a response comparable across runs: timestamps become `"<timestamp>"`, and each of
+/// `ids` (a seeded ID, username, ...) becomes its placeholder wherever it appears in a string.
+pub fn normalize_response(value: &mut Value, ids: &[(String, &str)]) {
+    match value {
+        Value::String(text) => {
+            let is_timestamp = chrono::DateTime::parse_from_rfc3339(text).is_ok()
+                || chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").is_ok();
+            if is_timestamp {
+                *text = "<timestamp>".to_string();
+                return;
+            }
+            for (id, placeholder) in ids {
+                *text = text.replace(id.as_str(), placeholder);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| normalize_response(item, ids)),
+        Value::Object(map) => map.values_mut().for_each(|item| normalize_response(item, ids)),
+        _ => {}
+    }
+}